@@ -0,0 +1,562 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tracing::warn;
+
+use crate::betfair::rpc::RpcResponse;
+use crate::clock::now_ms;
+use crate::redact;
+use crate::ui_error::UiErrorPayload;
+
+pub const AUDIT_FILE_NAME: &str = "audit.jsonl";
+/// Where `open` keeps a torn last line it cut off the log.
+pub const AUDIT_TAIL_FILE_NAME: &str = "audit.jsonl.tail";
+
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const DEFAULT_QUERY_LIMIT: usize = 500;
+
+/// Methods that change exposure on the exchange.
+pub fn is_transactional_method(service: &str, method: &str) -> bool {
+    service == "betting"
+        && matches!(
+            method,
+            "placeOrders" | "cancelOrders" | "replaceOrders" | "updateOrders"
+        )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditKind {
+    Request,
+    Response,
+    Error,
+    /// Something the app did on its own, e.g. a stop firing.
+    Trigger,
+}
+
+/// One line of `audit.jsonl`.
+///
+/// `hash` is SHA-256 over `prev_hash` followed by the entry serialized with an
+/// empty `hash`, so editing or dropping any line breaks every later link.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub ts_ms: u64,
+    /// `seq` of the request entry this entry belongs to.
+    pub call_id: u64,
+    pub kind: AuditKind,
+    pub service: String,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub market_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_ref: Option<String>,
+    /// Request params, response result (instruction reports) or error payload.
+    pub payload: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bf_correlation_id: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditQuery {
+    pub from_ms: Option<u64>,
+    pub to_ms: Option<u64>,
+    pub market_id: Option<String>,
+    pub method: Option<String>,
+    pub customer_ref: Option<String>,
+    /// Most recent N matching entries (default 500).
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, e: &AuditEntry) -> bool {
+        self.from_ms.is_none_or(|from| e.ts_ms >= from)
+            && self.to_ms.is_none_or(|to| e.ts_ms <= to)
+            && self
+                .market_id
+                .as_deref()
+                .is_none_or(|m| e.market_id.as_deref() == Some(m))
+            && self.method.as_deref().is_none_or(|m| e.method == m)
+            && self
+                .customer_ref
+                .as_deref()
+                .is_none_or(|r| e.customer_ref.as_deref() == Some(r))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditVerification {
+    pub entries: u64,
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_invalid_seq: Option<u64>,
+}
+
+struct Draft<'a> {
+    call_id: Option<u64>,
+    kind: AuditKind,
+    service: &'a str,
+    method: &'a str,
+    market_id: Option<String>,
+    customer_ref: Option<String>,
+    payload: serde_json::Value,
+    request_id: Option<String>,
+    bf_correlation_id: Option<String>,
+}
+
+struct Writer {
+    path: PathBuf,
+    file: File,
+    next_seq: u64,
+    last_hash: String,
+}
+
+/// Append-only, hash-chained JSONL log of transactional Betfair calls.
+///
+/// Stays inert until `open` is called with the app data directory; calls made
+/// before that are not recorded. From then on a transactional call that
+/// can't be recorded isn't sent (see `betfair::rpc::call`).
+pub struct AuditLog {
+    writer: Mutex<Option<Writer>>,
+    required: AtomicBool,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self {
+            writer: Mutex::new(None),
+            required: AtomicBool::new(false),
+        }
+    }
+
+    /// Opens (or creates) `<dir>/audit.jsonl` and resumes the chain from its
+    /// last entry. A torn last line (a crash mid-append) is cut off and kept in
+    /// `audit.jsonl.tail`.
+    pub fn open(&self, dir: &Path) -> std::io::Result<()> {
+        self.required.store(true, Ordering::Relaxed);
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(AUDIT_FILE_NAME);
+
+        let mut next_seq = 1;
+        let mut last_hash = GENESIS_HASH.to_string();
+        if path.exists() {
+            if let Some(entry) = recover_tail(&path)? {
+                next_seq = entry.seq + 1;
+                last_hash = entry.hash;
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        *self.writer.lock().unwrap_or_else(|e| e.into_inner()) = Some(Writer {
+            path,
+            file,
+            next_seq,
+            last_hash,
+        });
+        Ok(())
+    }
+
+    /// Whether every transactional call has to be recorded: once `open` was
+    /// called, even if it failed.
+    pub fn is_required(&self) -> bool {
+        self.required.load(Ordering::Relaxed)
+    }
+
+    pub fn path(&self) -> Option<PathBuf> {
+        self.writer
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|w| w.path.clone())
+    }
+
+    /// Records an outgoing call and returns its call id (0 if the log is not open).
+    pub fn record_request(&self, service: &str, method: &str, params: &serde_json::Value) -> u64 {
+        self.append(Draft {
+            call_id: None,
+            kind: AuditKind::Request,
+            service,
+            method,
+            market_id: string_field(params, "marketId"),
+            customer_ref: string_field(params, "customerRef"),
+            payload: redact::mask_json(params.clone()),
+            request_id: None,
+            bf_correlation_id: None,
+        })
+        .unwrap_or(0)
+    }
+
+    pub fn record_outcome(
+        &self,
+        call_id: u64,
+        service: &str,
+        method: &str,
+        outcome: &Result<RpcResponse, UiErrorPayload>,
+    ) {
+        let draft = match outcome {
+            Ok(resp) => Draft {
+                call_id: Some(call_id),
+                kind: AuditKind::Response,
+                service,
+                method,
+                market_id: string_field(&resp.result, "marketId"),
+                customer_ref: string_field(&resp.result, "customerRef"),
+                payload: redact::mask_json(resp.result.clone()),
+                request_id: resp.request_id.clone(),
+                bf_correlation_id: resp.bf_correlation_id.clone(),
+            },
+            Err(err) => {
+                let values = err.values.as_ref();
+                Draft {
+                    call_id: Some(call_id),
+                    kind: AuditKind::Error,
+                    service,
+                    method,
+                    market_id: None,
+                    customer_ref: None,
+                    payload: redact::mask_json(serde_json::to_value(err).unwrap_or_default()),
+                    request_id: values.and_then(|v| string_field(v, "requestId")),
+                    bf_correlation_id: values.and_then(|v| string_field(v, "bfCorrelationId")),
+                }
+            }
+        };
+        self.append(draft);
+    }
+
+    /// Records an automated decision (service `automation`) ahead of the
+    /// calls it leads to; those share its `customer_ref`.
+    pub fn record_trigger(
+        &self,
+        method: &str,
+        market_id: &str,
+        customer_ref: &str,
+        payload: serde_json::Value,
+    ) -> u64 {
+        self.append(Draft {
+            call_id: None,
+            kind: AuditKind::Trigger,
+            service: "automation",
+            method,
+            market_id: Some(market_id.to_string()),
+            customer_ref: Some(customer_ref.to_string()),
+            payload: redact::mask_json(payload),
+            request_id: None,
+            bf_correlation_id: None,
+        })
+        .unwrap_or(0)
+    }
+
+    fn append(&self, draft: Draft<'_>) -> Option<u64> {
+        let mut guard = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let writer = guard.as_mut()?;
+
+        let seq = writer.next_seq;
+        let mut entry = AuditEntry {
+            seq,
+            ts_ms: now_ms(),
+            call_id: draft.call_id.unwrap_or(seq),
+            kind: draft.kind,
+            service: draft.service.to_string(),
+            method: draft.method.to_string(),
+            market_id: draft.market_id,
+            customer_ref: draft.customer_ref,
+            payload: draft.payload,
+            request_id: draft.request_id,
+            bf_correlation_id: draft.bf_correlation_id,
+            prev_hash: writer.last_hash.clone(),
+            hash: String::new(),
+        };
+        entry.hash = entry_hash(&entry);
+
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!(error = %e, seq, "audit entry serialization failed");
+                return None;
+            }
+        };
+        if let Err(e) = writeln!(writer.file, "{line}").and_then(|_| writer.file.flush()) {
+            warn!(error = %e, seq, "audit write failed");
+            return None;
+        }
+
+        writer.next_seq = seq + 1;
+        writer.last_hash = entry.hash;
+        Some(seq)
+    }
+
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, UiErrorPayload> {
+        let limit = query.limit.unwrap_or(DEFAULT_QUERY_LIMIT);
+        let mut out: Vec<AuditEntry> = self
+            .read_all()?
+            .into_iter()
+            .filter(|e| query.matches(e))
+            .collect();
+        if out.len() > limit {
+            out.drain(..out.len() - limit);
+        }
+        Ok(out)
+    }
+
+    /// Walks the whole chain and reports the first entry whose link or hash is wrong.
+    pub fn verify(&self) -> Result<AuditVerification, UiErrorPayload> {
+        Ok(verify_entries(&self.read_all()?))
+    }
+
+    /// Copies the log to `dest` for compliance and checks the chain of the copy.
+    pub fn export(&self, dest: &Path) -> Result<AuditVerification, UiErrorPayload> {
+        let Some(path) = self.path() else {
+            return Err(UiErrorPayload::key("errors:audit.notOpen"));
+        };
+        {
+            // Hold the writer lock so no entry lands half-way through the copy.
+            let _writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+            std::fs::copy(&path, dest).map_err(|e| {
+                UiErrorPayload::with_values(
+                    "errors:audit.exportFailed",
+                    serde_json::json!({ "details": e.to_string() }),
+                )
+            })?;
+        }
+        Ok(verify_entries(&read_entries(dest)?))
+    }
+
+    fn read_all(&self) -> Result<Vec<AuditEntry>, UiErrorPayload> {
+        let Some(path) = self.path() else {
+            return Err(UiErrorPayload::key("errors:audit.notOpen"));
+        };
+        read_entries(&path)
+    }
+}
+
+/// Last entry of the log at `path`. Lines after it that don't parse (a torn
+/// append) are moved to `audit.jsonl.tail`, so appends continue on a line of
+/// their own.
+fn recover_tail(path: &Path) -> std::io::Result<Option<AuditEntry>> {
+    let bytes = std::fs::read(path)?;
+    let mut end = bytes.len();
+    let (keep, last) = loop {
+        let trimmed = bytes[..end].trim_ascii_end().len();
+        if trimmed == 0 {
+            break (0, None);
+        }
+        let start = bytes[..trimmed]
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .map_or(0, |i| i + 1);
+        if let Ok(entry) = serde_json::from_slice::<AuditEntry>(&bytes[start..trimmed]) {
+            break (trimmed, Some(entry));
+        }
+        end = start;
+    };
+    let dropped = &bytes[keep..];
+    if dropped.trim_ascii().is_empty() {
+        if keep > 0 && !dropped.starts_with(b"\n") {
+            OpenOptions::new()
+                .append(true)
+                .open(path)?
+                .write_all(b"\n")?;
+        }
+        return Ok(last);
+    }
+    warn!(
+        bytes = dropped.len(),
+        seq = last.as_ref().map(|e| e.seq),
+        "audit log ends in a torn entry; cut off"
+    );
+    let tail = path.with_file_name(AUDIT_TAIL_FILE_NAME);
+    let mut kept = OpenOptions::new().create(true).append(true).open(tail)?;
+    kept.write_all(dropped)?;
+    kept.write_all(b"\n")?;
+    let file = OpenOptions::new().write(true).open(path)?;
+    file.set_len(keep as u64)?;
+    if keep > 0 {
+        OpenOptions::new()
+            .append(true)
+            .open(path)?
+            .write_all(b"\n")?;
+    }
+    Ok(last)
+}
+
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, UiErrorPayload> {
+    let file = File::open(path).map_err(|e| UiErrorPayload::unexpected(e.to_string()))?;
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            serde_json::from_str::<AuditEntry>(&l)
+                .map_err(|e| UiErrorPayload::unexpected(format!("audit parse failed: {e}")))
+        })
+        .collect()
+}
+
+fn verify_entries(entries: &[AuditEntry]) -> AuditVerification {
+    let mut prev = GENESIS_HASH;
+    for e in entries {
+        if e.prev_hash != prev || entry_hash(e) != e.hash {
+            return AuditVerification {
+                entries: entries.len() as u64,
+                valid: false,
+                first_invalid_seq: Some(e.seq),
+            };
+        }
+        prev = &e.hash;
+    }
+    AuditVerification {
+        entries: entries.len() as u64,
+        valid: true,
+        first_invalid_seq: None,
+    }
+}
+
+fn entry_hash(entry: &AuditEntry) -> String {
+    let mut unsigned = entry.clone();
+    unsigned.hash = String::new();
+    let body = serde_json::to_string(&unsigned).unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(entry.prev_hash.as_bytes());
+    hasher.update(body.as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn string_field(v: &serde_json::Value, key: &str) -> Option<String> {
+    v.get(key).and_then(|x| x.as_str()).map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("audit-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn record_call(log: &AuditLog, market_id: &str) -> u64 {
+        let params = serde_json::json!({ "marketId": market_id, "customerRef": "ref-1" });
+        let call_id = log.record_request("betting", "placeOrders", &params);
+        let outcome = Err(UiErrorPayload::key("errors:orders.rejected"));
+        log.record_outcome(call_id, "betting", "placeOrders", &outcome);
+        call_id
+    }
+
+    #[test]
+    fn entries_chain_from_the_genesis_hash() {
+        let dir = temp_dir("chain");
+        let log = AuditLog::new();
+        log.open(&dir).unwrap();
+        assert_eq!(record_call(&log, "1.1"), 1);
+        assert_eq!(
+            log.record_trigger("stopTriggered", "1.1", "stop-1", serde_json::json!({})),
+            3
+        );
+
+        let entries = log.query(&AuditQuery::default()).unwrap();
+        let seqs: Vec<(u64, u64, AuditKind)> =
+            entries.iter().map(|e| (e.seq, e.call_id, e.kind)).collect();
+        assert_eq!(
+            seqs,
+            [
+                (1, 1, AuditKind::Request),
+                (2, 1, AuditKind::Error),
+                (3, 3, AuditKind::Trigger),
+            ]
+        );
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(entries[1].prev_hash, entries[0].hash);
+        assert_eq!(entries[2].prev_hash, entries[1].hash);
+        assert_eq!(entries[0].market_id.as_deref(), Some("1.1"));
+        let v = log.verify().unwrap();
+        assert!(v.valid);
+        assert_eq!(v.entries, 3);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn verify_finds_a_tampered_entry() {
+        let dir = temp_dir("tamper");
+        let log = AuditLog::new();
+        log.open(&dir).unwrap();
+        record_call(&log, "1.1");
+        record_call(&log, "1.2");
+
+        let path = dir.join(AUDIT_FILE_NAME);
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, text.replacen("\"1.2\"", "\"1.3\"", 1)).unwrap();
+        let v = log.verify().unwrap();
+        assert!(!v.valid);
+        assert_eq!(v.first_invalid_seq, Some(3));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reopening_cuts_off_a_torn_last_line() {
+        let dir = temp_dir("torn");
+        let log = AuditLog::new();
+        log.open(&dir).unwrap();
+        record_call(&log, "1.1");
+        let path = dir.join(AUDIT_FILE_NAME);
+        let torn = r#"{"seq":3,"tsMs":17"#;
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(torn.as_bytes())
+            .unwrap();
+
+        let log = AuditLog::new();
+        log.open(&dir).unwrap();
+        assert_eq!(record_call(&log, "1.2"), 3);
+        let v = log.verify().unwrap();
+        assert!(v.valid);
+        assert_eq!(v.entries, 4);
+        let tail = std::fs::read_to_string(dir.join(AUDIT_TAIL_FILE_NAME)).unwrap();
+        assert_eq!(tail.trim(), torn);
+
+        // A clean reopen leaves the log alone.
+        let log = AuditLog::new();
+        log.open(&dir).unwrap();
+        assert_eq!(log.verify().unwrap().entries, 4);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_log_that_failed_to_open_records_nothing() {
+        let dir = temp_dir("blocked");
+        std::fs::create_dir_all(&dir).unwrap();
+        // A file where the directory should be.
+        let blocked = dir.join("file");
+        std::fs::write(&blocked, "").unwrap();
+        let log = AuditLog::new();
+        assert!(!log.is_required());
+        assert!(log.open(&blocked).is_err());
+        assert!(log.is_required());
+        assert_eq!(record_call(&log, "1.1"), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,421 @@
+//! Simulated exchange for backtests. Orders never move the recorded book:
+//! they take what the book offers when they arrive, then rest behind the
+//! size that was already shown at their price and fill from traded volume
+//! once that queue is gone. What an order took stays in the recorded book, so
+//! each order remembers it and only takes size beyond it later.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::market::cache::{runner_key, LevelLadder, MarketState, RunnerKey, RunnerState};
+use crate::market::ladder::PriceLadder;
+use crate::orders::cache::RunnerOrdersSnapshot;
+use crate::orders::place::{OrderSide, Persistence};
+use crate::stream::ocm::{OrderStatus, Side, UnmatchedOrder};
+use crate::ticks::price_key;
+
+const EPSILON: f64 = 1e-9;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fill {
+    pub at_ms: u64,
+    pub market_id: String,
+    pub selection_id: u64,
+    pub handicap: f64,
+    pub bet_id: String,
+    pub side: OrderSide,
+    pub price: f64,
+    pub size: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimOrder {
+    pub bet_id: String,
+    pub market_id: String,
+    pub selection_id: u64,
+    pub handicap: f64,
+    pub side: OrderSide,
+    pub price: f64,
+    pub size: f64,
+    pub persistence: Persistence,
+    pub strategy_ref: String,
+    pub placed_at_ms: u64,
+    /// (price, size) of every fill.
+    pub fills: Vec<(f64, f64)>,
+    pub first_fill_ms: Option<u64>,
+    pub size_cancelled: f64,
+    pub size_lapsed: f64,
+    pub size_voided: f64,
+    /// Size shown at our price when we joined, less what traded or was
+    /// cancelled since.
+    queue_ahead: f64,
+    /// Traded volume at our price when last looked at.
+    traded_seen: f64,
+    /// Size taken from the book's offers, by price key.
+    taken: BTreeMap<i64, f64>,
+}
+
+impl SimOrder {
+    pub fn size_matched(&self) -> f64 {
+        self.fills.iter().map(|(_, s)| s).sum()
+    }
+
+    pub fn size_remaining(&self) -> f64 {
+        let done = self.size_matched() + self.size_cancelled + self.size_lapsed + self.size_voided;
+        (self.size - done).max(0.0)
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.size_remaining() > EPSILON
+    }
+
+    fn key(&self) -> RunnerKey {
+        runner_key(self.selection_id, self.handicap)
+    }
+
+    fn fill(&mut self, at_ms: u64, price: f64, size: f64) -> Option<Fill> {
+        let size = size.min(self.size_remaining());
+        if size <= EPSILON {
+            return None;
+        }
+        self.fills.push((price, size));
+        self.first_fill_ms.get_or_insert(at_ms);
+        Some(Fill {
+            at_ms,
+            market_id: self.market_id.clone(),
+            selection_id: self.selection_id,
+            handicap: self.handicap,
+            bet_id: self.bet_id.clone(),
+            side: self.side,
+            price,
+            size,
+        })
+    }
+
+    /// Takes the offers that cross the order, less what it took from them
+    /// before; at `fill_at`, or at each offer's price when unset.
+    fn take(&mut self, at_ms: u64, runner: &RunnerState, fill_at: Option<f64>) -> Vec<Fill> {
+        let shown = match self.side {
+            OrderSide::Back => offers(&runner.atb, &runner.batb),
+            OrderSide::Lay => offers(&runner.atl, &runner.batl),
+        };
+        // Size gone from the book was taken or pulled by someone else too.
+        self.taken.retain(|key, size| {
+            let left = shown
+                .iter()
+                .find(|(p, _)| price_key(*p) == *key)
+                .map_or(0.0, |(_, s)| *s);
+            *size = size.min(left);
+            *size > EPSILON
+        });
+        let mut fills = Vec::new();
+        for (price, size) in takeable(runner, self.side, self.price) {
+            let key = price_key(price);
+            let taken = self.taken.get(&key).copied().unwrap_or(0.0);
+            if let Some(fill) = self.fill(at_ms, fill_at.unwrap_or(price), size - taken) {
+                *self.taken.entry(key).or_default() += fill.size;
+                fills.push(fill);
+            }
+        }
+        fills
+    }
+
+    /// The order as the order stream would show it.
+    fn unmatched(&self) -> UnmatchedOrder {
+        let matched = self.size_matched();
+        UnmatchedOrder {
+            id: self.bet_id.clone(),
+            p: self.price,
+            s: self.size,
+            bsp: None,
+            side: match self.side {
+                OrderSide::Back => Side::Back,
+                OrderSide::Lay => Side::Lay,
+            },
+            status: if self.is_executable() {
+                OrderStatus::Executable
+            } else {
+                OrderStatus::ExecutionComplete
+            },
+            pt: Some(self.persistence.stream_code().to_string()),
+            ot: Some("L".to_string()),
+            pd: Some(self.placed_at_ms),
+            md: None,
+            avp: (matched > 0.0)
+                .then(|| self.fills.iter().map(|(p, s)| p * s).sum::<f64>() / matched),
+            sm: matched,
+            sr: self.size_remaining(),
+            sl: self.size_lapsed,
+            sc: self.size_cancelled,
+            sv: self.size_voided,
+            rfo: None,
+            rfs: Some(self.strategy_ref.clone()),
+        }
+    }
+}
+
+fn offers(ladder: &PriceLadder, best: &LevelLadder) -> Vec<(f64, f64)> {
+    if ladder.is_empty() {
+        best.levels()
+            .into_iter()
+            .map(|l| (l.price, l.size))
+            .collect()
+    } else {
+        ladder.iter().collect()
+    }
+}
+
+/// Offers an order on `side` at `price` would match now, best first.
+fn takeable(runner: &RunnerState, side: OrderSide, price: f64) -> Vec<(f64, f64)> {
+    match side {
+        OrderSide::Back => {
+            let mut o = offers(&runner.atb, &runner.batb);
+            o.retain(|(p, _)| *p >= price - EPSILON);
+            o.sort_by(|a, b| b.0.total_cmp(&a.0));
+            o
+        }
+        OrderSide::Lay => {
+            let mut o = offers(&runner.atl, &runner.batl);
+            o.retain(|(p, _)| *p <= price + EPSILON);
+            o.sort_by(|a, b| a.0.total_cmp(&b.0));
+            o
+        }
+    }
+}
+
+/// Unmatched size on our own side at `price`, i.e. the queue we'd join:
+/// resting backs show as available to lay, resting lays as available to back.
+fn queue_at(runner: &RunnerState, side: OrderSide, price: f64) -> f64 {
+    let shown = match side {
+        OrderSide::Back => offers(&runner.atl, &runner.batl),
+        OrderSide::Lay => offers(&runner.atb, &runner.batb),
+    };
+    shown
+        .iter()
+        .find(|(p, _)| (p - price).abs() < EPSILON)
+        .map_or(0.0, |(_, s)| *s)
+}
+
+#[derive(Debug, Default)]
+pub struct SimExchange {
+    /// Bet id -> order; ids sort in placement order.
+    orders: BTreeMap<String, SimOrder>,
+    next_id: u64,
+}
+
+impl SimExchange {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn order(&self, bet_id: &str) -> Option<&SimOrder> {
+        self.orders.get(bet_id)
+    }
+
+    pub fn orders(&self) -> impl Iterator<Item = &SimOrder> {
+        self.orders.values()
+    }
+
+    /// Places a limit order: matches what it can straight away, the rest
+    /// joins the back of the queue at its price.
+    #[allow(clippy::too_many_arguments)]
+    pub fn place(
+        &mut self,
+        now_ms: u64,
+        market: &MarketState,
+        selection_id: u64,
+        handicap: f64,
+        side: OrderSide,
+        price: f64,
+        size: f64,
+        persistence: Persistence,
+        strategy_ref: &str,
+    ) -> (String, Vec<Fill>) {
+        self.next_id += 1;
+        let bet_id = format!("sim-{:08}", self.next_id);
+        let mut order = SimOrder {
+            bet_id: bet_id.clone(),
+            market_id: market.market_id.clone(),
+            selection_id,
+            handicap,
+            side,
+            price,
+            size,
+            persistence,
+            strategy_ref: strategy_ref.to_string(),
+            placed_at_ms: now_ms,
+            fills: Vec::new(),
+            first_fill_ms: None,
+            size_cancelled: 0.0,
+            size_lapsed: 0.0,
+            size_voided: 0.0,
+            queue_ahead: 0.0,
+            traded_seen: 0.0,
+            taken: BTreeMap::new(),
+        };
+        let mut fills = Vec::new();
+        if let Some(runner) = market.runners.get(&order.key()) {
+            fills = order.take(now_ms, runner, None);
+            order.queue_ahead = queue_at(runner, side, price);
+            order.traded_seen = runner.trd.size_at(price).unwrap_or(0.0);
+        }
+        self.orders.insert(bet_id.clone(), order);
+        (bet_id, fills)
+    }
+
+    /// Cancels the unmatched part; false if nothing was left to cancel.
+    pub fn cancel(&mut self, bet_id: &str) -> bool {
+        let Some(order) = self.orders.get_mut(bet_id) else {
+            return false;
+        };
+        let remaining = order.size_remaining();
+        order.size_cancelled += remaining;
+        remaining > EPSILON
+    }
+
+    /// Cancels the unmatched part and places it again at `new_price`.
+    pub fn replace(
+        &mut self,
+        now_ms: u64,
+        market: &MarketState,
+        bet_id: &str,
+        new_price: f64,
+    ) -> Option<(String, Vec<Fill>)> {
+        let order = self.orders.get(bet_id)?.clone();
+        let remaining = order.size_remaining();
+        if !self.cancel(bet_id) {
+            return None;
+        }
+        Some(self.place(
+            now_ms,
+            market,
+            order.selection_id,
+            order.handicap,
+            order.side,
+            new_price,
+            remaining,
+            order.persistence,
+            &order.strategy_ref,
+        ))
+    }
+
+    /// Moves resting orders of `market` along after a frame: a book that
+    /// crossed them fills them at their price, then traded volume at their
+    /// price eats the queue ahead and fills the rest.
+    pub fn on_market(&mut self, now_ms: u64, market: &MarketState) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        for order in self.orders.values_mut() {
+            if order.market_id != market.market_id || !order.is_executable() {
+                continue;
+            }
+            let Some(runner) = market.runners.get(&order.key()) else {
+                continue;
+            };
+            let price = order.price;
+            fills.extend(order.take(now_ms, runner, Some(price)));
+
+            let traded = runner.trd.size_at(order.price).unwrap_or(0.0);
+            let delta = (traded - order.traded_seen).max(0.0);
+            order.traded_seen = traded;
+            let ahead = order.queue_ahead.min(delta);
+            order.queue_ahead -= ahead;
+            fills.extend(order.fill(now_ms, order.price, delta - ahead));
+
+            // Size ahead of us that was cancelled leaves the queue too.
+            order.queue_ahead = order
+                .queue_ahead
+                .min(queue_at(runner, order.side, order.price));
+        }
+        fills
+    }
+
+    /// Bets on a withdrawn runner are void, matched or not.
+    pub fn void_runner(&mut self, market_id: &str, selection_id: u64, handicap: f64) -> bool {
+        let key = runner_key(selection_id, handicap);
+        let mut changed = false;
+        for order in self.orders.values_mut() {
+            if order.market_id != market_id || order.key() != key {
+                continue;
+            }
+            let matched = order.size_matched();
+            order.size_cancelled += order.size_remaining();
+            order.size_voided += matched;
+            order.fills.clear();
+            order.first_fill_ms = None;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Lapses unmatched orders at the off (LAPSE persistence only) or when
+    /// the market closes (everything). Orders to convert to SP at the off
+    /// lapse too: the backtester doesn't model the SP reconciliation.
+    pub fn lapse(&mut self, market_id: &str, closed: bool) -> bool {
+        let mut changed = false;
+        for order in self.orders.values_mut() {
+            if order.market_id != market_id || !order.is_executable() {
+                continue;
+            }
+            if closed || order.persistence != Persistence::Persist {
+                order.size_lapsed += order.size_remaining();
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Executable bet ids of a market.
+    pub fn unmatched_ids(&self, market_id: &str) -> Vec<String> {
+        self.orders
+            .values()
+            .filter(|o| o.market_id == market_id && o.is_executable())
+            .map(|o| o.bet_id.clone())
+            .collect()
+    }
+
+    /// Orders of a market in the shape the position engine takes; `None`
+    /// if there are none.
+    pub fn snapshots(&self, market_id: &str) -> Option<Vec<RunnerOrdersSnapshot>> {
+        let mut runners: BTreeMap<RunnerKey, RunnerOrdersSnapshot> = BTreeMap::new();
+        let mut matched: BTreeMap<(RunnerKey, bool, i64), (f64, f64)> = BTreeMap::new();
+        for order in self.orders.values().filter(|o| o.market_id == market_id) {
+            let key = order.key();
+            let runner = runners.entry(key).or_insert_with(|| RunnerOrdersSnapshot {
+                key,
+                unmatched: Vec::new(),
+                matched_backs: PriceLadder::default(),
+                matched_lays: PriceLadder::default(),
+                matched_at: HashMap::new(),
+            });
+            if order.is_executable() {
+                runner.unmatched.push(order.unmatched());
+            }
+            for (price, size) in &order.fills {
+                let back = order.side == OrderSide::Back;
+                if let Some(at) = order.first_fill_ms {
+                    let first = runner
+                        .matched_at
+                        .entry((back, price_key(*price)))
+                        .or_insert(at);
+                    *first = (*first).min(at);
+                }
+                let entry = matched
+                    .entry((key, back, price_key(*price)))
+                    .or_insert((*price, 0.0));
+                entry.1 += size;
+            }
+        }
+        for ((key, back, _), (price, size)) in matched {
+            if let Some(runner) = runners.get_mut(&key) {
+                let ladder = if back {
+                    &mut runner.matched_backs
+                } else {
+                    &mut runner.matched_lays
+                };
+                ladder.apply(&[[price, size]]);
+            }
+        }
+        (!runners.is_empty()).then(|| runners.into_values().collect())
+    }
+}
@@ -0,0 +1,509 @@
+//! Offline backtests. Recorded mcm lines go through a `MarketCache` exactly
+//! as the live stream would, and a `Strategy` trades against
+//! `matching::SimExchange` with a fixed latency between deciding and
+//! reaching the exchange. No network and no app state; `src/bin/backtest.rs`
+//! runs one from the command line.
+
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::market::cache::{MarketCache, MarketState};
+use crate::market::lifecycle::LifecycleKind;
+use crate::orders::cache::RunnerOrdersSnapshot;
+use crate::orders::place::{limit_order_with, OrderSide};
+use crate::orders::position::{market_position, MarketPosition};
+use crate::risk::limits::{check_liability, order_liability, RiskLimits};
+use crate::strategy::{OrderIntent, Strategy, StrategyContext};
+use crate::stream::mcm::MarketChangeMessage;
+use crate::ui_error::UiErrorPayload;
+
+pub mod matching;
+
+use matching::{Fill, SimExchange};
+
+/// Placing from a home connection typically takes this long to reach the
+/// exchange.
+pub const DEFAULT_LATENCY_MS: u64 = 100;
+
+/// Recorded frames all come from one "connection".
+const CONN: u64 = 0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BacktestConfig {
+    /// Time from an intent to its arrival at the exchange.
+    pub latency_ms: u64,
+    /// Rate charged on each market's net winnings, e.g. 0.05.
+    pub commission: f64,
+    pub limits: RiskLimits,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            latency_ms: DEFAULT_LATENCY_MS,
+            commission: 0.0,
+            limits: RiskLimits::default(),
+        }
+    }
+}
+
+/// An intent the simulated exchange refused.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rejection {
+    pub at_ms: u64,
+    pub intent: OrderIntent,
+    pub error: UiErrorPayload,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnlPoint {
+    pub at_ms: u64,
+    /// Realised P&L of settled markets plus the green-up value of open ones.
+    pub pnl: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketReport {
+    pub market_id: String,
+    pub orders: usize,
+    pub fills: usize,
+    pub matched_back: f64,
+    pub matched_lay: f64,
+    /// Realised once settled, else the last green-up value.
+    pub pnl: f64,
+    pub settled: bool,
+    pub commission: f64,
+    pub net: f64,
+    pub position: MarketPosition,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BacktestReport {
+    pub strategy_ref: String,
+    pub latency_ms: u64,
+    pub frames: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_pt: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_pt: Option<u64>,
+    pub markets: Vec<MarketReport>,
+    pub fills: Vec<Fill>,
+    pub curve: Vec<PnlPoint>,
+    pub rejected: Vec<Rejection>,
+    pub pnl: f64,
+    pub commission: f64,
+    pub net: f64,
+    pub strategy_state: serde_json::Value,
+}
+
+fn money(x: f64) -> f64 {
+    (x * 100.0).round() / 100.0
+}
+
+fn rejected(code: &str) -> UiErrorPayload {
+    UiErrorPayload::with_values("errors:orders.rejected", json!({ "code": code }))
+}
+
+fn check_open(market: &MarketState) -> Result<(), UiErrorPayload> {
+    let status = market
+        .market_definition
+        .as_ref()
+        .and_then(|d| d.get("status"))
+        .and_then(|v| v.as_str());
+    match status {
+        Some("OPEN") => Ok(()),
+        Some("SUSPENDED") => Err(rejected("MARKET_SUSPENDED")),
+        _ => Err(rejected("MARKET_NOT_OPEN_FOR_BETTING")),
+    }
+}
+
+struct Pending {
+    arrives_at_ms: u64,
+    intent: OrderIntent,
+}
+
+pub struct Backtest {
+    config: BacktestConfig,
+    strategy_ref: String,
+    strategy: Box<dyn Strategy>,
+    cache: MarketCache,
+    exchange: SimExchange,
+    pending: VecDeque<Pending>,
+    frames: u64,
+    first_pt: Option<u64>,
+    now_ms: u64,
+    fills: Vec<Fill>,
+    rejected: Vec<Rejection>,
+    /// Market -> latest P&L, for the curve.
+    pnl: BTreeMap<String, f64>,
+    curve: Vec<PnlPoint>,
+}
+
+impl Backtest {
+    pub fn new(config: BacktestConfig, strategy_ref: &str, strategy: Box<dyn Strategy>) -> Self {
+        let mut backtest = Self {
+            config,
+            strategy_ref: strategy_ref.to_string(),
+            strategy,
+            cache: MarketCache::new(),
+            exchange: SimExchange::new(),
+            pending: VecDeque::new(),
+            frames: 0,
+            first_pt: None,
+            now_ms: 0,
+            fills: Vec::new(),
+            rejected: Vec::new(),
+            pnl: BTreeMap::new(),
+            curve: Vec::new(),
+        };
+        let ctx = StrategyContext {
+            now_ms: 0,
+            strategy_ref,
+            position: None,
+        };
+        let intents = backtest.strategy.on_start(&ctx);
+        backtest.queue(intents);
+        backtest
+    }
+
+    /// Feeds one recorded line; anything but an mcm frame is skipped.
+    pub fn feed(&mut self, line: &str) {
+        if let Some(msg) = MarketChangeMessage::parse(line) {
+            self.apply(&msg);
+        }
+    }
+
+    pub fn apply(&mut self, msg: &MarketChangeMessage) {
+        let now = msg.pt.unwrap_or(self.now_ms);
+        // Files of a bundle needn't be in time order: what is still in
+        // flight reaches the exchange before the clock goes back.
+        let mut touched = if now < self.now_ms {
+            self.deliver(u64::MAX)
+        } else {
+            BTreeSet::new()
+        };
+        self.now_ms = now;
+        self.first_pt.get_or_insert(now);
+        self.frames += 1;
+
+        // Intents that reached the exchange before this frame see the book
+        // as it was.
+        touched.extend(self.deliver(now));
+
+        self.cache.apply(CONN, msg);
+        let market_ids: BTreeSet<String> = msg.mc.iter().map(|mc| mc.id.clone()).collect();
+
+        for r in self.cache.take_removals() {
+            let removal = &r.removal;
+            if self
+                .exchange
+                .void_runner(&r.market_id, removal.selection_id, removal.handicap)
+            {
+                touched.insert(r.market_id.clone());
+            }
+        }
+        for id in &market_ids {
+            if let Some(market) = self.cache.market(id) {
+                let fills = self.exchange.on_market(now, &market);
+                if !fills.is_empty() {
+                    touched.insert(id.clone());
+                    self.fills.extend(fills);
+                }
+            }
+        }
+
+        for event in self.cache.take_lifecycle() {
+            let lapsed = match event.kind {
+                LifecycleKind::InPlay => self.exchange.lapse(&event.market_id, false),
+                LifecycleKind::Closed => self.exchange.lapse(&event.market_id, true),
+                _ => false,
+            };
+            if lapsed {
+                touched.insert(event.market_id.clone());
+            }
+            if self.strategy.wants_market(&event.market_id) {
+                let market = self.cache.market(&event.market_id);
+                let position = self.position(&event.market_id, market.as_ref());
+                let ctx = StrategyContext {
+                    now_ms: now,
+                    strategy_ref: &self.strategy_ref,
+                    position: position.as_ref(),
+                };
+                let intents = self.strategy.on_lifecycle(&ctx, &event);
+                self.queue(intents);
+            }
+        }
+
+        for id in &market_ids {
+            if !self.strategy.wants_market(id) {
+                continue;
+            }
+            let Some(market) = self.cache.market(id) else {
+                continue;
+            };
+            let position = self.position(id, Some(&market));
+            let ctx = StrategyContext {
+                now_ms: now,
+                strategy_ref: &self.strategy_ref,
+                position: position.as_ref(),
+            };
+            let intents = self.strategy.on_market(&ctx, &market);
+            self.queue(intents);
+        }
+
+        for id in &touched {
+            let Some(orders) = self.exchange.snapshots(id) else {
+                continue;
+            };
+            if !self.strategy.wants_market(id) {
+                continue;
+            }
+            let market = self.cache.market(id);
+            let position = market_position(id, market.as_ref(), &orders);
+            let ctx = StrategyContext {
+                now_ms: now,
+                strategy_ref: &self.strategy_ref,
+                position: Some(&position),
+            };
+            let intents = self.strategy.on_orders(&ctx, &orders);
+            self.queue(intents);
+        }
+
+        self.mark(now, market_ids.iter().chain(&touched));
+    }
+
+    fn position(&self, market_id: &str, market: Option<&MarketState>) -> Option<MarketPosition> {
+        let orders = self.exchange.snapshots(market_id)?;
+        Some(market_position(market_id, market, &orders))
+    }
+
+    fn queue(&mut self, intents: Vec<OrderIntent>) {
+        let arrives_at_ms = self.now_ms + self.config.latency_ms;
+        self.pending
+            .extend(intents.into_iter().map(|intent| Pending {
+                arrives_at_ms,
+                intent,
+            }));
+    }
+
+    /// Sends intents due by `now`; returns the markets whose orders changed.
+    fn deliver(&mut self, now: u64) -> BTreeSet<String> {
+        let mut touched = BTreeSet::new();
+        while self.pending.front().is_some_and(|p| p.arrives_at_ms <= now) {
+            let Some(Pending {
+                arrives_at_ms,
+                intent,
+            }) = self.pending.pop_front()
+            else {
+                break;
+            };
+            match self.execute(arrives_at_ms, &intent) {
+                Ok(fills) => {
+                    touched.insert(intent.market_id().to_string());
+                    self.fills.extend(fills);
+                }
+                Err(error) => self.rejected.push(Rejection {
+                    at_ms: arrives_at_ms,
+                    intent,
+                    error,
+                }),
+            }
+        }
+        touched
+    }
+
+    fn execute(&mut self, now: u64, intent: &OrderIntent) -> Result<Vec<Fill>, UiErrorPayload> {
+        let market_id = intent.market_id();
+        let market = self
+            .cache
+            .market(market_id)
+            .ok_or_else(|| rejected("INVALID_MARKET_ID"))?;
+        match intent {
+            OrderIntent::Place {
+                selection_id,
+                handicap,
+                side,
+                price,
+                size,
+                persistence,
+                ..
+            } => {
+                check_open(&market)?;
+                if !(size.is_finite() && *size > 0.0) {
+                    return Err(UiErrorPayload::with_values(
+                        "errors:validation.invalidSize",
+                        json!({ "size": size }),
+                    ));
+                }
+                let price = market.tick_ladder.validate(*price)?;
+                let params = json!({
+                    "instructions": [limit_order_with(
+                        *selection_id, *handicap, *side, *size, price, *persistence, None,
+                    )],
+                });
+                let open: f64 = self
+                    .exchange
+                    .snapshots(market_id)
+                    .iter()
+                    .flatten()
+                    .flat_map(|r| &r.unmatched)
+                    .map(|o| order_liability(o, &market.tick_ladder))
+                    .sum();
+                check_liability(&self.config.limits, &params, &market.tick_ladder, open)?;
+                let (_, fills) = self.exchange.place(
+                    now,
+                    &market,
+                    *selection_id,
+                    *handicap,
+                    *side,
+                    price,
+                    *size,
+                    *persistence,
+                    &self.strategy_ref,
+                );
+                Ok(fills)
+            }
+            OrderIntent::Cancel { bet_id, .. } => {
+                let bet_ids = match bet_id {
+                    Some(id) => {
+                        self.check_owned(market_id, id)?;
+                        vec![id.clone()]
+                    }
+                    None => self.exchange.unmatched_ids(market_id),
+                };
+                for id in bet_ids {
+                    self.exchange.cancel(&id);
+                }
+                Ok(Vec::new())
+            }
+            OrderIntent::Replace {
+                bet_id, new_price, ..
+            } => {
+                self.check_owned(market_id, bet_id)?;
+                check_open(&market)?;
+                let price = market.tick_ladder.validate(*new_price)?;
+                let (_, fills) = self
+                    .exchange
+                    .replace(now, &market, bet_id, price)
+                    .ok_or_else(|| rejected("BET_TAKEN_OR_LAPSED"))?;
+                Ok(fills)
+            }
+        }
+    }
+
+    fn check_owned(&self, market_id: &str, bet_id: &str) -> Result<(), UiErrorPayload> {
+        if self
+            .exchange
+            .order(bet_id)
+            .is_some_and(|o| o.market_id == market_id)
+        {
+            return Ok(());
+        }
+        Err(UiErrorPayload::with_values(
+            "errors:strategies.notOwnBet",
+            json!({ "betId": bet_id }),
+        ))
+    }
+
+    /// Adds a curve point when the P&L of the given markets moved it.
+    fn mark<'a>(&mut self, now: u64, market_ids: impl Iterator<Item = &'a String>) {
+        let mut changed = false;
+        for id in market_ids {
+            let market = self.cache.market(id);
+            let Some(position) = self.position(id, market.as_ref()) else {
+                continue;
+            };
+            let Some(pnl) = position.realised.or(position.green_profit) else {
+                continue;
+            };
+            let last = self.pnl.insert(id.clone(), pnl);
+            changed |= last != Some(pnl);
+        }
+        if changed {
+            self.curve.push(PnlPoint {
+                at_ms: now,
+                pnl: money(self.pnl.values().sum()),
+            });
+        }
+    }
+
+    /// Stops the strategy and reports on every market it had orders on.
+    /// Intents still in flight are dropped.
+    pub fn finish(mut self) -> BacktestReport {
+        self.strategy.on_stop();
+        let market_ids: BTreeSet<String> = self
+            .exchange
+            .orders()
+            .map(|o| o.market_id.clone())
+            .collect();
+        let markets: Vec<MarketReport> = market_ids
+            .iter()
+            .filter_map(|id| self.market_report(id))
+            .collect();
+        let pnl = money(markets.iter().map(|m| m.pnl).sum());
+        let commission = money(markets.iter().map(|m| m.commission).sum());
+        BacktestReport {
+            strategy_ref: self.strategy_ref.clone(),
+            latency_ms: self.config.latency_ms,
+            frames: self.frames,
+            first_pt: self.first_pt,
+            last_pt: self.first_pt.map(|_| self.now_ms),
+            markets,
+            fills: std::mem::take(&mut self.fills),
+            curve: std::mem::take(&mut self.curve),
+            rejected: std::mem::take(&mut self.rejected),
+            pnl,
+            commission,
+            net: money(pnl - commission),
+            strategy_state: self.strategy.state(),
+        }
+    }
+
+    fn market_report(&self, market_id: &str) -> Option<MarketReport> {
+        let orders: Vec<RunnerOrdersSnapshot> = self.exchange.snapshots(market_id)?;
+        let market = self.cache.market(market_id);
+        let position = market_position(market_id, market.as_ref(), &orders);
+        let settled = position.realised.is_some();
+        let pnl = position
+            .realised
+            .or_else(|| self.pnl.get(market_id).copied())
+            .unwrap_or(0.0);
+        let commission = if settled {
+            money(pnl.max(0.0) * self.config.commission)
+        } else {
+            0.0
+        };
+        // Voided bets keep their fills in the log but no longer count.
+        let matched = |side: OrderSide| -> f64 {
+            self.exchange
+                .orders()
+                .filter(|o| o.market_id == market_id && o.side == side)
+                .fold(0.0, |total, o| total + o.size_matched())
+        };
+        Some(MarketReport {
+            market_id: market_id.to_string(),
+            orders: self
+                .exchange
+                .orders()
+                .filter(|o| o.market_id == market_id)
+                .count(),
+            fills: self
+                .fills
+                .iter()
+                .filter(|f| f.market_id == market_id)
+                .count(),
+            matched_back: money(matched(OrderSide::Back)),
+            matched_lay: money(matched(OrderSide::Lay)),
+            pnl,
+            settled,
+            commission,
+            net: money(pnl - commission),
+            position,
+        })
+    }
+}
@@ -3,6 +3,8 @@ use tracing::warn;
 
 use crate::ui_error::UiErrorPayload;
 
+pub const LOGIN_URL: &str = "https://identitysso.betfair.com/api/login";
+
 #[derive(Debug, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct IdentityJsonResponse {
@@ -61,7 +63,7 @@ pub async fn login(
     // Betfair Identity endpoint: returns URL-encoded body like:
     // status=SUCCESS&token=... OR status=FAIL&error=...
     let resp = http
-        .post("https://identitysso.betfair.com/api/login")
+        .post(LOGIN_URL)
         .header("X-Application", app_key)
         .header("Accept", "application/json")
         .header("Content-Type", "application/x-www-form-urlencoded")
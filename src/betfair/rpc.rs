@@ -1,3 +1,5 @@
+use crate::audit;
+use crate::redact;
 use crate::state::AppState;
 use crate::ui_error::UiErrorPayload;
 use reqwest::Client;
@@ -32,6 +34,14 @@ pub fn is_method_allowed(state: &AppState, service: &str, method: &str) -> bool
     }
 }
 
+/// JSON-RPC `result` plus the correlation headers Betfair returned with it.
+#[derive(Debug, Clone)]
+pub struct RpcResponse {
+    pub result: serde_json::Value,
+    pub request_id: Option<String>,
+    pub bf_correlation_id: Option<String>,
+}
+
 pub async fn call_json_rpc(
     http: &Client,
     app_key: &str,
@@ -40,6 +50,64 @@ pub async fn call_json_rpc(
     method: &str,
     params: serde_json::Value,
 ) -> Result<serde_json::Value, UiErrorPayload> {
+    call_json_rpc_with_meta(http, app_key, session_token, service, method, params)
+        .await
+        .map(|r| r.result)
+}
+
+/// Calls Betfair with the Rust-owned session, recording transactional
+/// methods in the audit log.
+pub async fn call(
+    state: &AppState,
+    service: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, UiErrorPayload> {
+    let token = state.session_token.read().await.clone().unwrap_or_default();
+    if token.is_empty() {
+        return Err(UiErrorPayload::key("errors:auth.notLoggedIn"));
+    }
+
+    if !audit::is_transactional_method(service, method) {
+        return call_json_rpc(
+            &state.http,
+            state.app_key.as_str(),
+            &token,
+            service,
+            method,
+            params,
+        )
+        .await;
+    }
+
+    let call_id = state.audit.record_request(service, method, &params);
+    // Orders don't go out unless the audit log has them.
+    if call_id == 0 && state.audit.is_required() {
+        return Err(UiErrorPayload::key("errors:audit.unavailable"));
+    }
+    let outcome = call_json_rpc_with_meta(
+        &state.http,
+        state.app_key.as_str(),
+        &token,
+        service,
+        method,
+        params,
+    )
+    .await;
+    state
+        .audit
+        .record_outcome(call_id, service, method, &outcome);
+    outcome.map(|r| r.result)
+}
+
+pub async fn call_json_rpc_with_meta(
+    http: &Client,
+    app_key: &str,
+    session_token: &str,
+    service: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<RpcResponse, UiErrorPayload> {
     let Some(base_url) = service_base_url(service) else {
         return Err(UiErrorPayload::key("errors:validation.invalidService"));
     };
@@ -66,7 +134,7 @@ pub async fn call_json_rpc(
         method = %method,
         full_method = %request.get("method").and_then(|v| v.as_str()).unwrap_or(""),
         url = %base_url,
-        app_key = app_key,
+        app_key = %redact::secret(app_key),
         session_token_len = session_token.len(),
         request_bytes = params_len,
         "betfair json-rpc request"
@@ -148,7 +216,11 @@ pub async fn call_json_rpc(
     }
 
     if let Some(result) = first.get("result") {
-        return Ok(result.clone());
+        return Ok(RpcResponse {
+            result: result.clone(),
+            request_id,
+            bf_correlation_id,
+        });
     }
 
     Err(UiErrorPayload::with_values(
@@ -156,3 +228,25 @@ pub async fn call_json_rpc(
         serde_json::json!({ "httpStatus": status.as_u16() }),
     ))
 }
+
+/// True if a `call_json_rpc` error means Betfair no longer accepts the session token.
+pub fn is_session_error(err: &UiErrorPayload) -> bool {
+    let Some(values) = err.values.as_ref() else {
+        return false;
+    };
+
+    // ANGX-0003 = INVALID_SESSION_INFORMATION, ANGX-0005 = NO_SESSION.
+    if matches!(
+        values.get("message").and_then(|m| m.as_str()),
+        Some("ANGX-0003" | "ANGX-0005")
+    ) {
+        return true;
+    }
+
+    matches!(
+        values
+            .pointer("/error/data/APINGException/errorCode")
+            .and_then(|c| c.as_str()),
+        Some("INVALID_SESSION_INFORMATION" | "NO_SESSION")
+    )
+}
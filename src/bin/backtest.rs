@@ -0,0 +1,136 @@
+//! Runs a built-in strategy over recorded stream files, offline:
+//!
+//! ```text
+//! cargo run --bin backtest -- --strategy favourite-back \
+//!     [--latency-ms 100] [--commission 0.05] [--json] FILE...
+//! ```
+//!
+//! Files hold one stream frame per line, as recorded from the Stream API or
+//! downloaded from Betfair historical data (`.bz2` files and tar bundles).
+
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::process::ExitCode;
+
+use betfair_stream_app::backtest::{Backtest, BacktestConfig, BacktestReport};
+use betfair_stream_app::strategy::builtin;
+use betfair_stream_app::stream::historical;
+
+const USAGE: &str =
+    "usage: backtest --strategy ID [--latency-ms MS] [--commission RATE] [--json] FILE...";
+
+struct Args {
+    strategy: String,
+    config: BacktestConfig,
+    json: bool,
+    files: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        strategy: String::new(),
+        config: BacktestConfig::default(),
+        json: false,
+        files: Vec::new(),
+    };
+    let mut it = std::env::args().skip(1);
+    while let Some(arg) = it.next() {
+        let mut value = |name: &str| it.next().ok_or_else(|| format!("{name} needs a value"));
+        match arg.as_str() {
+            "--strategy" => args.strategy = value("--strategy")?,
+            "--latency-ms" => {
+                args.config.latency_ms = value("--latency-ms")?
+                    .parse()
+                    .map_err(|_| "--latency-ms must be a whole number".to_string())?;
+            }
+            "--commission" => {
+                let rate: f64 = value("--commission")?
+                    .parse()
+                    .map_err(|_| "--commission must be a number".to_string())?;
+                if !(0.0..1.0).contains(&rate) {
+                    return Err("--commission must be in [0, 1)".to_string());
+                }
+                args.config.commission = rate;
+            }
+            "--json" => args.json = true,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            s if s.starts_with("--") => return Err(format!("unknown option {s}")),
+            _ => args.files.push(arg),
+        }
+    }
+    if args.strategy.is_empty() || args.files.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok(args)
+}
+
+fn print_summary(report: &BacktestReport) {
+    println!(
+        "{}: {} frames, {} fills, {} rejected",
+        report.strategy_ref,
+        report.frames,
+        report.fills.len(),
+        report.rejected.len()
+    );
+    for m in &report.markets {
+        println!(
+            "  {}  orders {:>3}  back {:>9.2}  lay {:>9.2}  pnl {:>9.2}  net {:>9.2}{}",
+            m.market_id,
+            m.orders,
+            m.matched_back,
+            m.matched_lay,
+            m.pnl,
+            m.net,
+            if m.settled { "" } else { "  (open)" }
+        );
+    }
+    println!(
+        "total pnl {:.2}, commission {:.2}, net {:.2}",
+        report.pnl, report.commission, report.net
+    );
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::from(2);
+        }
+    };
+    let Some(strategy) = builtin::find(&args.strategy) else {
+        let known: Vec<&str> = builtin::BUILTINS.iter().map(|b| b.id).collect();
+        eprintln!(
+            "unknown strategy {}; built in: {}",
+            args.strategy,
+            known.join(", ")
+        );
+        return ExitCode::from(2);
+    };
+
+    let mut backtest = Backtest::new(args.config, strategy.strategy_ref, (strategy.create)());
+    for path in &args.files {
+        let read = historical::for_each_line(Path::new(path), |line, _| {
+            backtest.feed(line);
+            ControlFlow::Continue(())
+        });
+        if let Err(e) = read {
+            eprintln!("{path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let report = backtest.finish();
+    if args.json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("{e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        print_summary(&report);
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,43 @@
+use crate::audit::{AuditEntry, AuditQuery, AuditVerification};
+use crate::state::AppState;
+use crate::ui_error::UiErrorPayload;
+use serde::Deserialize;
+use tauri::State;
+use tracing::{info, warn};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditExportArgs {
+    pub path: String,
+}
+
+#[tauri::command]
+pub async fn audit_query(
+    state: State<'_, AppState>,
+    query: Option<AuditQuery>,
+) -> Result<Vec<AuditEntry>, UiErrorPayload> {
+    info!("audit_query");
+    state.audit.query(&query.unwrap_or_default())
+}
+
+#[tauri::command]
+pub async fn audit_verify(state: State<'_, AppState>) -> Result<AuditVerification, UiErrorPayload> {
+    info!("audit_verify");
+    let v = state.audit.verify()?;
+    if !v.valid {
+        warn!(first_invalid_seq = ?v.first_invalid_seq, "audit chain broken");
+    }
+    Ok(v)
+}
+
+#[tauri::command]
+pub async fn audit_export(
+    state: State<'_, AppState>,
+    args: AuditExportArgs,
+) -> Result<AuditVerification, UiErrorPayload> {
+    info!("audit_export");
+    if args.path.trim().is_empty() {
+        return Err(UiErrorPayload::key("errors:validation.pathRequired"));
+    }
+    state.audit.export(std::path::Path::new(args.path.trim()))
+}
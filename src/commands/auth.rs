@@ -1,4 +1,5 @@
 use crate::betfair;
+use crate::redact;
 use crate::state::AppState;
 use crate::ui_error::UiErrorPayload;
 use serde::{Deserialize, Serialize};
@@ -31,7 +32,9 @@ pub async fn auth_status(state: State<'_, AppState>) -> Result<AuthStatus, Strin
 pub async fn auth_logout(state: State<'_, AppState>) -> Result<(), String> {
     info!("auth_logout");
     let mut token = state.session_token.write().await;
-    *token = None;
+    if let Some(old) = token.take() {
+        redact::forget_secret(&old);
+    }
     Ok(())
 }
 
@@ -40,7 +43,7 @@ pub async fn auth_login(
     state: State<'_, AppState>,
     args: AuthLoginArgs,
 ) -> Result<(), UiErrorPayload> {
-    info!(username = %args.username.trim(), "auth_login");
+    info!(username = %redact::secret(args.username.trim()), "auth_login");
     if args.username.trim().is_empty() {
         return Err(UiErrorPayload::key("errors:validation.usernameRequired"));
     }
@@ -63,6 +66,9 @@ pub async fn auth_login(
         warn!(key = %e.key, "auth_login failed");
     })?;
 
+    redact::register_secret(args.username.trim());
+    redact::register_secret(&token);
+
     let mut token_state = state.session_token.write().await;
     *token_state = Some(token);
 
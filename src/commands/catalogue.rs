@@ -0,0 +1,152 @@
+use crate::clock::now_ms;
+use crate::commands::rpc::call_checked;
+use crate::market::catalogue::{
+    CatalogueMarket, CatalogueQuery, CATALOGUE_CHUNK, CATALOGUE_PROJECTION, DEFAULT_MAX_AGE_MS,
+    MAX_LIST_RESULTS,
+};
+use crate::state::AppState;
+use crate::ui_error::UiErrorPayload;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use tracing::{info, warn};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueRefreshArgs {
+    /// `MarketFilter`, as for `listMarketCatalogue`.
+    #[serde(default)]
+    pub filter: serde_json::Value,
+    /// Markets to list (default 100, max 1000), first to start first.
+    pub max_results: Option<u32>,
+    /// Refetch stored markets older than this (default 15 minutes).
+    pub max_age_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueRefresh {
+    /// Markets matching the filter.
+    pub listed: usize,
+    /// Of those, markets fetched in full: new or stale.
+    pub fetched: usize,
+    /// Closed or long-started markets dropped.
+    pub pruned: usize,
+    pub stored: usize,
+}
+
+fn invalid_response(e: serde_json::Error) -> UiErrorPayload {
+    UiErrorPayload::with_values(
+        "errors:catalogue.invalidResponse",
+        serde_json::json!({ "details": e.to_string() }),
+    )
+}
+
+async fn list_catalogue(
+    app: &AppHandle,
+    state: &AppState,
+    params: serde_json::Value,
+) -> Result<Vec<CatalogueMarket>, UiErrorPayload> {
+    let result = call_checked(app, state, "betting", "listMarketCatalogue", params).await?;
+    serde_json::from_value(result).map_err(invalid_response)
+}
+
+/// Lists the markets matching `filter` (a cheap call without projections)
+/// and fetches in full only those the store lacks or holds stale.
+#[tauri::command]
+pub async fn catalogue_refresh(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: CatalogueRefreshArgs,
+) -> Result<CatalogueRefresh, UiErrorPayload> {
+    info!(max_results = ?args.max_results, "catalogue_refresh");
+    let max_results = args.max_results.unwrap_or(100);
+    if !(1..=MAX_LIST_RESULTS).contains(&max_results) {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.maxResultsOutOfRange",
+            serde_json::json!({ "min": 1, "max": MAX_LIST_RESULTS }),
+        ));
+    }
+    let filter = match args.filter {
+        serde_json::Value::Null => serde_json::json!({}),
+        filter => filter,
+    };
+    let max_age_ms = args.max_age_ms.unwrap_or(DEFAULT_MAX_AGE_MS);
+    refresh(&app, &state, filter, max_results, max_age_ms)
+        .await
+        .map(|(_, refresh)| refresh)
+}
+
+/// Body of `catalogue_refresh`; also returns the listed market ids, first to
+/// start first.
+pub async fn refresh(
+    app: &AppHandle,
+    state: &AppState,
+    filter: serde_json::Value,
+    max_results: u32,
+    max_age_ms: u64,
+) -> Result<(Vec<String>, CatalogueRefresh), UiErrorPayload> {
+    let listed = list_catalogue(
+        app,
+        state,
+        serde_json::json!({
+            "filter": filter,
+            "maxResults": max_results,
+            "sort": "FIRST_TO_START",
+        }),
+    )
+    .await?;
+
+    let catalogue = &state.catalogue;
+    for m in &listed {
+        catalogue.set_total_matched(&m.market_id, m.total_matched);
+    }
+    let ids: Vec<&str> = listed.iter().map(|m| m.market_id.as_str()).collect();
+    let now = now_ms();
+    let stale = catalogue.stale(&ids, now.saturating_sub(max_age_ms));
+    for chunk in stale.chunks(CATALOGUE_CHUNK) {
+        let markets = list_catalogue(
+            app,
+            state,
+            serde_json::json!({
+                "filter": { "marketIds": chunk },
+                "marketProjection": CATALOGUE_PROJECTION,
+                "maxResults": chunk.len(),
+            }),
+        )
+        .await
+        .inspect_err(|e| warn!(key = %e.key, "catalogue fetch failed"))?;
+        catalogue.merge(markets, now);
+    }
+
+    let refresh = CatalogueRefresh {
+        listed: listed.len(),
+        fetched: stale.len(),
+        pruned: catalogue.prune(now),
+        stored: catalogue.len(),
+    };
+    info!(
+        listed = refresh.listed,
+        fetched = refresh.fetched,
+        stored = refresh.stored,
+        "catalogue refreshed"
+    );
+    let ids = listed.into_iter().map(|m| m.market_id).collect();
+    Ok((ids, refresh))
+}
+
+/// Searches stored markets without calling Betfair.
+#[tauri::command]
+pub async fn catalogue_search(
+    state: State<'_, AppState>,
+    query: CatalogueQuery,
+) -> Result<Vec<CatalogueMarket>, UiErrorPayload> {
+    Ok(state.catalogue.search(&query))
+}
+
+#[tauri::command]
+pub async fn catalogue_market(
+    state: State<'_, AppState>,
+    market_id: String,
+) -> Result<Option<CatalogueMarket>, UiErrorPayload> {
+    Ok(state.catalogue.market(market_id.trim()))
+}
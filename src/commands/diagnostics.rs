@@ -0,0 +1,44 @@
+use crate::diagnostics::{self, DiagnosticsSummary};
+use crate::state::AppState;
+use crate::ui_error::UiErrorPayload;
+use serde::Deserialize;
+use tauri::{AppHandle, Manager, State};
+use tracing::{info, warn};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsExportArgs {
+    /// Destination `.zip` path (from the save dialog).
+    pub path: String,
+    /// Raw stream frames to include (default 200).
+    pub frames: Option<usize>,
+}
+
+#[tauri::command]
+pub async fn diagnostics_export(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: DiagnosticsExportArgs,
+) -> Result<DiagnosticsSummary, UiErrorPayload> {
+    info!("diagnostics_export");
+    if args.path.trim().is_empty() {
+        return Err(UiErrorPayload::key("errors:validation.pathRequired"));
+    }
+    let version = app.package_info().version.to_string();
+    let summary = diagnostics::write_bundle(
+        &state,
+        &version,
+        std::path::Path::new(args.path.trim()),
+        args.frames.unwrap_or(diagnostics::DEFAULT_FRAMES),
+    )
+    .await;
+    match &summary {
+        Ok(s) => info!(
+            files = s.files.len(),
+            bytes = s.bytes,
+            "diagnostics bundle written"
+        ),
+        Err(e) => warn!(key = %e.key, "diagnostics bundle failed"),
+    }
+    summary
+}
@@ -0,0 +1,293 @@
+use crate::clock::now_ms;
+use crate::market::cache::{LadderLevel, MarketUpdate, RunnerState};
+use crate::market::ladder::PriceLadder;
+use crate::market::replay::{Replay, ReplayStatus, Replayed, MAX_REPLAY_SPEED};
+use crate::market::{
+    EVENT_MARKET_LIFECYCLE, EVENT_MARKET_UPDATE, EVENT_REPLAY, EVENT_RUNNER_REMOVED,
+};
+use crate::state::AppState;
+use crate::stream::historical::{self, HistoricalSummary};
+use crate::stream::mcm::MarketChangeMessage;
+use crate::stream::subscriptions::is_valid_subscriber_id;
+use crate::ui_error::UiErrorPayload;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+use tracing::{info, warn};
+
+/// Best-offer levels shown for files that only carry full depth.
+const BEST_LEVELS: usize = 3;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// Longest sleep between checks for a stop.
+const MAX_PACING_SLEEP: Duration = Duration::from_millis(200);
+
+fn read_failed(path: &Path, e: std::io::Error) -> UiErrorPayload {
+    UiErrorPayload::with_values(
+        "errors:historical.readFailed",
+        serde_json::json!({ "path": path.to_string_lossy(), "details": e.to_string() }),
+    )
+}
+
+fn checked_path(path: &str) -> Result<PathBuf, UiErrorPayload> {
+    if path.trim().is_empty() {
+        return Err(UiErrorPayload::key("errors:validation.pathRequired"));
+    }
+    Ok(PathBuf::from(path.trim()))
+}
+
+/// Lists the markets in a historical data file (`.bz2`, tar bundle or plain
+/// recording).
+#[tauri::command]
+pub async fn historical_scan(path: String) -> Result<HistoricalSummary, UiErrorPayload> {
+    info!(path = %path, "historical_scan");
+    let path = checked_path(&path)?;
+    let scanned = path.clone();
+    let summary = tokio::task::spawn_blocking(move || historical::scan(&scanned))
+        .await
+        .map_err(|e| read_failed(&path, std::io::Error::other(e)))
+        .and_then(|r| r.map_err(|e| read_failed(&path, e)));
+    match &summary {
+        Ok(s) => info!(
+            markets = s.markets.len(),
+            frames = s.frames,
+            "historical file scanned"
+        ),
+        Err(e) => warn!(key = %e.key, "historical scan failed"),
+    }
+    summary
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayArgs {
+    pub path: String,
+    /// Markets to play; every market in the file when empty.
+    #[serde(default)]
+    pub market_ids: Vec<String>,
+    /// View to send updates to as `betfair_market_update/<subscriberId>`;
+    /// plain `betfair_market_update` when unset.
+    pub subscriber_id: Option<String>,
+    /// Multiple of real time (default 1, max 1000); 0 plays as fast as the
+    /// file reads.
+    pub speed: Option<f64>,
+}
+
+/// Plays a historical file into the market views. Starting a replay stops
+/// the one before.
+#[tauri::command]
+pub async fn historical_replay_start(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: ReplayArgs,
+) -> Result<ReplayStatus, UiErrorPayload> {
+    info!(path = %args.path, markets = args.market_ids.len(), "historical_replay_start");
+    let path = checked_path(&args.path)?;
+    let speed = args.speed.unwrap_or(1.0);
+    if !(speed.is_finite() && (0.0..=MAX_REPLAY_SPEED).contains(&speed)) {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.replaySpeedOutOfRange",
+            serde_json::json!({ "max": MAX_REPLAY_SPEED }),
+        ));
+    }
+    if let Some(id) = &args.subscriber_id {
+        if !is_valid_subscriber_id(id) {
+            return Err(UiErrorPayload::key("errors:validation.subscriberId"));
+        }
+    }
+    if !path.is_file() {
+        return Err(read_failed(
+            &path,
+            std::io::Error::from(std::io::ErrorKind::NotFound),
+        ));
+    }
+    let status = ReplayStatus {
+        running: true,
+        path: Some(path.to_string_lossy().into_owned()),
+        market_ids: args.market_ids,
+        subscriber_id: args.subscriber_id,
+        speed,
+        ..ReplayStatus::default()
+    };
+    let generation = state.replay.start(status.clone());
+    let _ = app.emit(EVENT_REPLAY, &status);
+    let replay = state.replay.clone();
+    let wanted: BTreeSet<String> = status.market_ids.iter().cloned().collect();
+    std::thread::spawn(move || play(app, replay, generation, path, wanted, speed));
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn historical_replay_stop(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ReplayStatus, UiErrorPayload> {
+    info!("historical_replay_stop");
+    let status = state.replay.stop();
+    let _ = app.emit(EVENT_REPLAY, &status);
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn historical_replay_status(
+    state: State<'_, AppState>,
+) -> Result<ReplayStatus, UiErrorPayload> {
+    Ok(state.replay.status())
+}
+
+/// Waits until `due`, giving up if the playback was replaced or stopped.
+fn wait_until(replay: &Replay, generation: u64, due: Instant) -> ControlFlow<()> {
+    loop {
+        if !replay.is_current(generation) {
+            return ControlFlow::Break(());
+        }
+        let now = Instant::now();
+        if now >= due {
+            return ControlFlow::Continue(());
+        }
+        std::thread::sleep((due - now).min(MAX_PACING_SLEEP));
+    }
+}
+
+/// Reads the file on its own thread, pacing frames by their publish time.
+fn play(
+    app: AppHandle,
+    replay: Arc<Replay>,
+    generation: u64,
+    path: PathBuf,
+    wanted: BTreeSet<String>,
+    speed: f64,
+) {
+    // (pt, when it was played) that later frames are paced from.
+    let mut anchor: Option<(u64, Instant)> = None;
+    let mut source = String::new();
+    let mut last_progress = Instant::now();
+    let result = historical::for_each_line(&path, |line, file| {
+        if !replay.is_current(generation) {
+            return ControlFlow::Break(());
+        }
+        // Each file of a bundle plays from its own start, without the gap
+        // (or overlap) to the one before.
+        if file != source {
+            source = file.to_string();
+            anchor = None;
+        }
+        let Some(mut msg) = MarketChangeMessage::parse(line) else {
+            return ControlFlow::Continue(());
+        };
+        if !wanted.is_empty() {
+            msg.mc.retain(|mc| wanted.contains(&mc.id));
+            if msg.mc.is_empty() {
+                return ControlFlow::Continue(());
+            }
+        }
+        if let (true, Some(pt)) = (speed > 0.0, msg.pt) {
+            let (pt0, t0) = match anchor {
+                Some(a) if pt >= a.0 => a,
+                _ => *anchor.insert((pt, Instant::now())),
+            };
+            let offset = Duration::from_secs_f64((pt - pt0) as f64 / 1000.0 / speed);
+            wait_until(&replay, generation, t0 + offset)?;
+        }
+        if !replay.apply(generation, &msg) {
+            return ControlFlow::Break(());
+        }
+        let status = replay.update(generation, |s| {
+            s.frames += 1;
+            s.pt = msg.pt.or(s.pt);
+        });
+        if let Some(status) = status.filter(|_| last_progress.elapsed() >= PROGRESS_INTERVAL) {
+            last_progress = Instant::now();
+            let _ = app.emit(EVENT_REPLAY, &status);
+        }
+        ControlFlow::Continue(())
+    });
+    let error = result.err().map(|e| read_failed(&path, e));
+    if let Some(e) = &error {
+        warn!(key = %e.key, "historical replay failed");
+    }
+    let finished = replay.update(generation, |s| {
+        s.running = false;
+        s.error = error;
+    });
+    if let Some(status) = finished {
+        info!(frames = status.frames, "historical replay finished");
+        let _ = app.emit(EVENT_REPLAY, &status);
+    }
+}
+
+fn levels(prices: impl Iterator<Item = (f64, f64)>) -> Vec<LadderLevel> {
+    prices
+        .take(BEST_LEVELS)
+        .enumerate()
+        .map(|(level, (price, size))| LadderLevel {
+            level: level as u32,
+            price,
+            size,
+        })
+        .collect()
+}
+
+/// PRO files carry full depth only; views draw best offers, so derive them.
+fn fill_best_offers(update: &mut MarketUpdate, runner: impl Fn(u64, f64) -> Option<RunnerState>) {
+    for r in &mut update.runners {
+        if !(r.batb.is_empty() && r.batl.is_empty()) {
+            continue;
+        }
+        let Some(state) = runner(r.selection_id, r.handicap) else {
+            continue;
+        };
+        let depth = |l: &PriceLadder| l.iter().collect::<Vec<_>>();
+        r.batb = levels(depth(&state.atb).into_iter().rev());
+        r.batl = levels(depth(&state.atl).into_iter());
+    }
+}
+
+/// Emits replayed markets like `markets::publish_market_updates`, to the
+/// replay's view (or plain `betfair_market_update`), without positions,
+/// stops or strategies. Removals and lifecycle events carry `replay: true`.
+pub async fn publish_replay_updates(app: AppHandle, state: AppState) {
+    let cache = state.replay.markets.clone();
+    loop {
+        for removed in cache.take_removals() {
+            let _ = app.emit(EVENT_RUNNER_REMOVED, &Replayed::new(&removed));
+        }
+        for event in cache.take_lifecycle() {
+            let _ = app.emit(EVENT_MARKET_LIFECYCLE, &Replayed::new(&event));
+        }
+        let (updates, next_due) = cache.take_due(now_ms());
+        if !updates.is_empty() {
+            let subscriber = state.replay.status().subscriber_id;
+            let source = match &subscriber {
+                Some(id) => state.subscriptions.read().await.ladder_source(id),
+                None => Default::default(),
+            };
+            for mut update in updates {
+                let market_id = update.market_id.clone();
+                fill_best_offers(&mut update, |sel, hc| cache.runner(&market_id, sel, hc));
+                match &subscriber {
+                    Some(id) => {
+                        let update = update.for_ladder(source);
+                        let _ = app.emit(&format!("{EVENT_MARKET_UPDATE}/{id}"), &update);
+                    }
+                    None => {
+                        let _ = app.emit(EVENT_MARKET_UPDATE, update);
+                    }
+                }
+            }
+        }
+        match next_due {
+            Some(at) => {
+                let wait = Duration::from_millis(at.saturating_sub(now_ms()));
+                tokio::select! {
+                    _ = cache.changed() => {}
+                    _ = tokio::time::sleep(wait) => {}
+                }
+            }
+            None => cache.changed().await,
+        }
+    }
+}
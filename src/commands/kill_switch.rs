@@ -0,0 +1,160 @@
+use crate::betfair;
+use crate::commands::strategies;
+use crate::market::lifecycle::LifecycleKind;
+use crate::risk::kill_switch::{
+    KillSwitchReason, KillSwitchStatus, KillSwitchTriggers, EVENT_KILL_SWITCH,
+};
+use crate::state::AppState;
+use crate::strategy::runtime::StopReason;
+use crate::ui_error::UiErrorPayload;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+#[tauri::command]
+pub async fn kill_switch_status(
+    state: State<'_, AppState>,
+) -> Result<KillSwitchStatus, UiErrorPayload> {
+    Ok(state.kill_switch.status().await)
+}
+
+#[tauri::command]
+pub async fn kill_switch_engage(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<KillSwitchStatus, UiErrorPayload> {
+    info!("kill_switch_engage");
+    Ok(engage(&app, &state, KillSwitchReason::Manual).await)
+}
+
+#[tauri::command]
+pub async fn kill_switch_rearm(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<KillSwitchStatus, UiErrorPayload> {
+    info!("kill_switch_rearm");
+    state.kill_switch.rearm().await;
+    let status = state.kill_switch.status().await;
+    let _ = app.emit(EVENT_KILL_SWITCH, status.clone());
+    Ok(status)
+}
+
+#[tauri::command]
+pub async fn kill_switch_set_triggers(
+    state: State<'_, AppState>,
+    triggers: KillSwitchTriggers,
+) -> Result<KillSwitchStatus, UiErrorPayload> {
+    info!(
+        on_stream_disconnect = triggers.on_stream_disconnect,
+        on_session_expiry = triggers.on_session_expiry,
+        on_in_play = triggers.on_in_play,
+        "kill_switch_set_triggers"
+    );
+    state.kill_switch.set_triggers(triggers).await;
+    Ok(state.kill_switch.status().await)
+}
+
+/// Locks the gateway, then cancels every unmatched order on the account.
+///
+/// The lock is applied before the cancel so nothing new can be placed while
+/// `cancelOrders` is in flight. Listeners of `EVENT_KILL_SWITCH` (UI, strategies)
+/// must stop on `locked=true`.
+pub async fn engage(
+    app: &AppHandle,
+    state: &AppState,
+    reason: KillSwitchReason,
+) -> KillSwitchStatus {
+    if state.kill_switch.engage(reason).await {
+        warn!(?reason, "kill switch engaged");
+    } else {
+        info!(?reason, "kill switch already engaged; re-sending cancel");
+    }
+    let _ = app.emit(EVENT_KILL_SWITCH, state.kill_switch.status().await);
+    strategies::stop_all(app, state, StopReason::KillSwitch);
+
+    let outcome = cancel_all_orders(state).await;
+    match &outcome {
+        Ok(n) => info!(instructions = n, "kill switch cancelOrders done"),
+        Err(e) => warn!(key = %e.key, "kill switch cancelOrders failed"),
+    }
+    state.kill_switch.record_cancel(outcome).await;
+
+    let status = state.kill_switch.status().await;
+    let _ = app.emit(EVENT_KILL_SWITCH, status.clone());
+    status
+}
+
+/// Engages the kill switch after an unexpected stream drop, but only if the
+/// trigger is enabled and the account still has unmatched orders.
+pub async fn on_stream_lost(app: &AppHandle, state: &AppState) {
+    if !state.kill_switch.triggers().await.on_stream_disconnect {
+        return;
+    }
+
+    match has_open_orders(state).await {
+        Ok(true) => {
+            engage(app, state, KillSwitchReason::StreamDisconnected).await;
+        }
+        Ok(false) => info!("stream lost with no open orders; kill switch not engaged"),
+        Err(e) => {
+            // Can't prove we're flat, so fail safe.
+            warn!(key = %e.key, "open order check failed after stream loss");
+            engage(app, state, KillSwitchReason::StreamDisconnected).await;
+        }
+    }
+}
+
+/// Follows market lifecycle events for the app's lifetime and engages on
+/// in-play markets we still have unmatched orders on, if enabled.
+pub async fn watch_lifecycle(app: AppHandle, state: AppState) {
+    let mut events = state.lifecycle.subscribe();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                warn!(skipped = n, "kill switch lagged behind lifecycle events");
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        if event.kind != LifecycleKind::InPlay || !state.kill_switch.triggers().await.on_in_play {
+            continue;
+        }
+        if !state.orders.market_unmatched(&event.market_id).is_empty() {
+            warn!(market_id = %event.market_id, "market in-play with unmatched orders");
+            engage(&app, &state, KillSwitchReason::MarketInPlay).await;
+        }
+    }
+}
+
+pub async fn on_session_expired(app: &AppHandle, state: &AppState) {
+    if !state.kill_switch.triggers().await.on_session_expiry {
+        return;
+    }
+    engage(app, state, KillSwitchReason::SessionExpired).await;
+}
+
+async fn cancel_all_orders(state: &AppState) -> Result<usize, UiErrorPayload> {
+    // `cancelOrders` without a marketId cancels all unmatched orders on the account.
+    let result =
+        betfair::rpc::call(state, "betting", "cancelOrders", serde_json::json!({})).await?;
+    Ok(result
+        .get("instructionReports")
+        .and_then(|r| r.as_array())
+        .map(|r| r.len())
+        .unwrap_or(0))
+}
+
+async fn has_open_orders(state: &AppState) -> Result<bool, UiErrorPayload> {
+    let result = betfair::rpc::call(
+        state,
+        "betting",
+        "listCurrentOrders",
+        serde_json::json!({ "orderProjection": "EXECUTABLE", "recordCount": 1 }),
+    )
+    .await?;
+    Ok(result
+        .get("currentOrders")
+        .and_then(|o| o.as_array())
+        .is_some_and(|o| !o.is_empty()))
+}
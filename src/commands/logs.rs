@@ -0,0 +1,67 @@
+use crate::logging::{LogLine, LogStatus, Logging, EVENT_LOG_LINE};
+use crate::state::AppState;
+use crate::ui_error::UiErrorPayload;
+use serde::Deserialize;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+
+const DEFAULT_RECENT_LINES: usize = 500;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsSetLevelArgs {
+    /// `EnvFilter` directives, e.g. `debug` or `info,betfair_stream_app=trace`.
+    pub level: String,
+}
+
+#[tauri::command]
+pub async fn logs_status(state: State<'_, AppState>) -> Result<LogStatus, UiErrorPayload> {
+    Ok(state.logging.status())
+}
+
+#[tauri::command]
+pub async fn logs_set_level(
+    state: State<'_, AppState>,
+    args: LogsSetLevelArgs,
+) -> Result<LogStatus, UiErrorPayload> {
+    let status = state.logging.set_level(&args.level)?;
+    info!(level = %status.level, "logs_set_level");
+    Ok(status)
+}
+
+/// Lines already in memory, for filling the diagnostics panel when it opens.
+#[tauri::command]
+pub async fn logs_recent(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<LogLine>, UiErrorPayload> {
+    Ok(state.logging.recent(limit.unwrap_or(DEFAULT_RECENT_LINES)))
+}
+
+/// Starts or stops streaming new lines as `EVENT_LOG_LINE` events.
+#[tauri::command]
+pub async fn logs_follow(
+    state: State<'_, AppState>,
+    follow: bool,
+) -> Result<LogStatus, UiErrorPayload> {
+    state.logging.set_follow(follow);
+    Ok(state.logging.status())
+}
+
+/// Emits each new log line to the UI while following is on. Runs for the app's lifetime.
+pub async fn forward_live_lines(app: AppHandle, logging: Logging) {
+    let mut rx = logging.subscribe();
+    loop {
+        match rx.recv().await {
+            Ok(line) => {
+                if logging.following() {
+                    let _ = app.emit(EVENT_LOG_LINE, line);
+                }
+            }
+            // The panel re-reads `logs_recent` on gaps; nothing to do here.
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
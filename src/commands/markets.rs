@@ -0,0 +1,225 @@
+use crate::clock::now_ms;
+use crate::commands::orders::emit_position;
+use crate::commands::{stops, strategies};
+use crate::market::bsp::{bsp_view, BspView};
+use crate::market::cache::{LadderSource, MarketUpdate, MAX_RATE_HZ_LIMIT};
+use crate::market::ladder::{ladder_view, LadderView, DEFAULT_LADDER_TICKS, MAX_LADDER_TICKS};
+use crate::market::lifecycle::LifecycleKind;
+use crate::market::{EVENT_MARKET_LIFECYCLE, EVENT_MARKET_UPDATE, EVENT_RUNNER_REMOVED};
+use crate::state::AppState;
+use crate::ui_error::UiErrorPayload;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+use tracing::{info, warn};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketPublishRateArgs {
+    /// Max events per second per market (1–60).
+    pub max_hz: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketPublishSettings {
+    pub max_hz: u32,
+}
+
+#[tauri::command]
+pub async fn market_publish_settings(
+    state: State<'_, AppState>,
+) -> Result<MarketPublishSettings, UiErrorPayload> {
+    Ok(MarketPublishSettings {
+        max_hz: state.markets.max_rate_hz(),
+    })
+}
+
+#[tauri::command]
+pub async fn market_set_publish_rate(
+    state: State<'_, AppState>,
+    args: MarketPublishRateArgs,
+) -> Result<MarketPublishSettings, UiErrorPayload> {
+    info!(max_hz = args.max_hz, "market_set_publish_rate");
+    if !(1..=MAX_RATE_HZ_LIMIT).contains(&args.max_hz) {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.publishRateOutOfRange",
+            serde_json::json!({ "min": 1, "max": MAX_RATE_HZ_LIMIT }),
+        ));
+    }
+    state.markets.set_max_rate_hz(args.max_hz);
+    state.replay.markets.set_max_rate_hz(args.max_hz);
+    Ok(MarketPublishSettings {
+        max_hz: state.markets.max_rate_hz(),
+    })
+}
+
+/// Current cached state of a market, for views that mount mid-stream.
+/// Live markets first, then the historical replay.
+#[tauri::command]
+pub async fn market_snapshot(
+    state: State<'_, AppState>,
+    market_id: String,
+) -> Result<Option<MarketUpdate>, UiErrorPayload> {
+    let market_id = market_id.trim();
+    Ok(state
+        .markets
+        .snapshot(market_id)
+        .or_else(|| state.replay.markets.snapshot(market_id)))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketLadderArgs {
+    pub market_id: String,
+    pub selection_id: u64,
+    /// Asian handicap markets only.
+    #[serde(default)]
+    pub handicap: f64,
+    /// Ticks either side of the best price (default 10, max 100).
+    pub ticks: Option<u32>,
+}
+
+/// Full-depth ladder for one runner: available, traded and our unmatched
+/// stakes at every tick around the best price. Needs `fullDepth` data.
+#[tauri::command]
+pub async fn market_ladder(
+    state: State<'_, AppState>,
+    args: MarketLadderArgs,
+) -> Result<Option<LadderView>, UiErrorPayload> {
+    let ticks = args.ticks.unwrap_or(DEFAULT_LADDER_TICKS);
+    if !(1..=MAX_LADDER_TICKS).contains(&ticks) {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.ladderTicksOutOfRange",
+            serde_json::json!({ "min": 1, "max": MAX_LADDER_TICKS }),
+        ));
+    }
+    let market_id = args.market_id.trim();
+    let Some((runner, tick_ladder)) = [&state.markets, &state.replay.markets]
+        .into_iter()
+        .find_map(|cache| {
+            let runner = cache.runner(market_id, args.selection_id, args.handicap)?;
+            Some((runner, cache.tick_ladder(market_id)))
+        })
+    else {
+        return Ok(None);
+    };
+    let orders = state
+        .orders
+        .unmatched(market_id, args.selection_id, args.handicap);
+    Ok(Some(ladder_view(
+        market_id,
+        &tick_ladder,
+        &runner,
+        &orders,
+        ticks,
+    )))
+}
+
+/// Projected and (once in-play) reconciled BSP per runner, with our
+/// on-close orders. Needs the `sp` preset.
+#[tauri::command]
+pub async fn market_bsp(
+    state: State<'_, AppState>,
+    market_id: String,
+) -> Result<Option<BspView>, UiErrorPayload> {
+    let market_id = market_id.trim();
+    let market = state
+        .markets
+        .market(market_id)
+        .or_else(|| state.replay.markets.market(market_id));
+    Ok(market.map(|market| {
+        bsp_view(&market, |sel, hc| {
+            state.orders.unmatched(market_id, sel, hc)
+        })
+    }))
+}
+
+/// Emits conflated market updates for the app's lifetime.
+///
+/// Each update goes to `betfair_market_update/<subscriberId>` for every view
+/// subscribed to the market; markets no view asked for (raw `stream_send`
+/// subscriptions) go to plain `betfair_market_update`. Runner removals go out
+/// as `betfair_runner_removed` and lifecycle transitions as
+/// `betfair_market_lifecycle` as soon as they are seen.
+pub async fn publish_market_updates(app: AppHandle, state: AppState) {
+    let cache = state.markets.clone();
+    loop {
+        for removed in cache.take_removals() {
+            let r = &removed.removal;
+            warn!(
+                market_id = %removed.market_id,
+                selection_id = r.selection_id,
+                adjustment_factor = ?r.adjustment_factor,
+                "runner removed"
+            );
+            state
+                .orders
+                .void_runner(&removed.market_id, r.selection_id, r.handicap);
+            let _ = app.emit(EVENT_RUNNER_REMOVED, &removed);
+        }
+        for event in cache.take_lifecycle() {
+            info!(market_id = %event.market_id, kind = ?event.kind, "market lifecycle");
+            let _ = app.emit(EVENT_MARKET_LIFECYCLE, &event);
+            match event.kind {
+                LifecycleKind::RunnerSettled { .. } => {
+                    emit_position(&app, &state, &event.market_id)
+                }
+                LifecycleKind::Closed => {
+                    emit_position(&app, &state, &event.market_id);
+                    stops::close_market(&app, &state, &event.market_id)
+                }
+                _ => {}
+            }
+            strategies::on_lifecycle(&app, &state, &event);
+            // No receivers is fine.
+            let _ = state.lifecycle.send(event);
+        }
+        let (updates, next_due) = cache.take_due(now_ms());
+        if !updates.is_empty() {
+            // Routes first, so the guard isn't held while stops place orders:
+            // the stream reader waits on it to apply market definitions.
+            let routes: Vec<Vec<(String, LadderSource)>> = {
+                let subs = state.subscriptions.read().await;
+                updates
+                    .iter()
+                    .map(|u| {
+                        let ids = subs.subscribers_for(&u.market_id);
+                        ids.into_iter()
+                            .map(|id| {
+                                let source = subs.ladder_source(&id);
+                                (id, source)
+                            })
+                            .collect()
+                    })
+                    .collect()
+            };
+            for (update, subscribers) in updates.into_iter().zip(routes) {
+                // Hedge prices follow the market, and so do stops.
+                emit_position(&app, &state, &update.market_id);
+                stops::check_market(&app, &state, &update.market_id).await;
+                strategies::on_market(&app, &state, &update.market_id);
+                if subscribers.is_empty() {
+                    let _ = app.emit(EVENT_MARKET_UPDATE, update);
+                    continue;
+                }
+                for (id, source) in subscribers {
+                    let update = update.for_ladder(source);
+                    let _ = app.emit(&format!("{EVENT_MARKET_UPDATE}/{id}"), &update);
+                }
+            }
+        }
+        // Markets released by `sync_slots` go once their alerts above are out.
+        cache.drop_released();
+        match next_due {
+            Some(at) => {
+                let wait = Duration::from_millis(at.saturating_sub(now_ms()));
+                tokio::select! {
+                    _ = cache.changed() => {}
+                    _ = tokio::time::sleep(wait) => {}
+                }
+            }
+            None => cache.changed().await,
+        }
+    }
+}
@@ -1,3 +1,15 @@
+pub mod audit;
 pub mod auth;
+pub mod catalogue;
+pub mod diagnostics;
+pub mod historical;
+pub mod kill_switch;
+pub mod logs;
+pub mod markets;
+pub mod navigation;
+pub mod orders;
+pub mod risk;
 pub mod rpc;
+pub mod stops;
+pub mod strategies;
 pub mod stream;
@@ -0,0 +1,234 @@
+use crate::clock::now_ms;
+use crate::commands::catalogue;
+use crate::commands::rpc::call_checked;
+use crate::market::catalogue::{DEFAULT_MAX_AGE_MS, MAX_LIST_RESULTS};
+use crate::market::navigation::{self, NavLevel, NavNode, NodeKind, NodeRef};
+use crate::state::AppState;
+use crate::ui_error::UiErrorPayload;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, State};
+use tracing::info;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavigationArgs {
+    /// Node to expand; event types when unset.
+    pub parent: Option<NodeRef>,
+    /// Market type codes (WIN, MATCH_ODDS, ...) to browse; all when empty.
+    #[serde(default)]
+    pub market_types: Vec<String>,
+    /// Fetch again even if the cached level has not expired.
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+fn invalid_response(method: &str, e: serde_json::Error) -> UiErrorPayload {
+    UiErrorPayload::with_values(
+        "errors:navigation.invalidResponse",
+        serde_json::json!({ "method": method, "details": e.to_string() }),
+    )
+}
+
+async fn list(
+    app: &AppHandle,
+    state: &AppState,
+    method: &str,
+    filter: &serde_json::Value,
+    parse: fn(serde_json::Value) -> serde_json::Result<Vec<NavNode>>,
+) -> Result<Vec<NavNode>, UiErrorPayload> {
+    let params = serde_json::json!({ "filter": filter });
+    let result = call_checked(app, state, "betting", method, params).await?;
+    parse(result).map_err(|e| invalid_response(method, e))
+}
+
+/// Events of the event type in `filter` that no competition lists.
+async fn other_events(
+    app: &AppHandle,
+    state: &AppState,
+    filter: &serde_json::Value,
+    competitions: &[NavNode],
+) -> Result<Vec<NavNode>, UiErrorPayload> {
+    let all = list(app, state, "listEvents", filter, navigation::events).await?;
+    if competitions.is_empty() {
+        return Ok(all);
+    }
+    let mut filter = filter.clone();
+    let ids: Vec<&str> = competitions.iter().map(|c| c.id.as_str()).collect();
+    filter["competitionIds"] = serde_json::json!(ids);
+    let listed = list(app, state, "listEvents", &filter, navigation::events).await?;
+    Ok(navigation::other_events(all, &listed))
+}
+
+/// Children of a tree node, from the cache until its level's TTL runs out.
+/// Event types without competitions (horse racing) list their events
+/// directly; otherwise events outside every competition go under an
+/// "Other events" node after them. Events list their markets through the
+/// catalogue store.
+#[tauri::command]
+pub async fn navigation_children(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: NavigationArgs,
+) -> Result<NavLevel, UiErrorPayload> {
+    info!(parent = ?args.parent, refresh = args.refresh, "navigation_children");
+    let parent = args.parent;
+    let mut market_types: Vec<String> = args
+        .market_types
+        .iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    market_types.sort_unstable();
+    market_types.dedup();
+    if parent.as_ref().is_some_and(|p| p.id.trim().is_empty()) {
+        return Err(UiErrorPayload::key("errors:validation.nodeIdRequired"));
+    }
+
+    let now = now_ms();
+    if !args.refresh {
+        if let Some(level) = state.navigation.level(parent.as_ref(), &market_types, now) {
+            return Ok(level);
+        }
+    }
+
+    let mut filter = serde_json::json!({});
+    if !market_types.is_empty() {
+        filter["marketTypeCodes"] = serde_json::json!(market_types);
+    }
+    let (kind, children) = match &parent {
+        None => (
+            NodeKind::EventType,
+            list(
+                &app,
+                &state,
+                "listEventTypes",
+                &filter,
+                navigation::event_types,
+            )
+            .await?,
+        ),
+        Some(NodeRef {
+            kind: NodeKind::EventType,
+            id,
+        }) => {
+            filter["eventTypeIds"] = serde_json::json!([id]);
+            let competitions = list(
+                &app,
+                &state,
+                "listCompetitions",
+                &filter,
+                navigation::competitions,
+            )
+            .await?;
+            if competitions.is_empty() {
+                (
+                    NodeKind::Event,
+                    list(&app, &state, "listEvents", &filter, navigation::events).await?,
+                )
+            } else {
+                let mut children = competitions;
+                let others = other_events(&app, &state, &filter, &children).await?;
+                if !others.is_empty() {
+                    let node = navigation::other_events_node(id, &others);
+                    // Already fetched, so expanding it costs nothing.
+                    state.navigation.insert(NavLevel {
+                        parent: Some(NodeRef {
+                            kind: node.kind,
+                            id: node.id.clone(),
+                        }),
+                        market_types: market_types.clone(),
+                        children: others,
+                        fetched_ms: now,
+                        expires_ms: now + NodeKind::Event.ttl_ms(),
+                    });
+                    children.push(node);
+                }
+                (NodeKind::Competition, children)
+            }
+        }
+        Some(NodeRef {
+            kind: NodeKind::Competition,
+            id,
+        }) => {
+            let events = match navigation::other_events_of(id) {
+                Some(event_type_id) => {
+                    filter["eventTypeIds"] = serde_json::json!([event_type_id]);
+                    let competitions = list(
+                        &app,
+                        &state,
+                        "listCompetitions",
+                        &filter,
+                        navigation::competitions,
+                    )
+                    .await?;
+                    other_events(&app, &state, &filter, &competitions).await?
+                }
+                None => {
+                    filter["competitionIds"] = serde_json::json!([id]);
+                    list(&app, &state, "listEvents", &filter, navigation::events).await?
+                }
+            };
+            (NodeKind::Event, events)
+        }
+        Some(NodeRef {
+            kind: NodeKind::Event,
+            id,
+        }) => {
+            filter["eventIds"] = serde_json::json!([id]);
+            let (ids, _) =
+                catalogue::refresh(&app, &state, filter, MAX_LIST_RESULTS, DEFAULT_MAX_AGE_MS)
+                    .await?;
+            let markets = ids
+                .iter()
+                .filter_map(|id| state.catalogue.market(id))
+                .map(|m| navigation::market(&m))
+                .collect();
+            (NodeKind::Market, markets)
+        }
+        Some(NodeRef {
+            kind: NodeKind::Market,
+            ..
+        }) => {
+            return Err(UiErrorPayload::key("errors:navigation.noChildren"));
+        }
+    };
+
+    let level = NavLevel {
+        parent,
+        market_types,
+        children,
+        fetched_ms: now,
+        expires_ms: now + kind.ttl_ms(),
+    };
+    info!(children = level.children.len(), "navigation level fetched");
+    state.navigation.insert(level.clone());
+    Ok(level)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketTypeCount {
+    pub market_type: String,
+    #[serde(default)]
+    pub market_count: u32,
+}
+
+/// Market type codes on offer, for the tree's market type filter.
+#[tauri::command]
+pub async fn navigation_market_types(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    event_type_id: Option<String>,
+) -> Result<Vec<MarketTypeCount>, UiErrorPayload> {
+    info!(event_type = ?event_type_id, "navigation_market_types");
+    let filter = match event_type_id {
+        Some(id) => serde_json::json!({ "eventTypeIds": [id] }),
+        None => serde_json::json!({}),
+    };
+    let params = serde_json::json!({ "filter": filter });
+    let result = call_checked(&app, &state, "betting", "listMarketTypes", params).await?;
+    let mut types: Vec<MarketTypeCount> =
+        serde_json::from_value(result).map_err(|e| invalid_response("listMarketTypes", e))?;
+    types.sort_by_key(|t| std::cmp::Reverse(t.market_count));
+    Ok(types)
+}
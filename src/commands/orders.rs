@@ -0,0 +1,510 @@
+use crate::commands::rpc::{call_checked, check_placement};
+use crate::orders::cache::TrackedOrder;
+use crate::orders::hedge::{plan, HedgePlan, DEFAULT_MIN_STAKE};
+use crate::orders::place::{
+    cancel_instruction, limit_on_close, limit_order_with, market_on_close, new_customer_ref,
+    place_orders_params, replace_instruction, update_instruction, validate_ref, OrderSide,
+    Persistence, MAX_ORDER_REF, MAX_STRATEGY_REF,
+};
+use crate::orders::position::{market_position, MarketPosition};
+use crate::orders::result::{check_report, OrderResult};
+use crate::orders::EVENT_POSITION;
+use crate::state::AppState;
+use crate::stream::ocm::OrderStatus;
+use crate::stream::StreamRole;
+use crate::ui_error::UiErrorPayload;
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+use tracing::info;
+
+/// How long order commands wait for the order stream by default.
+pub const DEFAULT_CONFIRM_TIMEOUT_MS: u64 = 3000;
+pub const MAX_CONFIRM_TIMEOUT_MS: u64 = 30_000;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceOrderArgs {
+    pub market_id: String,
+    pub selection_id: u64,
+    #[serde(default)]
+    pub handicap: f64,
+    pub side: OrderSide,
+    pub price: f64,
+    pub size: f64,
+    #[serde(default)]
+    pub persistence: Persistence,
+    /// Generated when unset so the order stream can be matched back.
+    pub customer_order_ref: Option<String>,
+    pub customer_strategy_ref: Option<String>,
+    pub customer_ref: Option<String>,
+    /// Wait for the order stream up to this long (default 3000, 0 = don't).
+    pub confirm_timeout_ms: Option<u64>,
+}
+
+/// Places a LIMIT back or lay order and waits for the order stream to show it.
+#[tauri::command]
+pub async fn orders_place(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: PlaceOrderArgs,
+) -> Result<OrderResult, UiErrorPayload> {
+    info!(
+        market_id = %args.market_id,
+        selection_id = args.selection_id,
+        side = ?args.side,
+        price = args.price,
+        size = args.size,
+        "orders_place"
+    );
+    place(&app, &state, args).await
+}
+
+/// `orders_place` without the command wrapper; also used for strategy intents.
+pub async fn place(
+    app: &AppHandle,
+    state: &AppState,
+    args: PlaceOrderArgs,
+) -> Result<OrderResult, UiErrorPayload> {
+    let timeout = confirm_timeout(args.confirm_timeout_ms)?;
+    if !(args.size.is_finite() && args.size > 0.0) {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.invalidSize",
+            serde_json::json!({ "size": args.size }),
+        ));
+    }
+    let market_id = args.market_id.trim();
+    let order_ref = args.customer_order_ref.unwrap_or_else(new_customer_ref);
+    validate_ref(&order_ref, MAX_ORDER_REF)?;
+    if let Some(r) = &args.customer_strategy_ref {
+        validate_ref(r, MAX_STRATEGY_REF)?;
+    }
+    if let Some(r) = &args.customer_ref {
+        validate_ref(r, MAX_ORDER_REF)?;
+    }
+    // Fail with the nearest valid price before anything is sent.
+    let price = state.markets.tick_ladder(market_id).validate(args.price)?;
+
+    let instruction = limit_order_with(
+        args.selection_id,
+        args.handicap,
+        args.side,
+        args.size,
+        price,
+        args.persistence,
+        Some(&order_ref),
+    );
+    let params = place_orders_params(
+        market_id,
+        vec![instruction],
+        args.customer_ref.as_deref(),
+        args.customer_strategy_ref.as_deref(),
+    );
+    let report = call_checked(app, state, "betting", "placeOrders", params).await?;
+    let Some(ins) = check_report(&report)?.first() else {
+        return Err(UiErrorPayload::key("errors:orders.noReport"));
+    };
+    let mut result = OrderResult::from_place_report(market_id, ins);
+    result.customer_strategy_ref = args.customer_strategy_ref;
+    Ok(confirm(state, &result.bet_id.clone(), timeout, |_| true)
+        .await
+        .unwrap_or(result))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelOrdersArgs {
+    pub market_id: String,
+    /// Every unmatched bet on the market when empty.
+    #[serde(default)]
+    pub bet_ids: Vec<String>,
+    /// Partial cancel; only with a single bet id.
+    pub size_reduction: Option<f64>,
+    pub customer_ref: Option<String>,
+    pub confirm_timeout_ms: Option<u64>,
+}
+
+/// Cancels bets by id, or all of ours on a market, and reports each bet.
+#[tauri::command]
+pub async fn orders_cancel(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: CancelOrdersArgs,
+) -> Result<Vec<OrderResult>, UiErrorPayload> {
+    info!(
+        market_id = %args.market_id,
+        bets = args.bet_ids.len(),
+        "orders_cancel"
+    );
+    cancel(&app, &state, args).await
+}
+
+pub async fn cancel(
+    app: &AppHandle,
+    state: &AppState,
+    args: CancelOrdersArgs,
+) -> Result<Vec<OrderResult>, UiErrorPayload> {
+    let timeout = confirm_timeout(args.confirm_timeout_ms)?;
+    if args.size_reduction.is_some() && args.bet_ids.len() != 1 {
+        return Err(UiErrorPayload::key(
+            "errors:validation.sizeReductionSingleBet",
+        ));
+    }
+    let market_id = args.market_id.trim();
+    let targets: Vec<String> = if args.bet_ids.is_empty() {
+        state
+            .orders
+            .market_unmatched(market_id)
+            .into_iter()
+            .map(|o| o.id)
+            .collect()
+    } else {
+        args.bet_ids.clone()
+    };
+    let before: HashMap<String, f64> = targets
+        .iter()
+        .filter_map(|id| Some((id.clone(), state.orders.bet(id)?.order.sc)))
+        .collect();
+
+    let mut params = serde_json::json!({
+        "marketId": market_id,
+        "customerRef": args.customer_ref.clone().unwrap_or_else(new_customer_ref),
+    });
+    if !args.bet_ids.is_empty() {
+        params["instructions"] = args
+            .bet_ids
+            .iter()
+            .map(|id| cancel_instruction(id, args.size_reduction))
+            .collect();
+    }
+    let report = call_checked(app, state, "betting", "cancelOrders", params).await?;
+    let reports = match report.get("instructionReports").and_then(|v| v.as_array()) {
+        // Per-bet errors come back as a FAILURE with one report per bet.
+        Some(r) if !r.is_empty() => r.as_slice(),
+        _ => check_report(&report)?,
+    };
+    let by_bet: HashMap<&str, &serde_json::Value> = reports
+        .iter()
+        .filter_map(|r| Some((r.pointer("/instruction/betId")?.as_str()?, r)))
+        .collect();
+
+    // The waits run side by side, so the whole cancel waits `timeout` at most.
+    let waits = targets.iter().map(|id| {
+        let report = by_bet.get(id.as_str()).copied();
+        let error_code = report
+            .and_then(|r| r.get("errorCode"))
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        let sc_before = before.get(id).copied().unwrap_or(0.0);
+        let wait = if error_code.is_some() {
+            Duration::ZERO
+        } else {
+            timeout
+        };
+        async move {
+            let confirmed = confirm(state, id, wait, |t| {
+                t.order.status == OrderStatus::ExecutionComplete || t.order.sc > sc_before
+            })
+            .await;
+            let mut result = confirmed.unwrap_or_else(|| {
+                let mut r = OrderResult::unconfirmed(market_id, id, None);
+                r.size_cancelled = report
+                    .and_then(|r| r.get("sizeCancelled"))
+                    .and_then(|v| v.as_f64())
+                    .unwrap_or(0.0);
+                r
+            });
+            result.error_code = error_code;
+            result
+        }
+    });
+    Ok(join_all(waits).await)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceOrderArgs {
+    pub market_id: String,
+    pub bet_id: String,
+    pub new_price: f64,
+    pub customer_ref: Option<String>,
+    pub confirm_timeout_ms: Option<u64>,
+}
+
+/// Moves an unmatched bet to a new price. Betfair cancels it and places a
+/// new bet; the result describes the new bet.
+#[tauri::command]
+pub async fn orders_replace(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: ReplaceOrderArgs,
+) -> Result<OrderResult, UiErrorPayload> {
+    info!(
+        market_id = %args.market_id,
+        bet_id = %args.bet_id,
+        new_price = args.new_price,
+        "orders_replace"
+    );
+    replace(&app, &state, args).await
+}
+
+pub async fn replace(
+    app: &AppHandle,
+    state: &AppState,
+    args: ReplaceOrderArgs,
+) -> Result<OrderResult, UiErrorPayload> {
+    let timeout = confirm_timeout(args.confirm_timeout_ms)?;
+    let market_id = args.market_id.trim();
+    let new_price = state
+        .markets
+        .tick_ladder(market_id)
+        .validate(args.new_price)?;
+    let params = serde_json::json!({
+        "marketId": market_id,
+        "instructions": [replace_instruction(&args.bet_id, new_price)],
+        "customerRef": args.customer_ref.unwrap_or_else(new_customer_ref),
+    });
+    let report = call_checked(app, state, "betting", "replaceOrders", params).await?;
+    let Some(place) = check_report(&report)?
+        .first()
+        .and_then(|r| r.get("placeInstructionReport"))
+    else {
+        return Err(UiErrorPayload::key("errors:orders.noReport"));
+    };
+    let result = OrderResult::from_place_report(market_id, place);
+    Ok(confirm(state, &result.bet_id.clone(), timeout, |_| true)
+        .await
+        .unwrap_or(result))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateOrderArgs {
+    pub market_id: String,
+    pub bet_id: String,
+    pub persistence: Persistence,
+    pub customer_ref: Option<String>,
+    pub confirm_timeout_ms: Option<u64>,
+}
+
+/// Changes what happens to an unmatched bet at the off.
+#[tauri::command]
+pub async fn orders_update(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: UpdateOrderArgs,
+) -> Result<OrderResult, UiErrorPayload> {
+    info!(
+        market_id = %args.market_id,
+        bet_id = %args.bet_id,
+        persistence = ?args.persistence,
+        "orders_update"
+    );
+    let timeout = confirm_timeout(args.confirm_timeout_ms)?;
+    let market_id = args.market_id.trim();
+    let params = serde_json::json!({
+        "marketId": market_id,
+        "instructions": [update_instruction(&args.bet_id, args.persistence)],
+        "customerRef": args.customer_ref.unwrap_or_else(new_customer_ref),
+    });
+    let report = call_checked(&app, &state, "betting", "updateOrders", params).await?;
+    check_report(&report)?;
+    let code = args.persistence.stream_code();
+    Ok(confirm(&state, &args.bet_id, timeout, |t| {
+        t.order.pt.as_deref() == Some(code)
+    })
+    .await
+    .unwrap_or_else(|| OrderResult::unconfirmed(market_id, &args.bet_id, None)))
+}
+
+fn confirm_timeout(ms: Option<u64>) -> Result<Duration, UiErrorPayload> {
+    let ms = ms.unwrap_or(DEFAULT_CONFIRM_TIMEOUT_MS);
+    if ms > MAX_CONFIRM_TIMEOUT_MS {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.confirmTimeoutOutOfRange",
+            serde_json::json!({ "min": 0, "max": MAX_CONFIRM_TIMEOUT_MS }),
+        ));
+    }
+    Ok(Duration::from_millis(ms))
+}
+
+/// The bet as the order stream reports it once `done`, or its last streamed
+/// state (unconfirmed) on timeout; `None` without an order stream or if the
+/// bet never showed up.
+async fn confirm(
+    state: &AppState,
+    bet_id: &str,
+    timeout: Duration,
+    done: impl Fn(&TrackedOrder) -> bool,
+) -> Option<OrderResult> {
+    if bet_id.is_empty() || !state.streams.read().await.contains_key(&StreamRole::Orders) {
+        return None;
+    }
+    match state.orders.wait_for(bet_id, timeout, done).await {
+        Ok(t) => Some(OrderResult::from_tracked(&t, true)),
+        Err(last) => last.map(|t| OrderResult::from_tracked(&t, false)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceBspArgs {
+    pub market_id: String,
+    pub selection_id: u64,
+    #[serde(default)]
+    pub handicap: f64,
+    pub side: OrderSide,
+    /// Backer's stake or layer's liability.
+    pub liability: f64,
+    /// LIMIT_ON_CLOSE at this price; MARKET_ON_CLOSE when unset.
+    pub limit_price: Option<f64>,
+    pub customer_ref: Option<String>,
+}
+
+/// Places one BSP order. Returns the raw `placeOrders` report.
+#[tauri::command]
+pub async fn orders_place_bsp(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: PlaceBspArgs,
+) -> Result<serde_json::Value, UiErrorPayload> {
+    info!(
+        market_id = %args.market_id,
+        selection_id = args.selection_id,
+        side = ?args.side,
+        limit = args.limit_price.is_some(),
+        "orders_place_bsp"
+    );
+    let market_id = args.market_id.trim();
+    let instruction = match args.limit_price {
+        Some(price) => limit_on_close(
+            args.selection_id,
+            args.handicap,
+            args.side,
+            args.liability,
+            state.markets.tick_ladder(market_id).validate(price)?,
+        )?,
+        None => market_on_close(args.selection_id, args.handicap, args.side, args.liability)?,
+    };
+    let params = place_orders_params(
+        market_id,
+        vec![instruction],
+        args.customer_ref.as_deref(),
+        None,
+    );
+    call_checked(&app, &state, "betting", "placeOrders", params).await
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HedgeArgs {
+    pub market_id: String,
+    /// Close out one selection only; the whole market when unset.
+    pub selection_id: Option<u64>,
+    #[serde(default)]
+    pub handicap: f64,
+    /// Return the plan without placing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Account currency minimum stake (default 1).
+    pub min_stake: Option<f64>,
+    pub customer_ref: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HedgeResult {
+    #[serde(flatten)]
+    pub plan: HedgePlan,
+    pub dry_run: bool,
+    /// `placeOrders` result when placed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<serde_json::Value>,
+}
+
+/// Greens up a market (or closes out one selection) at current best prices.
+/// The plan passes the pre-trade checks in dry runs too, so a dry run that
+/// succeeds would have been sent.
+#[tauri::command]
+pub async fn orders_hedge(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: HedgeArgs,
+) -> Result<HedgeResult, UiErrorPayload> {
+    info!(
+        market_id = %args.market_id,
+        selection_id = ?args.selection_id,
+        dry_run = args.dry_run,
+        "orders_hedge"
+    );
+    let market_id = args.market_id.trim();
+    let min_stake = args.min_stake.unwrap_or(DEFAULT_MIN_STAKE);
+    if !(min_stake.is_finite() && min_stake > 0.0) {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.invalidMinStake",
+            serde_json::json!({ "minStake": min_stake }),
+        ));
+    }
+    let Some(position) = position(&state, market_id) else {
+        return Err(UiErrorPayload::key("errors:orders.noPosition"));
+    };
+    let only = args.selection_id.map(|id| (id, args.handicap));
+    let plan = plan(
+        &position,
+        &state.markets.tick_ladder(market_id),
+        only,
+        min_stake,
+    );
+    if plan.instructions.is_empty() {
+        if args.dry_run {
+            return Ok(HedgeResult {
+                plan,
+                dry_run: true,
+                report: None,
+            });
+        }
+        return Err(UiErrorPayload::key("errors:orders.nothingToHedge"));
+    }
+
+    let params = place_orders_params(
+        market_id,
+        plan.instructions.clone(),
+        args.customer_ref.as_deref(),
+        None,
+    );
+    let report = if args.dry_run {
+        check_placement(&state, "placeOrders", &params).await?;
+        None
+    } else {
+        Some(call_checked(&app, &state, "betting", "placeOrders", params).await?)
+    };
+    Ok(HedgeResult {
+        plan,
+        dry_run: args.dry_run,
+        report,
+    })
+}
+
+/// P&L and hedges for a market we have orders on; `None` otherwise.
+#[tauri::command]
+pub async fn orders_position(
+    state: State<'_, AppState>,
+    market_id: String,
+) -> Result<Option<MarketPosition>, UiErrorPayload> {
+    Ok(position(&state, market_id.trim()))
+}
+
+pub fn position(state: &AppState, market_id: &str) -> Option<MarketPosition> {
+    let orders = state.orders.market(market_id)?;
+    let market = state.markets.market(market_id);
+    Some(market_position(market_id, market.as_ref(), &orders))
+}
+
+/// Emits `EVENT_POSITION` if we have orders on the market.
+pub fn emit_position(app: &AppHandle, state: &AppState, market_id: &str) {
+    if let Some(position) = position(state, market_id) {
+        let _ = app.emit(EVENT_POSITION, position);
+    }
+}
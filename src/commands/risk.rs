@@ -0,0 +1,25 @@
+use crate::risk::limits::RiskLimits;
+use crate::state::AppState;
+use crate::ui_error::UiErrorPayload;
+use tauri::State;
+use tracing::info;
+
+#[tauri::command]
+pub async fn risk_limits(state: State<'_, AppState>) -> Result<RiskLimits, UiErrorPayload> {
+    Ok(*state.risk_limits.read().await)
+}
+
+#[tauri::command]
+pub async fn risk_set_limits(
+    state: State<'_, AppState>,
+    limits: RiskLimits,
+) -> Result<RiskLimits, UiErrorPayload> {
+    info!(
+        max_order_liability = ?limits.max_order_liability,
+        max_market_liability = ?limits.max_market_liability,
+        "risk_set_limits"
+    );
+    limits.validate()?;
+    *state.risk_limits.write().await = limits;
+    Ok(limits)
+}
@@ -1,8 +1,12 @@
 use crate::betfair;
+use crate::commands::kill_switch;
+use crate::risk::kill_switch::is_placement_method;
+use crate::risk::limits::{check_liability, order_liability};
+use crate::risk::prices::check_order_prices;
 use crate::state::AppState;
 use crate::ui_error::UiErrorPayload;
 use serde::Deserialize;
-use tauri::State;
+use tauri::{AppHandle, State};
 use tracing::{info, warn};
 
 #[derive(Debug, Deserialize)]
@@ -15,30 +19,77 @@ pub struct RpcArgs {
 
 #[tauri::command]
 pub async fn betfair_rpc(
+    app: AppHandle,
     state: State<'_, AppState>,
     args: RpcArgs,
 ) -> Result<serde_json::Value, UiErrorPayload> {
     info!(service = %args.service, method = %args.method, "betfair_rpc");
-    if !betfair::rpc::is_method_allowed(&state, &args.service, &args.method) {
+    call_checked(&app, &state, &args.service, &args.method, args.params).await
+}
+
+/// Calls Betfair through the same gates as `betfair_rpc`: allowlist, kill
+/// switch, tick prices and liability limits. Order commands go through here.
+pub async fn call_checked(
+    app: &AppHandle,
+    state: &AppState,
+    service: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value, UiErrorPayload> {
+    if !betfair::rpc::is_method_allowed(state, service, method) {
         return Err(UiErrorPayload::key("errors:validation.methodNotAllowed"));
     }
 
-    let token = state.session_token.read().await;
-    let token = token.as_deref().unwrap_or("");
-    if token.is_empty() {
-        return Err(UiErrorPayload::key("errors:auth.notLoggedIn"));
+    if is_placement_method(service, method) {
+        check_placement(state, method, &params).await?;
+    }
+
+    let result = betfair::rpc::call(state, service, method, params)
+        .await
+        .inspect_err(|e| {
+            warn!(key = %e.key, "betfair_rpc failed");
+        });
+
+    if let Err(e) = &result {
+        if betfair::rpc::is_session_error(e) {
+            kill_switch::on_session_expired(app, state).await;
+        }
+    }
+
+    result
+}
+
+/// Pre-trade checks for `placeOrders` / `replaceOrders`: kill switch, tick
+/// prices and liability limits. Also used by dry runs.
+pub async fn check_placement(
+    state: &AppState,
+    method: &str,
+    params: &serde_json::Value,
+) -> Result<(), UiErrorPayload> {
+    if state.kill_switch.is_locked().await {
+        return Err(UiErrorPayload::key("errors:risk.killSwitchEngaged"));
+    }
+    check_order_prices(method, params, |m| state.markets.tick_ladder(m))?;
+    if method == "placeOrders" {
+        check_market_liability(state, params).await?;
     }
+    Ok(())
+}
 
-    betfair::rpc::call_json_rpc(
-        &state.http,
-        state.app_key.as_str(),
-        token,
-        &args.service,
-        &args.method,
-        args.params,
-    )
-    .await
-    .inspect_err(|e| {
-        warn!(key = %e.key, "betfair_rpc failed");
-    })
+async fn check_market_liability(
+    state: &AppState,
+    params: &serde_json::Value,
+) -> Result<(), UiErrorPayload> {
+    let limits = *state.risk_limits.read().await;
+    let Some(market_id) = params.get("marketId").and_then(|v| v.as_str()) else {
+        return Ok(());
+    };
+    let ladder = state.markets.tick_ladder(market_id);
+    let open: f64 = state
+        .orders
+        .market_unmatched(market_id)
+        .iter()
+        .map(|o| order_liability(o, &ladder))
+        .sum();
+    check_liability(&limits, params, &ladder, open)
 }
@@ -0,0 +1,318 @@
+use crate::clock::now_ms;
+use crate::commands::orders::position;
+use crate::commands::rpc::call_checked;
+use crate::market::cache::runner_key;
+use crate::orders::hedge::{plan, DEFAULT_MIN_STAKE};
+use crate::orders::place::{place_orders_params, OrderSide};
+use crate::orders::result::check_report;
+use crate::orders::stops::{
+    crossed, hedge_price, new_stop_id, position_side, trigger_from, Evaluation, StopBookStatus,
+    StopKind, StopOrder, StopStatus, StopTrigger,
+};
+use crate::orders::EVENT_STOP;
+use crate::state::AppState;
+use crate::ui_error::UiErrorPayload;
+use serde::Deserialize;
+use tauri::{AppHandle, Emitter, State};
+use tracing::{info, warn};
+
+/// `customerStrategyRef` of every stop hedge.
+pub const STOP_STRATEGY_REF: &str = "bsa-stop";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateStopArgs {
+    pub market_id: String,
+    pub selection_id: u64,
+    #[serde(default)]
+    pub handicap: f64,
+    pub kind: StopKind,
+    /// Absolute trigger price (stop-loss / take-profit).
+    pub price: Option<f64>,
+    /// Ticks from the average matched price, or for trailing stops from the
+    /// best hedge price seen.
+    pub ticks: Option<u32>,
+    /// Account currency minimum stake (default 1).
+    pub min_stake: Option<f64>,
+}
+
+#[tauri::command]
+pub async fn stops_list(
+    state: State<'_, AppState>,
+    market_id: Option<String>,
+) -> Result<Vec<StopOrder>, UiErrorPayload> {
+    Ok(state.stops.list(market_id.as_deref().map(str::trim)))
+}
+
+/// Whether stops are saved, and why the saved ones didn't load if they didn't.
+#[tauri::command]
+pub async fn stops_status(state: State<'_, AppState>) -> Result<StopBookStatus, UiErrorPayload> {
+    Ok(state.stops.status())
+}
+
+/// Arms a stop on an open position. The stop closes the whole selection
+/// position when it fires.
+#[tauri::command]
+pub async fn stops_create(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: CreateStopArgs,
+) -> Result<StopOrder, UiErrorPayload> {
+    info!(
+        market_id = %args.market_id,
+        selection_id = args.selection_id,
+        kind = ?args.kind,
+        price = ?args.price,
+        ticks = ?args.ticks,
+        "stops_create"
+    );
+    let market_id = args.market_id.trim();
+    let min_stake = args.min_stake.unwrap_or(DEFAULT_MIN_STAKE);
+    if !(min_stake.is_finite() && min_stake > 0.0) {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.invalidMinStake",
+            serde_json::json!({ "minStake": min_stake }),
+        ));
+    }
+    let runner = position(&state, market_id).and_then(|p| {
+        p.runners
+            .into_iter()
+            .find(|r| r.selection_id == args.selection_id && r.handicap == args.handicap)
+    });
+    let Some((runner, side)) = runner.and_then(|r| position_side(&r).map(|s| (r, s))) else {
+        return Err(UiErrorPayload::key("errors:orders.noPosition"));
+    };
+    let entry = match side {
+        OrderSide::Back => runner.back_avg_price,
+        OrderSide::Lay => runner.lay_avg_price,
+    };
+    let ladder = state.markets.tick_ladder(market_id);
+    let invalid = || UiErrorPayload::key("errors:stops.invalidTrigger");
+    let current = state
+        .markets
+        .runner(market_id, args.selection_id, args.handicap)
+        .and_then(|r| hedge_price(side, r.best_back(), r.best_lay()));
+
+    let (trigger_price, anchor_price) = match (args.kind, args.price, args.ticks) {
+        (StopKind::TrailingStop, None, Some(ticks)) if ticks > 0 => {
+            let from = current.or(entry).ok_or_else(invalid)?;
+            let trigger =
+                trigger_from(&ladder, args.kind, side, from, ticks).ok_or_else(invalid)?;
+            (trigger, Some(from))
+        }
+        (StopKind::StopLoss | StopKind::TakeProfit, Some(price), None) => {
+            let price = ladder.validate(price)?;
+            // On the wrong side of the market it would fire straight away.
+            if let Some(current) = current.filter(|c| crossed(args.kind, side, price, *c)) {
+                return Err(UiErrorPayload::with_values(
+                    "errors:stops.triggerPassed",
+                    serde_json::json!({ "price": price, "current": current }),
+                ));
+            }
+            (price, None)
+        }
+        (StopKind::StopLoss | StopKind::TakeProfit, None, Some(ticks)) if ticks > 0 => {
+            let from = entry.ok_or_else(invalid)?;
+            let trigger =
+                trigger_from(&ladder, args.kind, side, from, ticks).ok_or_else(invalid)?;
+            (trigger, None)
+        }
+        _ => return Err(invalid()),
+    };
+
+    let now = now_ms();
+    let stop = StopOrder {
+        id: new_stop_id(),
+        market_id: market_id.to_string(),
+        selection_id: args.selection_id,
+        handicap: args.handicap,
+        kind: args.kind,
+        position_side: side,
+        trigger_price,
+        trail_ticks: (args.kind == StopKind::TrailingStop)
+            .then_some(args.ticks)
+            .flatten(),
+        anchor_price,
+        min_stake,
+        status: StopStatus::Active,
+        created_at_ms: now,
+        updated_at_ms: now,
+        trigger: None,
+    };
+    state.stops.insert(stop.clone());
+    let _ = app.emit(EVENT_STOP, &stop);
+    Ok(stop)
+}
+
+#[tauri::command]
+pub async fn stops_cancel(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<StopOrder, UiErrorPayload> {
+    info!(id = %id, "stops_cancel");
+    let Some(stop) = state
+        .stops
+        .update_active(id.trim(), |s| s.status = StopStatus::Cancelled)
+    else {
+        return Err(UiErrorPayload::key("errors:stops.notActive"));
+    };
+    let _ = app.emit(EVENT_STOP, &stop);
+    Ok(stop)
+}
+
+/// Checks a market's active stops against its latest prices and position.
+/// Called by the market publisher, so stops run at the publish rate.
+pub async fn check_market(app: &AppHandle, state: &AppState, market_id: &str) {
+    let stops = state.stops.active(market_id);
+    if stops.is_empty() {
+        return;
+    }
+    // Stops resume once the kill switch is re-armed.
+    if state.kill_switch.is_locked().await {
+        return;
+    }
+    let (Some(market), Some(position)) =
+        (state.markets.market(market_id), position(state, market_id))
+    else {
+        // Order stream not caught up (e.g. right after a restart).
+        return;
+    };
+
+    for mut stop in stops {
+        let side = position
+            .runners
+            .iter()
+            .find(|r| r.selection_id == stop.selection_id && r.handicap == stop.handicap)
+            .and_then(position_side);
+        if side != Some(stop.position_side) {
+            info!(id = %stop.id, "position closed before stop fired");
+            finish(app, state, &stop.id, StopStatus::Closed);
+            continue;
+        }
+        let price = market
+            .runners
+            .get(&runner_key(stop.selection_id, stop.handicap))
+            .and_then(|r| hedge_price(stop.position_side, r.best_back(), r.best_lay()));
+        let Some(price) = price else {
+            continue;
+        };
+        match stop.evaluate(price, &market.tick_ladder) {
+            Evaluation::Unchanged => {}
+            Evaluation::Trailed => {
+                let (trigger, anchor) = (stop.trigger_price, stop.anchor_price);
+                if let Some(stop) = state.stops.trail(&stop.id, |s| {
+                    s.trigger_price = trigger;
+                    s.anchor_price = anchor;
+                }) {
+                    let _ = app.emit(EVENT_STOP, &stop);
+                }
+            }
+            Evaluation::Fire => {
+                let trigger_price = stop.trigger_price;
+                let Some(stop) = state.stops.update_active(&stop.id, |s| {
+                    s.trigger_price = trigger_price;
+                    s.status = StopStatus::Triggered;
+                    s.trigger = Some(StopTrigger {
+                        at_ms: now_ms(),
+                        price,
+                        bet_ids: Vec::new(),
+                        error: None,
+                    });
+                }) else {
+                    continue;
+                };
+                warn!(
+                    id = %stop.id,
+                    market_id = %stop.market_id,
+                    selection_id = stop.selection_id,
+                    kind = ?stop.kind,
+                    trigger_price = stop.trigger_price,
+                    price,
+                    "stop triggered"
+                );
+                state.audit.record_trigger(
+                    "stopTriggered",
+                    &stop.market_id,
+                    &stop.id,
+                    serde_json::to_value(&stop).unwrap_or_default(),
+                );
+                let _ = app.emit(EVENT_STOP, &stop);
+                tauri::async_runtime::spawn(fire(app.clone(), state.clone(), stop));
+            }
+        }
+    }
+}
+
+/// Finishes every active stop of a closed market.
+pub fn close_market(app: &AppHandle, state: &AppState, market_id: &str) {
+    for stop in state.stops.active(market_id) {
+        finish(app, state, &stop.id, StopStatus::Closed);
+    }
+}
+
+fn finish(app: &AppHandle, state: &AppState, id: &str, status: StopStatus) {
+    if let Some(stop) = state.stops.update_active(id, |s| s.status = status) {
+        let _ = app.emit(EVENT_STOP, &stop);
+    }
+}
+
+/// Places the hedge of a triggered stop and records how it went.
+async fn fire(app: AppHandle, state: AppState, stop: StopOrder) {
+    let outcome = place_hedge(&app, &state, &stop).await;
+    if let Err(e) = &outcome {
+        warn!(id = %stop.id, key = %e.key, "stop hedge failed");
+        state.audit.record_trigger(
+            "stopFailed",
+            &stop.market_id,
+            &stop.id,
+            serde_json::json!({ "stopId": stop.id, "error": e }),
+        );
+    }
+    let updated = state.stops.update(&stop.id, |s| {
+        let Some(trigger) = s.trigger.as_mut() else {
+            return;
+        };
+        match outcome {
+            Ok(bet_ids) => trigger.bet_ids = bet_ids,
+            Err(e) => {
+                s.status = StopStatus::Failed;
+                trigger.error = Some(e);
+            }
+        }
+    });
+    if let Some(stop) = updated {
+        let _ = app.emit(EVENT_STOP, &stop);
+    }
+}
+
+async fn place_hedge(
+    app: &AppHandle,
+    state: &AppState,
+    stop: &StopOrder,
+) -> Result<Vec<String>, UiErrorPayload> {
+    let Some(position) = position(state, &stop.market_id) else {
+        return Err(UiErrorPayload::key("errors:orders.noPosition"));
+    };
+    let plan = plan(
+        &position,
+        &state.markets.tick_ladder(&stop.market_id),
+        Some((stop.selection_id, stop.handicap)),
+        stop.min_stake,
+    );
+    if plan.instructions.is_empty() {
+        return Err(UiErrorPayload::key("errors:orders.nothingToHedge"));
+    }
+    // The stop id as customerRef ties the placement to its audit trigger.
+    let params = place_orders_params(
+        &stop.market_id,
+        plan.instructions,
+        Some(&stop.id),
+        Some(STOP_STRATEGY_REF),
+    );
+    let report = call_checked(app, state, "betting", "placeOrders", params).await?;
+    Ok(check_report(&report)?
+        .iter()
+        .filter_map(|r| r.get("betId")?.as_str().map(str::to_string))
+        .collect())
+}
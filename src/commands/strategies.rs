@@ -0,0 +1,284 @@
+use crate::clock::now_ms;
+use crate::commands::orders::{self, CancelOrdersArgs, PlaceOrderArgs, ReplaceOrderArgs};
+use crate::market::lifecycle::LifecycleEvent;
+use crate::orders::position::{market_position, MarketPosition};
+use crate::state::AppState;
+use crate::strategy::runtime::{IntentBatch, StopReason, StrategyInfo};
+use crate::strategy::{OrderIntent, StrategyContext, EVENT_STRATEGY};
+use crate::ui_error::UiErrorPayload;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, State};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrategyDetails {
+    #[serde(flatten)]
+    pub info: StrategyInfo,
+    pub state: serde_json::Value,
+    /// One per market the strategy has orders on.
+    pub positions: Vec<MarketPosition>,
+}
+
+#[tauri::command]
+pub async fn strategies_list(
+    state: State<'_, AppState>,
+) -> Result<Vec<StrategyInfo>, UiErrorPayload> {
+    Ok(state.strategies.list())
+}
+
+#[tauri::command]
+pub async fn strategies_inspect(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<StrategyDetails, UiErrorPayload> {
+    let (info, strategy_state) = state.strategies.inspect(&id)?;
+    let positions = state
+        .orders
+        .strategy_markets(&info.strategy_ref)
+        .iter()
+        .filter_map(|m| strategy_position(&state, m, &info.strategy_ref))
+        .collect();
+    Ok(StrategyDetails {
+        info,
+        state: strategy_state,
+        positions,
+    })
+}
+
+#[tauri::command]
+pub async fn strategies_set_enabled(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+    enabled: bool,
+) -> Result<StrategyInfo, UiErrorPayload> {
+    info!(id = %id, enabled, "strategies_set_enabled");
+    let info = state.strategies.set_enabled(&id, enabled)?;
+    let _ = app.emit(EVENT_STRATEGY, &info);
+    Ok(info)
+}
+
+#[tauri::command]
+pub async fn strategies_start(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<StrategyInfo, UiErrorPayload> {
+    info!(id = %id, "strategies_start");
+    if state.kill_switch.is_locked().await {
+        return Err(UiErrorPayload::key("errors:risk.killSwitchEngaged"));
+    }
+    let (info, batch) = state.strategies.start(&id)?;
+    let _ = app.emit(EVENT_STRATEGY, &info);
+    run(&app, &state, batch);
+    Ok(info)
+}
+
+#[tauri::command]
+pub async fn strategies_stop(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<StrategyInfo, UiErrorPayload> {
+    info!(id = %id, "strategies_stop");
+    let info = state.strategies.stop(&id, StopReason::Manual)?;
+    let _ = app.emit(EVENT_STRATEGY, &info);
+    Ok(info)
+}
+
+/// Stops every running strategy, e.g. when the kill switch engages.
+pub fn stop_all(app: &AppHandle, state: &AppState, reason: StopReason) {
+    for info in state.strategies.stop_all(reason) {
+        let _ = app.emit(EVENT_STRATEGY, &info);
+    }
+}
+
+fn strategy_position(
+    state: &AppState,
+    market_id: &str,
+    strategy_ref: &str,
+) -> Option<MarketPosition> {
+    let orders = state.orders.strategy_market(market_id, strategy_ref)?;
+    let market = state.markets.market(market_id);
+    Some(market_position(market_id, market.as_ref(), &orders))
+}
+
+/// Hands a published market update to running strategies.
+pub fn on_market(app: &AppHandle, state: &AppState, market_id: &str) {
+    let Some(market) = state.markets.market(market_id) else {
+        return;
+    };
+    let now = now_ms();
+    let (batches, stopped) = state.strategies.dispatch(market_id, |s, info| {
+        let position = strategy_position(state, market_id, &info.strategy_ref);
+        let ctx = StrategyContext {
+            now_ms: now,
+            strategy_ref: &info.strategy_ref,
+            position: position.as_ref(),
+        };
+        s.on_market(&ctx, &market)
+    });
+    dispatched(app, state, batches, stopped);
+}
+
+/// Hands order stream changes to the strategies that own orders on the market.
+pub fn on_orders(app: &AppHandle, state: &AppState, market_id: &str) {
+    let now = now_ms();
+    let (batches, stopped) = state.strategies.dispatch(market_id, |s, info| {
+        let Some(orders) = state.orders.strategy_market(market_id, &info.strategy_ref) else {
+            return Vec::new();
+        };
+        let market = state.markets.market(market_id);
+        let position = market_position(market_id, market.as_ref(), &orders);
+        let ctx = StrategyContext {
+            now_ms: now,
+            strategy_ref: &info.strategy_ref,
+            position: Some(&position),
+        };
+        s.on_orders(&ctx, &orders)
+    });
+    dispatched(app, state, batches, stopped);
+}
+
+pub fn on_lifecycle(app: &AppHandle, state: &AppState, event: &LifecycleEvent) {
+    let now = now_ms();
+    let (batches, stopped) = state.strategies.dispatch(&event.market_id, |s, info| {
+        let position = strategy_position(state, &event.market_id, &info.strategy_ref);
+        let ctx = StrategyContext {
+            now_ms: now,
+            strategy_ref: &info.strategy_ref,
+            position: position.as_ref(),
+        };
+        s.on_lifecycle(&ctx, event)
+    });
+    dispatched(app, state, batches, stopped);
+}
+
+fn dispatched(
+    app: &AppHandle,
+    state: &AppState,
+    batches: Vec<IntentBatch>,
+    stopped: Vec<StrategyInfo>,
+) {
+    for info in stopped {
+        let _ = app.emit(EVENT_STRATEGY, &info);
+    }
+    for batch in batches {
+        run(app, state, Some(batch));
+    }
+}
+
+fn run(app: &AppHandle, state: &AppState, batch: Option<IntentBatch>) {
+    if let Some(batch) = batch {
+        tauri::async_runtime::spawn(execute(app.clone(), state.clone(), batch));
+    }
+}
+
+/// Sends a batch in order, giving up on the rest once the strategy stops.
+async fn execute(app: AppHandle, state: AppState, batch: IntentBatch) {
+    for intent in batch.intents {
+        if !state.strategies.is_running(&batch.id) {
+            info!(id = %batch.id, "strategy stopped; dropping intents");
+            return;
+        }
+        let outcome = send(&app, &state, &batch.strategy_ref, intent).await;
+        if let Err(e) = &outcome {
+            warn!(id = %batch.id, key = %e.key, "strategy intent failed");
+        }
+        if let Some(info) = state.strategies.record(&batch.id, &outcome) {
+            let _ = app.emit(EVENT_STRATEGY, &info);
+        }
+    }
+}
+
+/// Strategies may only touch their own bets.
+fn check_owned(state: &AppState, bet_id: &str, strategy_ref: &str) -> Result<(), UiErrorPayload> {
+    let owner = state.orders.bet(bet_id).and_then(|t| t.order.rfs);
+    if owner.as_deref() == Some(strategy_ref) {
+        return Ok(());
+    }
+    Err(UiErrorPayload::with_values(
+        "errors:strategies.notOwnBet",
+        serde_json::json!({ "betId": bet_id }),
+    ))
+}
+
+async fn send(
+    app: &AppHandle,
+    state: &AppState,
+    strategy_ref: &str,
+    intent: OrderIntent,
+) -> Result<(), UiErrorPayload> {
+    info!(strategy_ref, ?intent, "strategy intent");
+    match intent {
+        OrderIntent::Place {
+            market_id,
+            selection_id,
+            handicap,
+            side,
+            price,
+            size,
+            persistence,
+        } => {
+            let args = PlaceOrderArgs {
+                market_id,
+                selection_id,
+                handicap,
+                side,
+                price,
+                size,
+                persistence,
+                customer_order_ref: None,
+                customer_strategy_ref: Some(strategy_ref.to_string()),
+                customer_ref: None,
+                // Strategies follow fills through `on_orders`.
+                confirm_timeout_ms: Some(0),
+            };
+            orders::place(app, state, args).await.map(drop)
+        }
+        OrderIntent::Cancel { market_id, bet_id } => {
+            let bet_ids = match bet_id {
+                Some(id) => {
+                    check_owned(state, &id, strategy_ref)?;
+                    vec![id]
+                }
+                None => state
+                    .orders
+                    .strategy_market(&market_id, strategy_ref)
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|r| r.unmatched)
+                    .map(|o| o.id)
+                    .collect(),
+            };
+            // An empty list would cancel the whole market.
+            if bet_ids.is_empty() {
+                return Ok(());
+            }
+            let args = CancelOrdersArgs {
+                market_id,
+                bet_ids,
+                size_reduction: None,
+                customer_ref: None,
+                confirm_timeout_ms: Some(0),
+            };
+            orders::cancel(app, state, args).await.map(drop)
+        }
+        OrderIntent::Replace {
+            market_id,
+            bet_id,
+            new_price,
+        } => {
+            check_owned(state, &bet_id, strategy_ref)?;
+            let args = ReplaceOrderArgs {
+                market_id,
+                bet_id,
+                new_price,
+                customer_ref: None,
+                confirm_timeout_ms: Some(0),
+            };
+            orders::replace(app, state, args).await.map(drop)
+        }
+    }
+}
@@ -1,38 +1,482 @@
+use crate::clock::parse_utc_ms;
 use crate::codec::CrlfTextCodec;
+use crate::commands::kill_switch;
+use crate::commands::{orders, strategies};
+use crate::market::cache::LadderSource;
 use crate::state::AppState;
-use crate::stream::{StreamConnection, EVENT_STREAM_LINE};
-use futures_util::{SinkExt, StreamExt};
+use crate::stream::filter::{DataField, FilterPreset, MarketDataFilter, MarketStreamSettings};
+use crate::stream::history::ChangeMeta;
+use crate::stream::mcm::MarketChangeMessage;
+use crate::stream::ocm::OrderChangeMessage;
+use crate::stream::subscriptions::{
+    is_valid_subscriber_id, order_subscription_message, Rebalance, SubscriptionRules,
+    SubscriptionStatus, MAX_POOL_SIZE,
+};
+use crate::stream::{
+    authentication_message, next_connection_id, next_request_id, run_writer, ConnectionMessage,
+    StatusMessage, StreamConnection, StreamRole, StreamStatusEvent, AUTH_REQUEST_ID,
+    EVENT_MARKETS_DROPPED, EVENT_ORDER_CHANGE, EVENT_STREAM_CONNECTION, EVENT_STREAM_LINE,
+    EVENT_STREAM_STATUS, STREAM_HOST, STREAM_PORT,
+};
+use crate::ui_error::UiErrorPayload;
+use futures_util::future::BoxFuture;
+use futures_util::{SinkExt, Stream, StreamExt};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use tauri::{AppHandle, Emitter, State};
-use tracing::{info, warn};
+use tracing::{info, info_span, trace, warn, Instrument, Span};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamSubscribeArgs {
+    /// Stable id of the view, e.g. `"main"` or `"ladder-1"`. Updates for its
+    /// markets are emitted as `betfair_market_update/<subscriberId>`.
+    pub subscriber_id: String,
+    pub market_ids: Vec<String>,
+    /// Replaces the data filter of every market connection.
+    #[serde(default)]
+    pub filter: Option<MarketDataFilterArgs>,
+}
+
+/// `marketDataFilter` and subscription options, built from presets
+/// (`"ladder"`, `"fullDepth"`, `"sp"`) plus any extra fields.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketDataFilterArgs {
+    #[serde(default)]
+    pub presets: Vec<FilterPreset>,
+    #[serde(default)]
+    pub fields: Vec<DataField>,
+    pub ladder_levels: Option<u32>,
+    pub conflate_ms: Option<u64>,
+    pub heartbeat_ms: Option<u64>,
+}
+
+impl MarketDataFilterArgs {
+    fn build(&self) -> Result<MarketStreamSettings, UiErrorPayload> {
+        let mut filter = MarketDataFilter::empty();
+        for preset in &self.presets {
+            filter = filter.with_preset(*preset);
+        }
+        for field in &self.fields {
+            filter = filter.with_field(*field);
+        }
+        if filter.fields().is_empty() {
+            return Err(UiErrorPayload::key(
+                "errors:validation.marketDataFieldsRequired",
+            ));
+        }
+        if let Some(levels) = self.ladder_levels {
+            filter = filter.with_ladder_levels(levels)?;
+        }
+        let mut settings = MarketStreamSettings::new(filter);
+        if let Some(ms) = self.heartbeat_ms {
+            settings = settings.with_heartbeat_ms(ms)?;
+        }
+        if let Some(ms) = self.conflate_ms {
+            settings = settings.with_conflate_ms(ms)?;
+        }
+        Ok(settings)
+    }
+}
 
 #[tauri::command]
 pub async fn stream_connect(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
     info!("stream_connect");
-    // Already connected?
-    {
-        let guard = state.stream.read().await;
-        if guard.is_some() {
-            return Ok(());
+    let reconnect = !state
+        .streams
+        .read()
+        .await
+        .keys()
+        .any(|r| matches!(r, StreamRole::Market(_)));
+    if reconnect {
+        // A limit stepped down after a rejection applies to that session only.
+        let rebalance = state.subscriptions.write().await.reset_limit();
+        apply_rebalance(&app, &state, rebalance)
+            .await
+            .map_err(|e| e.key)?;
+    }
+    open_connection(&app, &state, StreamRole::Market(0))
+        .await
+        .map_err(|e| e.key)
+}
+
+/// Sends a raw frame on the first market connection (debug / legacy path).
+#[tauri::command]
+pub async fn stream_send(state: State<'_, AppState>, line: String) -> Result<(), String> {
+    info!(n = line.len(), "stream_send");
+    // Basic safety: don't allow UI to send authentication (token must not cross boundary).
+    if let Ok(v) = serde_json::from_str::<serde_json::Value>(&line) {
+        if v.get("op").and_then(|x| x.as_str()) == Some("authentication") {
+            return Err("errors:validation.methodNotAllowed".to_string());
         }
     }
 
+    let conn = {
+        let guard = state.streams.read().await;
+        guard
+            .get(&StreamRole::Market(0))
+            .map(|c| (c.id, c.tx.clone()))
+    };
+
+    let Some((conn_id, tx)) = conn else {
+        return Err("errors:stream.notConnected".to_string());
+    };
+    state.stream_history.frame_out(conn_id, &line);
+
+    tx.send(line)
+        .await
+        .map_err(|_| "errors:stream.notConnected".to_string())?;
+    Ok(())
+}
+
+/// Turns the raw `EVENT_STREAM_LINE` debug channel on or off.
+#[tauri::command]
+pub async fn stream_set_raw_lines(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    info!(enabled, "stream_set_raw_lines");
+    state.stream_raw_lines.store(enabled, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Closes every connection, including the order stream.
+#[tauri::command]
+pub async fn stream_disconnect(state: State<'_, AppState>) -> Result<(), String> {
+    info!("stream_disconnect");
+    let mut guard = state.streams.write().await;
+    guard.clear();
+    Ok(())
+}
+
+/// Sets the markets a view wants. Connections are opened, resubscribed or
+/// closed so the pool covers the union of all views.
+#[tauri::command]
+pub async fn stream_subscribe_markets(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: StreamSubscribeArgs,
+) -> Result<SubscriptionStatus, UiErrorPayload> {
+    info!(
+        subscriber = %args.subscriber_id,
+        markets = args.market_ids.len(),
+        "stream_subscribe_markets"
+    );
+    if !is_valid_subscriber_id(&args.subscriber_id) {
+        return Err(UiErrorPayload::key("errors:validation.subscriberId"));
+    }
+    let settings = args.filter.as_ref().map(|f| f.build()).transpose()?;
+    let mut rebalance = {
+        let mut subs = state.subscriptions.write().await;
+        let resend = settings.map(|s| subs.set_settings(s)).unwrap_or_default();
+        let mut r = subs.set_view(&args.subscriber_id, &args.market_ids);
+        r.changed.extend(resend);
+        r
+    };
+    rebalance.changed.sort_unstable();
+    rebalance.changed.dedup();
+    // The view may join markets other views already stream.
+    state.markets.request_snapshot(&args.market_ids);
+    apply_rebalance(&app, &state, rebalance).await?;
+    Ok(state.subscriptions.read().await.status())
+}
+
+#[tauri::command]
+pub async fn stream_unsubscribe_markets(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    subscriber_id: String,
+) -> Result<SubscriptionStatus, UiErrorPayload> {
+    info!(subscriber = %subscriber_id, "stream_unsubscribe_markets");
+    let rebalance = state
+        .subscriptions
+        .write()
+        .await
+        .remove_view(&subscriber_id);
+    apply_rebalance(&app, &state, rebalance).await?;
+    Ok(state.subscriptions.read().await.status())
+}
+
+#[tauri::command]
+pub async fn stream_subscriptions(
+    state: State<'_, AppState>,
+) -> Result<SubscriptionStatus, UiErrorPayload> {
+    Ok(state.subscriptions.read().await.status())
+}
+
+/// Spreads subscribed markets over `size` market connections (1–4).
+#[tauri::command]
+pub async fn stream_set_pool_size(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    size: usize,
+) -> Result<SubscriptionStatus, UiErrorPayload> {
+    info!(size, "stream_set_pool_size");
+    if !(1..=MAX_POOL_SIZE).contains(&size) {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.poolSizeOutOfRange",
+            serde_json::json!({ "min": 1, "max": MAX_POOL_SIZE }),
+        ));
+    }
+    let rebalance = state.subscriptions.write().await.set_pool_size(size);
+    apply_rebalance(&app, &state, rebalance).await?;
+    Ok(state.subscriptions.read().await.status())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamLadderSourceArgs {
+    pub subscriber_id: String,
+    pub source: LadderSource,
+}
+
+/// Chooses the direct (`batb`/`batl`) or virtual (`bdatb`/`bdatl`) ladder for
+/// one view. `EX_BEST_OFFERS_DISP` is added to the subscription while any
+/// view wants virtual prices.
+#[tauri::command]
+pub async fn stream_set_ladder_source(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: StreamLadderSourceArgs,
+) -> Result<SubscriptionStatus, UiErrorPayload> {
+    info!(subscriber = %args.subscriber_id, source = ?args.source, "stream_set_ladder_source");
+    if !is_valid_subscriber_id(&args.subscriber_id) {
+        return Err(UiErrorPayload::key("errors:validation.subscriberId"));
+    }
+    let (changed, markets) = {
+        let mut subs = state.subscriptions.write().await;
+        let changed = subs.set_ladder_source(&args.subscriber_id, args.source);
+        let markets: Vec<String> = subs
+            .status()
+            .subscribers
+            .remove(&args.subscriber_id)
+            .unwrap_or_default();
+        (changed, markets)
+    };
+    // The view needs the newly chosen ladder in full.
+    state.markets.request_snapshot(&markets);
+    sync_slots(&app, &state, changed).await?;
+    Ok(state.subscriptions.read().await.status())
+}
+
+/// Replaces the `marketDataFilter`, `conflateMs` and `heartbeatMs` used by all
+/// market connections and resubscribes them.
+#[tauri::command]
+pub async fn stream_set_market_data_filter(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    args: MarketDataFilterArgs,
+) -> Result<SubscriptionStatus, UiErrorPayload> {
+    info!(
+        presets = ?args.presets,
+        fields = ?args.fields,
+        ladder_levels = ?args.ladder_levels,
+        conflate_ms = ?args.conflate_ms,
+        heartbeat_ms = ?args.heartbeat_ms,
+        "stream_set_market_data_filter"
+    );
+    let settings = args.build()?;
+    let changed = state.subscriptions.write().await.set_settings(settings);
+    sync_slots(&app, &state, changed).await?;
+    Ok(state.subscriptions.read().await.status())
+}
+
+/// Sets pinned markets, priority order and the account limit used when views
+/// want more markets than can be subscribed.
+#[tauri::command]
+pub async fn stream_set_subscription_rules(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    rules: SubscriptionRules,
+) -> Result<SubscriptionStatus, UiErrorPayload> {
+    info!(
+        pinned = rules.pinned.len(),
+        order = ?rules.order,
+        limit = ?rules.limit,
+        "stream_set_subscription_rules"
+    );
+    if rules.limit == Some(0) {
+        return Err(UiErrorPayload::key(
+            "errors:validation.subscriptionLimitOutOfRange",
+        ));
+    }
+    let rebalance = state.subscriptions.write().await.set_rules(rules);
+    apply_rebalance(&app, &state, rebalance).await?;
+    Ok(state.subscriptions.read().await.status())
+}
+
+/// Opens the dedicated order-stream connection (`orderSubscription`).
+#[tauri::command]
+pub async fn stream_orders_connect(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), UiErrorPayload> {
+    info!("stream_orders_connect");
+    open_connection(&app, &state, StreamRole::Orders).await
+}
+
+#[tauri::command]
+pub async fn stream_orders_disconnect(state: State<'_, AppState>) -> Result<(), UiErrorPayload> {
+    info!("stream_orders_disconnect");
+    state.streams.write().await.remove(&StreamRole::Orders);
+    state.orders.clear();
+    Ok(())
+}
+
+/// Resubscribes changed slots and tells the UI about newly dropped markets.
+///
+/// Boxed because the reader task calls it and it may open connections that
+/// spawn further readers; an `async fn` here would make that type recursive.
+fn apply_rebalance<'a>(
+    app: &'a AppHandle,
+    state: &'a AppState,
+    rebalance: Rebalance,
+) -> BoxFuture<'a, Result<(), UiErrorPayload>> {
+    Box::pin(async move {
+        if !rebalance.dropped.is_empty() {
+            let event = state
+                .subscriptions
+                .read()
+                .await
+                .dropped_event(rebalance.dropped);
+            info!(
+                dropped = event.dropped.len(),
+                subscribed = event.subscribed,
+                limit = event.limit,
+                "markets dropped from subscription"
+            );
+            let _ = app.emit(EVENT_MARKETS_DROPPED, &event);
+        }
+        sync_slots(app, state, rebalance.changed).await
+    })
+}
+
+/// Brings the given market slots in line with the subscription manager.
+async fn sync_slots(
+    app: &AppHandle,
+    state: &AppState,
+    slots: Vec<usize>,
+) -> Result<(), UiErrorPayload> {
+    for slot in slots {
+        let role = StreamRole::Market(slot);
+        let wanted = state
+            .subscriptions
+            .read()
+            .await
+            .slot(slot)
+            .is_some_and(|m| !m.is_empty());
+        // Betfair treats an empty `marketIds` as "every market", so an idle
+        // slot is closed rather than resubscribed.
+        if !wanted {
+            if state.streams.write().await.remove(&role).is_some() {
+                info!(%role, "stream slot closed");
+            }
+            continue;
+        }
+        let open = state.streams.read().await.contains_key(&role);
+        if open {
+            send_subscription(state, role).await?;
+        } else {
+            // Subscribes once authenticated.
+            open_connection(app, state, role).await?;
+        }
+    }
+    let active = state.subscriptions.read().await.active();
+    state.markets.release(&active);
+    Ok(())
+}
+
+/// Sends the current subscription for `role`, if its connection is authenticated.
+async fn send_subscription(state: &AppState, role: StreamRole) -> Result<(), UiErrorPayload> {
+    let line = match role {
+        StreamRole::Market(slot) => {
+            let subs = state.subscriptions.read().await;
+            match subs.slot(slot) {
+                Some(markets) if !markets.is_empty() => subs
+                    .effective_settings()
+                    .subscription_message(next_request_id(), markets),
+                _ => return Ok(()),
+            }
+        }
+        StreamRole::Orders => order_subscription_message(next_request_id()),
+    };
+
+    let conn = {
+        let guard = state.streams.read().await;
+        guard
+            .get(&role)
+            .filter(|c| c.authenticated)
+            .map(|c| (c.id, c.tx.clone()))
+    };
+    let Some((conn_id, tx)) = conn else {
+        return Ok(());
+    };
+    info!(%role, conn = conn_id, "stream subscribe");
+    state.stream_history.frame_out(conn_id, &line);
+    tx.send(line)
+        .await
+        .map_err(|_| UiErrorPayload::key("errors:stream.notConnected"))
+}
+
+/// Holds a role's connect slot; released on drop, whether the connect
+/// succeeded or not.
+struct Connecting {
+    roles: Arc<std::sync::Mutex<BTreeSet<StreamRole>>>,
+    role: StreamRole,
+}
+
+impl Connecting {
+    fn reserve(state: &AppState, role: StreamRole) -> Option<Self> {
+        let roles = state.streams_connecting.clone();
+        let reserved = roles.lock().unwrap_or_else(|e| e.into_inner()).insert(role);
+        reserved.then_some(Self { roles, role })
+    }
+}
+
+impl Drop for Connecting {
+    fn drop(&mut self) {
+        self.roles
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.role);
+    }
+}
+
+/// Opens and authenticates a connection for `role` unless one is already
+/// open or being opened. A caller that finds a connect in flight returns
+/// straight away: subscriptions go out once that one authenticates.
+async fn open_connection(
+    app: &AppHandle,
+    state: &AppState,
+    role: StreamRole,
+) -> Result<(), UiErrorPayload> {
+    if state.streams.read().await.contains_key(&role) {
+        return Ok(());
+    }
+    let Some(_connecting) = Connecting::reserve(state, role) else {
+        return Ok(());
+    };
+    // A connect may have finished between the check and the reservation.
+    if state.streams.read().await.contains_key(&role) {
+        return Ok(());
+    }
+
     if state.app_key.trim().is_empty() {
-        return Err("errors:validation.appKeyRequired".to_string());
+        return Err(UiErrorPayload::key("errors:validation.appKeyRequired"));
     }
 
-    let token = state.session_token.read().await;
-    if token.as_deref().unwrap_or("").is_empty() {
-        return Err("errors:auth.notLoggedIn".to_string());
+    let token = state.session_token.read().await.clone().unwrap_or_default();
+    if token.is_empty() {
+        return Err(UiErrorPayload::key("errors:auth.notLoggedIn"));
     }
 
     // Build a fresh TLS stream and framed codec.
-    let addr = "stream-api.betfair.com:443";
-    let tcp = tokio::net::TcpStream::connect(addr)
+    let tcp = tokio::net::TcpStream::connect((STREAM_HOST, STREAM_PORT))
         .await
-        .map_err(|e| format!("errors:network.requestFailed:{e}"))?;
+        .map_err(connect_failed)?;
 
-    let server_name = rustls_pki_types::ServerName::try_from("stream-api.betfair.com")
-        .map_err(|_| "errors:network.invalidHost".to_string())?;
+    let server_name = rustls_pki_types::ServerName::try_from(STREAM_HOST)
+        .map_err(|_| UiErrorPayload::key("errors:network.invalidHost"))?;
 
     let tls_config = rustls::ClientConfig::builder()
         .with_root_certificates(std::sync::Arc::new(rustls::RootCertStore::from_iter(
@@ -44,87 +488,213 @@ pub async fn stream_connect(app: AppHandle, state: State<'_, AppState>) -> Resul
     let tls_stream = tls
         .connect(server_name, tcp)
         .await
-        .map_err(|e| format!("errors:network.requestFailed:{e}"))?;
+        .map_err(connect_failed)?;
 
     let framed = tokio_util::codec::Framed::new(tls_stream, CrlfTextCodec::new(1_048_576));
-    let (mut sink, mut stream) = framed.split();
+    let (mut sink, stream) = framed.split();
 
     // Authenticate immediately using embedded AppKey + Rust-owned token.
-    let auth = serde_json::json!({
-      "op": "authentication",
-            "id": 1,
-      "appKey": state.app_key.as_str(),
-      "session": token.as_deref().unwrap_or("")
-    })
-    .to_string();
+    let auth = authentication_message(state.app_key.as_str(), &token);
 
-    sink.send(auth)
-        .await
-        .map_err(|e| format!("errors:network.requestFailed:{e}"))?;
+    sink.send(auth).await.map_err(connect_failed)?;
 
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(256);
+    let (tx, rx) = tokio::sync::mpsc::channel::<String>(256);
+    let conn_id = next_connection_id();
+    state.stream_history.opened(conn_id, role);
+    // Every line logged for this socket carries `conn`, `role` and, once Betfair
+    // has sent it, the `connection_id`.
+    let span = info_span!(
+        "stream",
+        conn = conn_id,
+        %role,
+        connection_id = tracing::field::Empty
+    );
 
-    // Writer task.
-    tokio::spawn(async move {
-        while let Some(line) = rx.recv().await {
-            let _ = sink.send(line).await;
-        }
-        let _ = sink.close().await;
-    });
+    // Registered before the reader starts so the auth reply finds it.
+    state.streams.write().await.insert(
+        role,
+        StreamConnection {
+            id: conn_id,
+            role,
+            tx,
+            authenticated: false,
+        },
+    );
 
-    // Reader task.
-    tokio::spawn(async move {
-        while let Some(msg) = stream.next().await {
-            match msg {
-                Ok(line) => {
-                    // Keep this as debug-ish info: it's helpful during bring-up.
-                    info!(n = line.len(), "stream_in");
-                    let _ = app.emit(EVENT_STREAM_LINE, line);
+    tokio::spawn(run_writer(sink, rx).instrument(span.clone()));
+    tokio::spawn(
+        read_frames(
+            app.clone(),
+            state.clone(),
+            conn_id,
+            role,
+            stream,
+            span.clone(),
+        )
+        .instrument(span),
+    );
+
+    Ok(())
+}
+
+async fn read_frames<S, E>(
+    app: AppHandle,
+    state: AppState,
+    conn_id: u64,
+    role: StreamRole,
+    mut stream: S,
+    span: Span,
+) where
+    S: Stream<Item = Result<String, E>> + Unpin,
+{
+    while let Some(msg) = stream.next().await {
+        match msg {
+            Ok(line) => {
+                // Every frame: too many for the log file and the memory tail.
+                trace!(n = line.len(), "stream_in");
+                let mcm = MarketChangeMessage::parse(&line);
+                let ocm = match mcm {
+                    Some(_) => None,
+                    None => OrderChangeMessage::parse(&line),
+                };
+                let change = match (&mcm, &ocm) {
+                    (Some(m), _) => Some(ChangeMeta::new(m.pt, m.ct)),
+                    (_, Some(o)) => Some(ChangeMeta::new(o.pt, o.ct)),
+                    _ => None,
+                };
+                state.stream_history.frame_in(conn_id, &line, change);
+                if let Some(conn) = ConnectionMessage::parse(&line) {
+                    span.record("connection_id", conn.connection_id.as_str());
+                    info!(connection_id = %conn.connection_id, "stream connected");
+                    state
+                        .stream_history
+                        .connection_id(conn_id, &conn.connection_id);
+                    let _ = app.emit(EVENT_STREAM_CONNECTION, &conn);
+                } else if let Some(status) = StatusMessage::parse(&line) {
+                    on_status(&app, &state, conn_id, role, &status).await;
+                } else if let Some(mcm) = mcm {
+                    state.markets.apply(conn_id, &mcm);
+                    on_market_definitions(&app, &state, &mcm).await;
+                } else if let Some(ocm) = ocm {
+                    state.orders.apply(&ocm);
+                    for oc in &ocm.oc {
+                        orders::emit_position(&app, &state, &oc.id);
+                        strategies::on_orders(&app, &state, &oc.id);
+                    }
+                    if let Ok(ocm) = serde_json::from_str::<serde_json::Value>(&line) {
+                        let _ = app.emit(EVENT_ORDER_CHANGE, ocm);
+                    }
                 }
-                Err(_) => {
-                    warn!("stream_in error");
-                    break;
+                if state.stream_raw_lines.load(Ordering::Relaxed) {
+                    let _ = app.emit(EVENT_STREAM_LINE, line);
                 }
             }
+            Err(_) => {
+                warn!("stream_in error");
+                break;
+            }
         }
-        info!("stream reader exited");
-    });
-
-    let mut guard = state.stream.write().await;
-    *guard = Some(StreamConnection { tx });
+    }
+    info!("stream reader exited");
 
-    Ok(())
+    // Disconnect commands remove the connection first, so one still in place
+    // here means the stream dropped without the user asking.
+    let unexpected = {
+        let mut guard = state.streams.write().await;
+        if guard.get(&role).is_some_and(|c| c.id == conn_id) {
+            guard.remove(&role);
+            true
+        } else {
+            false
+        }
+    };
+    state.stream_history.closed(conn_id, unexpected);
+    if unexpected {
+        warn!("stream lost unexpectedly");
+        kill_switch::on_stream_lost(&app, &state).await;
+    }
 }
 
-#[tauri::command]
-pub async fn stream_send(state: State<'_, AppState>, line: String) -> Result<(), String> {
-    info!(n = line.len(), "stream_send");
-    // Basic safety: don't allow UI to send authentication (token must not cross boundary).
-    if let Ok(v) = serde_json::from_str::<serde_json::Value>(&line) {
-        if v.get("op").and_then(|x| x.as_str()) == Some("authentication") {
-            return Err("errors:validation.methodNotAllowed".to_string());
+async fn on_status(
+    app: &AppHandle,
+    state: &AppState,
+    conn_id: u64,
+    role: StreamRole,
+    status: &StatusMessage,
+) {
+    state.stream_history.status(conn_id, status);
+    let _ = app.emit(
+        EVENT_STREAM_STATUS,
+        StreamStatusEvent {
+            conn: conn_id,
+            role,
+            status,
+        },
+    );
+    if status.is_session_error() {
+        warn!(code = ?status.error_code, "stream session rejected");
+        kill_switch::on_session_expired(app, state).await;
+        return;
+    }
+    if status.is_subscription_limit() && matches!(role, StreamRole::Market(_)) {
+        let (rebalance, limit) = {
+            let mut subs = state.subscriptions.write().await;
+            let r = subs.limit_exceeded();
+            (r, subs.limit())
+        };
+        warn!(limit, "subscription limit exceeded; rotating markets");
+        if let Err(e) = apply_rebalance(app, state, rebalance).await {
+            warn!(key = %e.key, "stream resubscribe failed");
         }
+        return;
     }
 
-    let tx = {
-        let guard = state.stream.read().await;
-        guard.as_ref().map(|c| c.tx.clone())
-    };
-
-    let Some(tx) = tx else {
-        return Err("errors:stream.notConnected".to_string());
-    };
+    if status.id == Some(AUTH_REQUEST_ID) && status.status_code.as_deref() == Some("SUCCESS") {
+        if let Some(c) = state.streams.write().await.get_mut(&role) {
+            if c.id == conn_id {
+                c.authenticated = true;
+            }
+        }
+        if let Err(e) = send_subscription(state, role).await {
+            warn!(key = %e.key, "stream subscribe failed");
+        }
+    }
+}
 
-    tx.send(line)
-        .await
-        .map_err(|_| "errors:stream.notConnected".to_string())?;
-    Ok(())
+/// Feeds `marketTime` and CLOSED status to the subscription manager, which
+/// may rotate markets in or out.
+async fn on_market_definitions(app: &AppHandle, state: &AppState, mcm: &MarketChangeMessage) {
+    let mut rebalance = false;
+    for mc in &mcm.mc {
+        let Some(def) = &mc.market_definition else {
+            continue;
+        };
+        state.catalogue.merge_definition(&mc.id, def);
+        let start_ms = def
+            .get("marketTime")
+            .and_then(|v| v.as_str())
+            .and_then(parse_utc_ms);
+        let closed = def.get("status").and_then(|v| v.as_str()) == Some("CLOSED");
+        if closed {
+            info!(market = %mc.id, "market closed");
+        }
+        rebalance |= state
+            .subscriptions
+            .write()
+            .await
+            .note_definition(&mc.id, start_ms, closed);
+    }
+    if rebalance {
+        let r = state.subscriptions.write().await.rebalance();
+        if let Err(e) = apply_rebalance(app, state, r).await {
+            warn!(key = %e.key, "stream resubscribe failed");
+        }
+    }
 }
 
-#[tauri::command]
-pub async fn stream_disconnect(state: State<'_, AppState>) -> Result<(), String> {
-    info!("stream_disconnect");
-    let mut guard = state.stream.write().await;
-    *guard = None;
-    Ok(())
+fn connect_failed(e: impl std::fmt::Display) -> UiErrorPayload {
+    UiErrorPayload::with_values(
+        "errors:stream.connectFailed",
+        serde_json::json!({ "details": e.to_string() }),
+    )
 }
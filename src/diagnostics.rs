@@ -0,0 +1,207 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::betfair;
+use crate::clock::now_ms;
+use crate::redact;
+use crate::state::AppState;
+use crate::stream::history::MAX_FRAMES;
+use crate::stream::{STREAM_HOST, STREAM_PORT};
+use crate::ui_error::UiErrorPayload;
+
+pub const DEFAULT_FRAMES: usize = 200;
+/// Only the tail of each log file is bundled.
+const MAX_LOG_BYTES: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsSummary {
+    pub path: String,
+    pub files: Vec<String>,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest<'a> {
+    app_version: &'a str,
+    created_at_ms: u64,
+    os: &'static str,
+    arch: &'static str,
+    log_level: String,
+    redaction_enabled: bool,
+    logged_in: bool,
+    stream_connections: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EndpointProfile {
+    identity_login: &'static str,
+    betting: Option<&'static str>,
+    account: Option<&'static str>,
+    heartbeat: Option<&'static str>,
+    stream: String,
+}
+
+impl EndpointProfile {
+    fn current() -> Self {
+        Self {
+            identity_login: betfair::identity::LOGIN_URL,
+            betting: betfair::rpc::service_base_url("betting"),
+            account: betfair::rpc::service_base_url("account"),
+            heartbeat: betfair::rpc::service_base_url("heartbeat"),
+            stream: format!("{STREAM_HOST}:{STREAM_PORT}"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Allowlist {
+    betting: Vec<String>,
+    account: Vec<String>,
+    heartbeat: Vec<String>,
+}
+
+impl Allowlist {
+    fn from_state(state: &AppState) -> Self {
+        let sorted = |set: &std::collections::HashSet<String>| {
+            let mut v: Vec<String> = set.iter().cloned().collect();
+            v.sort();
+            v
+        };
+        Self {
+            betting: sorted(&state.allowlist_betting),
+            account: sorted(&state.allowlist_account),
+            heartbeat: sorted(&state.allowlist_heartbeat),
+        }
+    }
+}
+
+/// Writes a support bundle to `dest`. Everything that goes in is masked
+/// regardless of the `BETFAIR_LOG_SECRETS` debug opt-in.
+pub async fn write_bundle(
+    state: &AppState,
+    app_version: &str,
+    dest: &Path,
+    frames: usize,
+) -> Result<DiagnosticsSummary, UiErrorPayload> {
+    let manifest = Manifest {
+        app_version,
+        created_at_ms: now_ms(),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        log_level: state.logging.status().level,
+        redaction_enabled: redact::enabled(),
+        logged_in: state
+            .session_token
+            .read()
+            .await
+            .as_deref()
+            .is_some_and(|t| !t.is_empty()),
+        stream_connections: state.streams.read().await.len(),
+    };
+    let frames = state.stream_history.frames(frames.min(MAX_FRAMES));
+
+    let mut bundle = Bundle::create(dest)?;
+    bundle.json("manifest.json", &manifest)?;
+    bundle.json("endpoints.json", &EndpointProfile::current())?;
+    bundle.json("allowlist.json", &Allowlist::from_state(state))?;
+    bundle.json("stream/history.json", &state.stream_history.snapshot())?;
+
+    let mut jsonl = String::new();
+    for f in &frames {
+        jsonl.push_str(&serde_json::to_string(f).unwrap_or_default());
+        jsonl.push('\n');
+    }
+    bundle.text("stream/frames.jsonl", &jsonl)?;
+
+    let recent: Vec<String> = state
+        .logging
+        .recent(usize::MAX)
+        .into_iter()
+        .map(|l| l.text)
+        .collect();
+    bundle.text("logs/recent.log", &redact::mask_text(&recent.join("\n")))?;
+    if let Some(dir) = state.logging.dir() {
+        let mut entries: Vec<_> = std::fs::read_dir(&dir)
+            .map(|rd| rd.filter_map(Result::ok).collect())
+            .unwrap_or_default();
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            let Ok(text) = read_tail(&path, MAX_LOG_BYTES) else {
+                continue;
+            };
+            let name = format!("logs/{}", entry.file_name().to_string_lossy());
+            bundle.text(&name, &redact::mask_text(&text))?;
+        }
+    }
+
+    bundle.finish(dest)
+}
+
+fn read_tail(path: &Path, max: u64) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(max)))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+struct Bundle {
+    zip: ZipWriter<File>,
+    files: Vec<String>,
+}
+
+impl Bundle {
+    fn create(dest: &Path) -> Result<Self, UiErrorPayload> {
+        let file = File::create(dest).map_err(export_failed)?;
+        Ok(Self {
+            zip: ZipWriter::new(file),
+            files: Vec::new(),
+        })
+    }
+
+    fn json<T: Serialize>(&mut self, name: &str, value: &T) -> Result<(), UiErrorPayload> {
+        let value = serde_json::to_value(value).map_err(export_failed)?;
+        let text =
+            serde_json::to_string_pretty(&redact::mask_json(value)).map_err(export_failed)?;
+        self.text(name, &text)
+    }
+
+    fn text(&mut self, name: &str, text: &str) -> Result<(), UiErrorPayload> {
+        self.zip
+            .start_file(name, SimpleFileOptions::default())
+            .map_err(export_failed)?;
+        self.zip.write_all(text.as_bytes()).map_err(export_failed)?;
+        self.files.push(name.to_string());
+        Ok(())
+    }
+
+    fn finish(self, dest: &Path) -> Result<DiagnosticsSummary, UiErrorPayload> {
+        let file = self.zip.finish().map_err(export_failed)?;
+        let bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(DiagnosticsSummary {
+            path: dest.display().to_string(),
+            files: self.files,
+            bytes,
+        })
+    }
+}
+
+fn export_failed(e: impl std::fmt::Display) -> UiErrorPayload {
+    UiErrorPayload::with_values(
+        "errors:diagnostics.exportFailed",
+        serde_json::json!({ "details": e.to_string() }),
+    )
+}
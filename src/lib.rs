@@ -1,6 +1,17 @@
+pub mod audit;
+pub mod backtest;
 pub mod betfair;
+pub mod clock;
 pub mod codec;
 pub mod commands;
+pub mod diagnostics;
+pub mod logging;
+pub mod market;
+pub mod orders;
+pub mod redact;
+pub mod risk;
 pub mod state;
+pub mod strategy;
 pub mod stream;
+pub mod ticks;
 pub mod ui_error;
@@ -0,0 +1,245 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::writer::MakeWriterExt;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::redact;
+use crate::ui_error::UiErrorPayload;
+
+pub const EVENT_LOG_LINE: &str = "betfair_log_line";
+
+const DEFAULT_LEVEL: &str = "info";
+const LOG_FILE_PREFIX: &str = "betfair_stream_app";
+const MAX_LOG_FILES: usize = 7;
+const RECENT_CAPACITY: usize = 2_000;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLine {
+    pub seq: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogStatus {
+    pub level: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir: Option<String>,
+    pub follow: bool,
+}
+
+struct Inner {
+    filter: reload::Handle<EnvFilter, Registry>,
+    level: Mutex<String>,
+    recent: Mutex<VecDeque<LogLine>>,
+    next_seq: AtomicU64,
+    live: broadcast::Sender<LogLine>,
+    file: Mutex<Option<NonBlocking>>,
+    // Dropping the guard flushes and stops the file writer thread.
+    file_guard: Mutex<Option<WorkerGuard>>,
+    dir: Mutex<Option<PathBuf>>,
+    follow: AtomicBool,
+}
+
+/// Process-wide logging: stdout, daily-rotated files and an in-memory tail
+/// for the diagnostics panel, all redacted, with a runtime-reloadable filter.
+#[derive(Clone)]
+pub struct Logging {
+    inner: Arc<Inner>,
+}
+
+impl Logging {
+    /// Installs the global subscriber. Files are only written once `attach_dir`
+    /// is called (the app data directory is known after Tauri setup).
+    pub fn init() -> Self {
+        redact::init_from_env();
+
+        let level = std::env::var("RUST_LOG")
+            .ok()
+            .filter(|v| EnvFilter::try_new(v).is_ok())
+            .unwrap_or_else(|| DEFAULT_LEVEL.to_string());
+        let (filter, handle) = reload::Layer::new(EnvFilter::new(&level));
+        let (live, _) = broadcast::channel(256);
+
+        let logging = Self {
+            inner: Arc::new(Inner {
+                filter: handle,
+                level: Mutex::new(level),
+                recent: Mutex::new(VecDeque::with_capacity(RECENT_CAPACITY)),
+                next_seq: AtomicU64::new(1),
+                live,
+                file: Mutex::new(None),
+                file_guard: Mutex::new(None),
+                dir: Mutex::new(None),
+                follow: AtomicBool::new(false),
+            }),
+        };
+
+        let stdout = tracing_subscriber::fmt::layer()
+            .fmt_fields(redact::field_formatter())
+            .with_writer(redact::RedactingMakeWriter::new(io::stdout));
+        let persisted = tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .fmt_fields(redact::field_formatter())
+            .with_writer(redact::RedactingMakeWriter::new(
+                FileMakeWriter(logging.inner.clone()).and(MemoryMakeWriter(logging.inner.clone())),
+            ));
+
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(stdout)
+            .with(persisted)
+            .init();
+
+        logging
+    }
+
+    /// Starts writing `<dir>/betfair_stream_app.<date>.log`, keeping the last 7 days.
+    pub fn attach_dir(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let appender = RollingFileAppender::builder()
+            .rotation(Rotation::DAILY)
+            .filename_prefix(LOG_FILE_PREFIX)
+            .filename_suffix("log")
+            .max_log_files(MAX_LOG_FILES)
+            .build(dir)
+            .map_err(io::Error::other)?;
+        let (writer, guard) = tracing_appender::non_blocking(appender);
+
+        *lock(&self.inner.file) = Some(writer);
+        *lock(&self.inner.file_guard) = Some(guard);
+        *lock(&self.inner.dir) = Some(dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Replaces the active filter, e.g. `debug` or `info,betfair_stream_app=trace`.
+    pub fn set_level(&self, directives: &str) -> Result<LogStatus, UiErrorPayload> {
+        let directives = directives.trim();
+        let filter = EnvFilter::try_new(directives).map_err(|e| {
+            UiErrorPayload::with_values(
+                "errors:logs.invalidLevel",
+                serde_json::json!({ "level": directives, "details": e.to_string() }),
+            )
+        })?;
+        self.inner
+            .filter
+            .reload(filter)
+            .map_err(|e| UiErrorPayload::unexpected(e.to_string()))?;
+        *lock(&self.inner.level) = directives.to_string();
+        Ok(self.status())
+    }
+
+    pub fn status(&self) -> LogStatus {
+        LogStatus {
+            level: lock(&self.inner.level).clone(),
+            dir: lock(&self.inner.dir)
+                .as_ref()
+                .map(|d| d.display().to_string()),
+            follow: self.following(),
+        }
+    }
+
+    pub fn dir(&self) -> Option<PathBuf> {
+        lock(&self.inner.dir).clone()
+    }
+
+    /// Most recent `limit` lines, oldest first.
+    pub fn recent(&self, limit: usize) -> Vec<LogLine> {
+        let recent = lock(&self.inner.recent);
+        let skip = recent.len().saturating_sub(limit);
+        recent.iter().skip(skip).cloned().collect()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LogLine> {
+        self.inner.live.subscribe()
+    }
+
+    pub fn set_follow(&self, follow: bool) {
+        self.inner.follow.store(follow, Ordering::Relaxed);
+    }
+
+    pub fn following(&self) -> bool {
+        self.inner.follow.load(Ordering::Relaxed)
+    }
+}
+
+fn lock<T>(m: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+struct FileMakeWriter(Arc<Inner>);
+
+impl<'a> MakeWriter<'a> for FileMakeWriter {
+    type Writer = FileWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        FileWriter(lock(&self.0.file).clone())
+    }
+}
+
+struct FileWriter(Option<NonBlocking>);
+
+impl io::Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.as_mut() {
+            Some(w) => w.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0.as_mut() {
+            Some(w) => w.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+struct MemoryMakeWriter(Arc<Inner>);
+
+impl<'a> MakeWriter<'a> for MemoryMakeWriter {
+    type Writer = MemoryWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        MemoryWriter(self.0.clone())
+    }
+}
+
+struct MemoryWriter(Arc<Inner>);
+
+impl io::Write for MemoryWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        for line in text.lines().filter(|l| !l.is_empty()) {
+            let entry = LogLine {
+                seq: self.0.next_seq.fetch_add(1, Ordering::Relaxed),
+                text: line.to_string(),
+            };
+            {
+                let mut recent = lock(&self.0.recent);
+                if recent.len() == RECENT_CAPACITY {
+                    recent.pop_front();
+                }
+                recent.push_back(entry.clone());
+            }
+            // No receivers is fine: nobody is following.
+            let _ = self.0.live.send(entry);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,5 +1,8 @@
 use betfair_stream_app::commands;
+use betfair_stream_app::logging::Logging;
 use betfair_stream_app::state::AppState;
+use betfair_stream_app::strategy;
+use tauri::Manager;
 
 fn force_app_key_from_dotenv(dotenv_path: &std::path::Path) {
     let Ok(text) = std::fs::read_to_string(dotenv_path) else {
@@ -34,6 +37,32 @@ fn force_app_key_from_dotenv(dotenv_path: &std::path::Path) {
     }
 }
 
+/// The kill switch must work even when the window is not focused, so it is a
+/// global (OS-level) shortcut rather than a UI key binding.
+#[cfg(desktop)]
+fn register_kill_switch_shortcut(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
+    use betfair_stream_app::risk::kill_switch::{KillSwitchReason, KILL_SWITCH_SHORTCUT};
+    use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+    let kill: Shortcut = KILL_SWITCH_SHORTCUT.parse()?;
+    app.handle().plugin(
+        tauri_plugin_global_shortcut::Builder::new()
+            .with_handler(move |app, shortcut, event| {
+                if shortcut != &kill || event.state() != ShortcutState::Pressed {
+                    return;
+                }
+                let app = app.clone();
+                let state = app.state::<AppState>().inner().clone();
+                tauri::async_runtime::spawn(async move {
+                    commands::kill_switch::engage(&app, &state, KillSwitchReason::Shortcut).await;
+                });
+            })
+            .build(),
+    )?;
+    app.global_shortcut().register(kill)?;
+    Ok(())
+}
+
 fn main() {
     // Dev convenience: load env vars from `.env` (ignored by git).
     // `cargo tauri dev` can run the binary with a different working directory,
@@ -46,11 +75,7 @@ fn main() {
         let _ = dotenvy::dotenv();
     }
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()),
-        )
-        .init();
+    let logging = Logging::init();
 
     // Ensure a process-wide rustls CryptoProvider is installed.
     let _ = rustls::crypto::ring::default_provider().install_default();
@@ -60,17 +85,112 @@ fn main() {
         .build()
         .expect("reqwest client");
 
-    let state = AppState::new(http);
+    let state = AppState::new(http, logging);
 
     tauri::Builder::default()
         .manage(state)
+        .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            let state = app.state::<AppState>().inner().clone();
+            if let Err(e) = state.logging.attach_dir(&data_dir.join("logs")) {
+                tracing::warn!(error = %e, "log files unavailable");
+            }
+            tracing::info!(
+                version = %app.package_info().version,
+                "betfair_stream_app started"
+            );
+            if let Err(e) = state.audit.open(&data_dir) {
+                tracing::warn!(error = %e, "audit log unavailable; orders will be refused");
+            }
+            match state.stops.open(&data_dir) {
+                Ok(active) => tracing::info!(active, "stops loaded"),
+                Err(e) => tracing::warn!(error = %e, "stops unavailable"),
+            }
+            if let Err(e) = strategy::builtin::register(&state.strategies) {
+                tracing::warn!(key = %e.key, "built-in strategies not registered");
+            }
+            tauri::async_runtime::spawn(commands::logs::forward_live_lines(
+                app.handle().clone(),
+                state.logging.clone(),
+            ));
+            tauri::async_runtime::spawn(commands::markets::publish_market_updates(
+                app.handle().clone(),
+                state.clone(),
+            ));
+            tauri::async_runtime::spawn(commands::historical::publish_replay_updates(
+                app.handle().clone(),
+                state.clone(),
+            ));
+            tauri::async_runtime::spawn(commands::kill_switch::watch_lifecycle(
+                app.handle().clone(),
+                state.clone(),
+            ));
+
+            #[cfg(desktop)]
+            register_kill_switch_shortcut(app)?;
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
+            commands::audit::audit_query,
+            commands::audit::audit_verify,
+            commands::audit::audit_export,
             commands::auth::auth_status,
             commands::auth::auth_login,
             commands::auth::auth_logout,
+            commands::catalogue::catalogue_refresh,
+            commands::catalogue::catalogue_search,
+            commands::catalogue::catalogue_market,
+            commands::diagnostics::diagnostics_export,
+            commands::historical::historical_scan,
+            commands::historical::historical_replay_start,
+            commands::historical::historical_replay_stop,
+            commands::historical::historical_replay_status,
+            commands::kill_switch::kill_switch_status,
+            commands::kill_switch::kill_switch_engage,
+            commands::kill_switch::kill_switch_rearm,
+            commands::kill_switch::kill_switch_set_triggers,
+            commands::logs::logs_status,
+            commands::logs::logs_set_level,
+            commands::logs::logs_recent,
+            commands::logs::logs_follow,
+            commands::markets::market_publish_settings,
+            commands::markets::market_set_publish_rate,
+            commands::markets::market_snapshot,
+            commands::markets::market_ladder,
+            commands::markets::market_bsp,
+            commands::navigation::navigation_children,
+            commands::navigation::navigation_market_types,
+            commands::orders::orders_place,
+            commands::orders::orders_cancel,
+            commands::orders::orders_replace,
+            commands::orders::orders_update,
+            commands::orders::orders_place_bsp,
+            commands::orders::orders_position,
+            commands::orders::orders_hedge,
+            commands::risk::risk_limits,
+            commands::risk::risk_set_limits,
             commands::rpc::betfair_rpc,
+            commands::stops::stops_list,
+            commands::stops::stops_status,
+            commands::stops::stops_create,
+            commands::stops::stops_cancel,
+            commands::strategies::strategies_list,
+            commands::strategies::strategies_inspect,
+            commands::strategies::strategies_set_enabled,
+            commands::strategies::strategies_start,
+            commands::strategies::strategies_stop,
             commands::stream::stream_connect,
             commands::stream::stream_send,
+            commands::stream::stream_set_raw_lines,
+            commands::stream::stream_subscribe_markets,
+            commands::stream::stream_unsubscribe_markets,
+            commands::stream::stream_subscriptions,
+            commands::stream::stream_set_pool_size,
+            commands::stream::stream_set_subscription_rules,
+            commands::stream::stream_set_market_data_filter,
+            commands::stream::stream_set_ladder_source,
+            commands::stream::stream_orders_connect,
+            commands::stream::stream_orders_disconnect,
             commands::stream::stream_disconnect
         ])
         .run(tauri::generate_context!())
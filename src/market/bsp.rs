@@ -0,0 +1,111 @@
+use serde::Serialize;
+
+use crate::market::cache::{runner_key, MarketState, RunnerKey};
+use crate::stream::ocm::{Side, UnmatchedOrder};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BspRunner {
+    pub selection_id: u64,
+    pub handicap: f64,
+    /// Projected near price (`spn`), up to the off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub near: Option<f64>,
+    /// Projected far price (`spf`), from SP bets only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub far: Option<f64>,
+    /// Reconciled BSP from the market definition once `bspReconciled`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<f64>,
+    /// `actual - near`: how far the last projection was off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drift: Option<f64>,
+    /// Market-wide SP back/lay liability (`spb`/`spl` totals).
+    pub sp_back_liability: f64,
+    pub sp_lay_liability: f64,
+    /// Liability of our unreconciled MARKET_ON_CLOSE / LIMIT_ON_CLOSE orders.
+    pub own_back_liability: f64,
+    pub own_lay_liability: f64,
+}
+
+/// Projected against reconciled BSP for a market; `reconciled` flips once
+/// Betfair has settled the starting prices at the off.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BspView {
+    pub market_id: String,
+    pub bsp_market: bool,
+    pub in_play: bool,
+    pub reconciled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    pub runners: Vec<BspRunner>,
+}
+
+/// `uo.bsp` is the BSP liability of an on-close order; `None` otherwise.
+pub fn bsp_liability(order: &UnmatchedOrder) -> Option<f64> {
+    let on_close = matches!(order.ot.as_deref(), Some("MOC" | "LOC"));
+    on_close.then(|| order.bsp.unwrap_or(order.s))
+}
+
+pub fn bsp_view(
+    market: &MarketState,
+    orders_for: impl Fn(u64, f64) -> Vec<UnmatchedOrder>,
+) -> BspView {
+    let def = market.market_definition.as_ref();
+    let flag = |k: &str| def.and_then(|d| d.get(k)).and_then(|v| v.as_bool()) == Some(true);
+    let reconciled = flag("bspReconciled");
+    let actual_for = |key: RunnerKey| {
+        def?.get("runners")?
+            .as_array()?
+            .iter()
+            .find(|r| {
+                let id = r.get("id").and_then(|v| v.as_u64());
+                let hc = r.get("hc").and_then(|v| v.as_f64()).unwrap_or(0.0);
+                id.is_some_and(|id| runner_key(id, hc) == key)
+            })?
+            .get("bsp")?
+            .as_f64()
+    };
+
+    let runners = market
+        .runners
+        .iter()
+        .map(|(key, r)| {
+            let actual = if reconciled { actual_for(*key) } else { None };
+            let (mut own_back, mut own_lay) = (0.0, 0.0);
+            for order in orders_for(r.selection_id, r.handicap) {
+                if let Some(liability) = bsp_liability(&order) {
+                    match order.side {
+                        Side::Back => own_back += liability,
+                        Side::Lay => own_lay += liability,
+                    }
+                }
+            }
+            BspRunner {
+                selection_id: r.selection_id,
+                handicap: r.handicap,
+                near: r.spn,
+                far: r.spf,
+                actual,
+                drift: actual.zip(r.spn).map(|(a, n)| a - n),
+                sp_back_liability: r.spb.total(),
+                sp_lay_liability: r.spl.total(),
+                own_back_liability: own_back,
+                own_lay_liability: own_lay,
+            }
+        })
+        .collect();
+
+    BspView {
+        market_id: market.market_id.clone(),
+        bsp_market: flag("bspMarket"),
+        in_play: flag("inPlay"),
+        reconciled,
+        status: def
+            .and_then(|d| d.get("status"))
+            .and_then(|v| v.as_str())
+            .map(str::to_string),
+        runners,
+    }
+}
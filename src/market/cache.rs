@@ -0,0 +1,721 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+use crate::clock::now_ms;
+use crate::market::ladder::PriceLadder;
+use crate::market::lifecycle::{diff, LifecycleEvent};
+use crate::market::removal::{removals, RunnerRemoval, RunnerRemoved};
+use crate::stream::mcm::{ChangeType, MarketChange, MarketChangeMessage, RunnerChange};
+use crate::ticks::{price_key, TickLadder};
+
+pub const DEFAULT_MAX_RATE_HZ: u32 = 10;
+pub const MAX_RATE_HZ_LIMIT: u32 = 60;
+/// Betfair publishes virtual prices ~150ms after the direct ones.
+pub const VIRTUAL_PRICE_LAG_MS: u64 = 150;
+
+/// Which best-offers ladder a view shows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LadderSource {
+    /// `batb`/`batl`: offers actually in the book.
+    #[default]
+    Direct,
+    /// `bdatb`/`bdatl`: including cross-matched virtual bets, as shown on
+    /// the Betfair website. Lags the direct ladder by `VIRTUAL_PRICE_LAG_MS`.
+    Virtual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateKind {
+    /// Full market; replaces whatever the view holds.
+    Snapshot,
+    /// Only runners that changed since the last event, each in full.
+    Diff,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LadderLevel {
+    pub level: u32,
+    pub price: f64,
+    pub size: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnerSnapshot {
+    pub selection_id: u64,
+    pub handicap: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ltp: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tv: Option<f64>,
+    pub batb: Vec<LadderLevel>,
+    pub batl: Vec<LadderLevel>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bdatb: Vec<LadderLevel>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bdatl: Vec<LadderLevel>,
+    /// Projected BSP (`SP_PROJECTED`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spn: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spf: Option<f64>,
+    /// Set once the runner is withdrawn; its ladders are cleared.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removal: Option<RunnerRemoval>,
+}
+
+/// Payload of `EVENT_MARKET_UPDATE`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketUpdate {
+    pub market_id: String,
+    pub kind: UpdateKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publish_time: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clk: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tv: Option<f64>,
+    /// Present on snapshots and on diffs where the definition changed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_definition: Option<serde_json::Value>,
+    /// Present with the market definition. `lineRange` marks a LINE market:
+    /// prices are line values, back is under (sell) and lay is over (buy).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tick_ladder: Option<TickLadder>,
+    /// Ladder the runners carry when published to a view; unset (both
+    /// ladders) from `market_snapshot`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ladder: Option<LadderSource>,
+    /// Set for virtual ladders so the UI can mark the prices as delayed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub virtual_lag_ms: Option<u64>,
+    pub runners: Vec<RunnerSnapshot>,
+}
+
+impl MarketUpdate {
+    /// Copy for a view showing `source`, without the other best-offers ladder.
+    pub fn for_ladder(&self, source: LadderSource) -> MarketUpdate {
+        let mut update = self.clone();
+        update.ladder = Some(source);
+        for r in &mut update.runners {
+            match source {
+                LadderSource::Direct => {
+                    r.bdatb.clear();
+                    r.bdatl.clear();
+                }
+                LadderSource::Virtual => {
+                    r.batb.clear();
+                    r.batl.clear();
+                }
+            }
+        }
+        if source == LadderSource::Virtual {
+            update.virtual_lag_ms = Some(VIRTUAL_PRICE_LAG_MS);
+        }
+        update
+    }
+}
+
+/// Level-based ladder (`batb`/`batl`): level -> (price, size).
+#[derive(Debug, Clone, Default)]
+pub struct LevelLadder(BTreeMap<u32, (f64, f64)>);
+
+impl LevelLadder {
+    pub fn apply(&mut self, changes: &[[f64; 3]]) {
+        for [level, price, size] in changes {
+            let level = *level as u32;
+            if *size == 0.0 {
+                self.0.remove(&level);
+            } else {
+                self.0.insert(level, (*price, *size));
+            }
+        }
+    }
+
+    pub fn best_price(&self) -> Option<f64> {
+        self.0.values().next().map(|(price, _)| *price)
+    }
+
+    pub fn levels(&self) -> Vec<LadderLevel> {
+        self.0
+            .iter()
+            .map(|(level, (price, size))| LadderLevel {
+                level: *level,
+                price: *price,
+                size: *size,
+            })
+            .collect()
+    }
+}
+
+/// Runners are identified by selection id and handicap (hundredths), as
+/// Asian handicap markets repeat a selection at several handicaps.
+pub type RunnerKey = (u64, i64);
+
+pub fn runner_key(selection_id: u64, handicap: f64) -> RunnerKey {
+    (selection_id, price_key(handicap))
+}
+
+#[derive(Debug, Clone)]
+pub struct RunnerState {
+    pub selection_id: u64,
+    pub handicap: f64,
+    pub ltp: Option<f64>,
+    pub tv: Option<f64>,
+    pub batb: LevelLadder,
+    pub batl: LevelLadder,
+    pub bdatb: LevelLadder,
+    pub bdatl: LevelLadder,
+    pub atb: PriceLadder,
+    pub atl: PriceLadder,
+    pub trd: PriceLadder,
+    pub spn: Option<f64>,
+    pub spf: Option<f64>,
+    /// SP back/lay ladders (`SP_TRADED`).
+    pub spb: PriceLadder,
+    pub spl: PriceLadder,
+    pub removal: Option<RunnerRemoval>,
+}
+
+impl RunnerState {
+    fn new(selection_id: u64, handicap: f64) -> Self {
+        Self {
+            selection_id,
+            handicap,
+            ltp: None,
+            tv: None,
+            batb: LevelLadder::default(),
+            batl: LevelLadder::default(),
+            bdatb: LevelLadder::default(),
+            bdatl: LevelLadder::default(),
+            atb: PriceLadder::default(),
+            atl: PriceLadder::default(),
+            trd: PriceLadder::default(),
+            spn: None,
+            spf: None,
+            spb: PriceLadder::default(),
+            spl: PriceLadder::default(),
+            removal: None,
+        }
+    }
+
+    fn apply(&mut self, rc: &RunnerChange) {
+        if rc.ltp.is_some() {
+            self.ltp = rc.ltp;
+        }
+        if rc.tv.is_some() {
+            self.tv = rc.tv;
+        }
+        if let Some(batb) = &rc.batb {
+            self.batb.apply(batb);
+        }
+        if let Some(batl) = &rc.batl {
+            self.batl.apply(batl);
+        }
+        if let Some(bdatb) = &rc.bdatb {
+            self.bdatb.apply(bdatb);
+        }
+        if let Some(bdatl) = &rc.bdatl {
+            self.bdatl.apply(bdatl);
+        }
+        if let Some(atb) = &rc.atb {
+            self.atb.apply(atb);
+        }
+        if let Some(atl) = &rc.atl {
+            self.atl.apply(atl);
+        }
+        if let Some(trd) = &rc.trd {
+            self.trd.apply(trd);
+        }
+        if let Some(spn) = rc.spn {
+            self.spn = spn;
+        }
+        if let Some(spf) = rc.spf {
+            self.spf = spf;
+        }
+        if let Some(spb) = &rc.spb {
+            self.spb.apply(spb);
+        }
+        if let Some(spl) = &rc.spl {
+            self.spl.apply(spl);
+        }
+    }
+
+    /// Best price we could back at now: full depth when subscribed,
+    /// otherwise the direct best-offers ladder.
+    pub fn best_back(&self) -> Option<f64> {
+        self.atb.highest().or_else(|| self.batb.best_price())
+    }
+
+    pub fn best_lay(&self) -> Option<f64> {
+        self.atl.lowest().or_else(|| self.batl.best_price())
+    }
+
+    /// Withdrawn: the book is gone, only traded history stays.
+    fn remove(&mut self, removal: RunnerRemoval) {
+        self.batb = LevelLadder::default();
+        self.batl = LevelLadder::default();
+        self.bdatb = LevelLadder::default();
+        self.bdatl = LevelLadder::default();
+        self.atb = PriceLadder::default();
+        self.atl = PriceLadder::default();
+        self.spn = None;
+        self.spf = None;
+        self.removal = Some(removal);
+    }
+
+    pub fn snapshot(&self) -> RunnerSnapshot {
+        RunnerSnapshot {
+            selection_id: self.selection_id,
+            handicap: self.handicap,
+            ltp: self.ltp,
+            tv: self.tv,
+            batb: self.batb.levels(),
+            batl: self.batl.levels(),
+            bdatb: self.bdatb.levels(),
+            bdatl: self.bdatl.levels(),
+            spn: self.spn,
+            spf: self.spf,
+            removal: self.removal.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MarketState {
+    pub market_id: String,
+    pub publish_time: Option<u64>,
+    pub clk: Option<String>,
+    pub tv: Option<f64>,
+    pub market_definition: Option<serde_json::Value>,
+    /// From `priceLadderDefinition` (CLASSIC until a definition arrives).
+    pub tick_ladder: TickLadder,
+    pub runners: BTreeMap<RunnerKey, RunnerState>,
+}
+
+impl MarketState {
+    fn new(market_id: &str) -> Self {
+        Self {
+            market_id: market_id.to_string(),
+            publish_time: None,
+            clk: None,
+            tv: None,
+            market_definition: None,
+            tick_ladder: TickLadder::default(),
+            runners: BTreeMap::new(),
+        }
+    }
+
+    pub fn removals(&self) -> impl Iterator<Item = &RunnerRemoval> {
+        self.runners.values().filter_map(|r| r.removal.as_ref())
+    }
+
+    pub fn total_reduction_factor(&self) -> f64 {
+        self.removals().filter_map(|r| r.adjustment_factor).sum()
+    }
+
+    pub fn snapshot(&self) -> MarketUpdate {
+        MarketUpdate {
+            market_id: self.market_id.clone(),
+            kind: UpdateKind::Snapshot,
+            publish_time: self.publish_time,
+            clk: self.clk.clone(),
+            tv: self.tv,
+            market_definition: self.market_definition.clone(),
+            tick_ladder: self.market_definition.as_ref().map(|_| self.tick_ladder),
+            ladder: None,
+            virtual_lag_ms: None,
+            runners: self.runners.values().map(RunnerState::snapshot).collect(),
+        }
+    }
+}
+
+/// What changed since the market was last published.
+#[derive(Debug, Default)]
+struct Dirty {
+    snapshot: bool,
+    market: bool,
+    definition: bool,
+    runners: BTreeSet<RunnerKey>,
+}
+
+impl Dirty {
+    fn is_clean(&self) -> bool {
+        !self.snapshot && !self.market && !self.definition && self.runners.is_empty()
+    }
+}
+
+struct Entry {
+    /// Connection whose subscription delivers this market.
+    conn: u64,
+    state: MarketState,
+    dirty: Dirty,
+    last_emit_ms: u64,
+    /// Removals found by the last `apply`, moved to `Alerts` by the cache.
+    removed: Vec<RunnerRemoval>,
+    /// These two survive `img` so a re-imaged market doesn't alert twice.
+    removed_keys: BTreeSet<RunnerKey>,
+    last_definition: Option<serde_json::Value>,
+    /// Likewise for lifecycle transitions.
+    lifecycle: Vec<LifecycleEvent>,
+    /// No view wants the market any more; dropped once its alerts are out.
+    released: bool,
+}
+
+impl Entry {
+    fn new(conn: u64, market_id: &str) -> Self {
+        Self {
+            conn,
+            state: MarketState::new(market_id),
+            dirty: Dirty {
+                snapshot: true,
+                ..Dirty::default()
+            },
+            last_emit_ms: 0,
+            removed: Vec::new(),
+            removed_keys: BTreeSet::new(),
+            last_definition: None,
+            lifecycle: Vec::new(),
+            released: false,
+        }
+    }
+
+    fn apply(&mut self, mc: &MarketChange, pt: Option<u64>, clk: Option<&str>) {
+        if mc.img {
+            self.state = MarketState::new(&mc.id);
+            self.dirty = Dirty {
+                snapshot: true,
+                ..Dirty::default()
+            };
+        }
+        if pt.is_some() {
+            self.state.publish_time = pt;
+            self.dirty.market = true;
+        }
+        if let Some(clk) = clk {
+            self.state.clk = Some(clk.to_string());
+        }
+        if mc.tv.is_some() {
+            self.state.tv = mc.tv;
+            self.dirty.market = true;
+        }
+        if let Some(def) = &mc.market_definition {
+            // Runners already removed in the first definition we see are
+            // recorded without an alert.
+            let at_ms = pt.unwrap_or_else(now_ms);
+            let seen_definition = self.last_definition.is_some();
+            for (key, removal) in removals(def, at_ms) {
+                let runner =
+                    self.state.runners.entry(key).or_insert_with(|| {
+                        RunnerState::new(removal.selection_id, removal.handicap)
+                    });
+                if runner.removal.is_none() {
+                    runner.remove(removal.clone());
+                    self.dirty.runners.insert(key);
+                }
+                if self.removed_keys.insert(key) && seen_definition {
+                    self.removed.push(removal);
+                }
+            }
+            if let Some(prev) = &self.last_definition {
+                self.lifecycle
+                    .extend(diff(prev, def).into_iter().map(|kind| LifecycleEvent {
+                        market_id: mc.id.clone(),
+                        at_ms,
+                        kind,
+                    }));
+            }
+            self.last_definition = Some(def.clone());
+            self.state.tick_ladder = TickLadder::from_market_definition(def);
+            self.state.market_definition = Some(def.clone());
+            self.dirty.definition = true;
+        }
+        for rc in &mc.rc {
+            let handicap = rc.hc.unwrap_or(0.0);
+            let key = runner_key(rc.id, handicap);
+            self.state
+                .runners
+                .entry(key)
+                .or_insert_with(|| RunnerState::new(rc.id, handicap))
+                .apply(rc);
+            self.dirty.runners.insert(key);
+        }
+    }
+
+    /// Builds the event for everything dirty and marks the market clean.
+    fn take_update(&mut self, now_ms: u64) -> MarketUpdate {
+        let dirty = std::mem::take(&mut self.dirty);
+        self.last_emit_ms = now_ms;
+        if dirty.snapshot {
+            return self.state.snapshot();
+        }
+        MarketUpdate {
+            market_id: self.state.market_id.clone(),
+            kind: UpdateKind::Diff,
+            publish_time: self.state.publish_time,
+            clk: self.state.clk.clone(),
+            tv: self.state.tv,
+            market_definition: if dirty.definition {
+                self.state.market_definition.clone()
+            } else {
+                None
+            },
+            tick_ladder: dirty.definition.then_some(self.state.tick_ladder),
+            ladder: None,
+            virtual_lag_ms: None,
+            runners: dirty
+                .runners
+                .iter()
+                .filter_map(|id| self.state.runners.get(id))
+                .map(RunnerState::snapshot)
+                .collect(),
+        }
+    }
+}
+
+/// Announcements published without waiting for the rate limit. Kept
+/// outside the entries so a market closed and dropped straight away still
+/// reports it.
+#[derive(Default)]
+struct Alerts {
+    removed: Vec<RunnerRemoved>,
+    lifecycle: Vec<LifecycleEvent>,
+}
+
+/// Market state built from the stream, published to the UI at most
+/// `max_rate_hz` times per second per market. Updates arriving in between are
+/// coalesced into the next event.
+pub struct MarketCache {
+    markets: Mutex<HashMap<String, Entry>>,
+    alerts: Mutex<Alerts>,
+    min_interval_ms: AtomicU64,
+    changed: Notify,
+}
+
+impl Default for MarketCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MarketCache {
+    pub fn new() -> Self {
+        Self {
+            markets: Mutex::new(HashMap::new()),
+            alerts: Mutex::new(Alerts::default()),
+            min_interval_ms: AtomicU64::new(interval_ms(DEFAULT_MAX_RATE_HZ)),
+            changed: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.markets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn max_rate_hz(&self) -> u32 {
+        (1000 / self.min_interval_ms.load(Ordering::Relaxed).max(1)) as u32
+    }
+
+    /// Caller validates `1..=MAX_RATE_HZ_LIMIT`.
+    pub fn set_max_rate_hz(&self, hz: u32) {
+        self.min_interval_ms
+            .store(interval_ms(hz), Ordering::Relaxed);
+        self.changed.notify_one();
+    }
+
+    /// Applies an mcm frame received on connection `conn`.
+    pub fn apply(&self, conn: u64, msg: &MarketChangeMessage) {
+        if msg.ct == Some(ChangeType::Heartbeat) {
+            return;
+        }
+        let mut became_dirty = false;
+        let mut alerts = Alerts::default();
+        {
+            let mut markets = self.lock();
+            if msg.starts_image() {
+                // A new image replaces everything this connection delivered.
+                markets.retain(|_, e| e.conn != conn);
+            }
+            for mc in &msg.mc {
+                let entry = markets
+                    .entry(mc.id.clone())
+                    .or_insert_with(|| Entry::new(conn, &mc.id));
+                entry.conn = conn;
+                let was_clean = entry.dirty.is_clean();
+                entry.apply(mc, msg.pt, msg.clk.as_deref());
+                became_dirty |= was_clean && !entry.dirty.is_clean();
+                let total = entry.state.total_reduction_factor();
+                alerts
+                    .removed
+                    .extend(entry.removed.drain(..).map(|removal| RunnerRemoved {
+                        market_id: mc.id.clone(),
+                        removal,
+                        total_reduction_factor: total,
+                    }));
+                alerts.lifecycle.append(&mut entry.lifecycle);
+            }
+        }
+        if !alerts.removed.is_empty() || !alerts.lifecycle.is_empty() {
+            let mut pending = self.alerts.lock().unwrap_or_else(|e| e.into_inner());
+            pending.removed.append(&mut alerts.removed);
+            pending.lifecycle.append(&mut alerts.lifecycle);
+            became_dirty = true;
+        }
+        if became_dirty || msg.starts_image() {
+            self.changed.notify_one();
+        }
+    }
+
+    /// Drops markets no view subscribes to any more.
+    pub fn retain(&self, wanted: &BTreeSet<String>) {
+        self.lock().retain(|id, _| wanted.contains(id));
+    }
+
+    /// Like `retain`, but leaves the dropping to `drop_released` so a market
+    /// that left the subscription because it CLOSED still has its settlement
+    /// and final position published.
+    pub fn release(&self, wanted: &BTreeSet<String>) {
+        let mut released = false;
+        for (id, e) in self.lock().iter_mut() {
+            let release = !wanted.contains(id);
+            released |= release && !e.released;
+            e.released = release;
+        }
+        if released {
+            self.changed.notify_one();
+        }
+    }
+
+    /// Drops released markets with no removals or lifecycle events left to
+    /// publish.
+    pub fn drop_released(&self) {
+        let pending: BTreeSet<String> = {
+            let alerts = self.alerts.lock().unwrap_or_else(|e| e.into_inner());
+            alerts
+                .removed
+                .iter()
+                .map(|r| r.market_id.clone())
+                .chain(alerts.lifecycle.iter().map(|e| e.market_id.clone()))
+                .collect()
+        };
+        self.lock()
+            .retain(|id, e| !e.released || pending.contains(id));
+    }
+
+    /// Makes the next event for these markets a full snapshot, e.g. for a view
+    /// that just subscribed to a market others already watch.
+    pub fn request_snapshot(&self, market_ids: &[String]) {
+        let mut requested = false;
+        {
+            let mut markets = self.lock();
+            for id in market_ids {
+                if let Some(e) = markets.get_mut(id) {
+                    e.dirty.snapshot = true;
+                    requested = true;
+                }
+            }
+        }
+        if requested {
+            self.changed.notify_one();
+        }
+    }
+
+    pub fn snapshot(&self, market_id: &str) -> Option<MarketUpdate> {
+        self.lock().get(market_id).map(|e| e.state.snapshot())
+    }
+
+    /// Price ladder of a cached market; CLASSIC for unknown markets.
+    pub fn tick_ladder(&self, market_id: &str) -> TickLadder {
+        self.lock()
+            .get(market_id)
+            .map(|e| e.state.tick_ladder)
+            .unwrap_or_default()
+    }
+
+    pub fn market(&self, market_id: &str) -> Option<MarketState> {
+        self.lock().get(market_id).map(|e| e.state.clone())
+    }
+
+    pub fn runner(&self, market_id: &str, selection_id: u64, handicap: f64) -> Option<RunnerState> {
+        self.lock()
+            .get(market_id)
+            .and_then(|e| e.state.runners.get(&runner_key(selection_id, handicap)))
+            .cloned()
+    }
+
+    /// Runner removals seen since the last call.
+    pub fn take_removals(&self) -> Vec<RunnerRemoved> {
+        let mut alerts = self.alerts.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::take(&mut alerts.removed)
+    }
+
+    /// Lifecycle transitions seen since the last call, in arrival order.
+    pub fn take_lifecycle(&self) -> Vec<LifecycleEvent> {
+        let mut alerts = self.alerts.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::take(&mut alerts.lifecycle)
+    }
+
+    /// Markets due for publishing now, and when the next pending one is due.
+    pub fn take_due(&self, now_ms: u64) -> (Vec<MarketUpdate>, Option<u64>) {
+        let interval = self.min_interval_ms.load(Ordering::Relaxed);
+        let mut due = Vec::new();
+        let mut next_due: Option<u64> = None;
+        for entry in self.lock().values_mut() {
+            if entry.dirty.is_clean() {
+                continue;
+            }
+            let at = entry.last_emit_ms.saturating_add(interval);
+            if at <= now_ms {
+                due.push(entry.take_update(now_ms));
+            } else {
+                next_due = Some(next_due.map_or(at, |n| n.min(at)));
+            }
+        }
+        (due, next_due)
+    }
+
+    /// Resolves when a market goes from clean to dirty or the rate changes.
+    pub async fn changed(&self) {
+        self.changed.notified().await
+    }
+}
+
+fn interval_ms(hz: u32) -> u64 {
+    1000 / u64::from(hz.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn frame(rc: serde_json::Value) -> MarketChangeMessage {
+        let line = json!({ "op": "mcm", "pt": 1_000, "mc": [{ "id": "1.1", "rc": [rc] }] });
+        MarketChangeMessage::parse(&line.to_string()).unwrap()
+    }
+
+    #[test]
+    fn projected_sp_clears_when_sent_without_a_price() {
+        let cache = MarketCache::new();
+        cache.apply(1, &frame(json!({ "id": 7, "spn": 3.5, "spf": 3.2 })));
+        let sp = |c: &MarketCache| c.runner("1.1", 7, 0.0).map(|r| (r.spn, r.spf));
+        assert_eq!(sp(&cache), Some((Some(3.5), Some(3.2))));
+
+        // Absent fields leave the projection alone.
+        cache.apply(1, &frame(json!({ "id": 7, "ltp": 3.4 })));
+        assert_eq!(sp(&cache), Some((Some(3.5), Some(3.2))));
+
+        cache.apply(
+            1,
+            &frame(json!({ "id": 7, "spn": "NaN", "spf": "Infinity" })),
+        );
+        assert_eq!(sp(&cache), Some((None, None)));
+    }
+}
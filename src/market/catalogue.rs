@@ -0,0 +1,436 @@
+//! Market catalogue kept on the Rust side: filled from `listMarketCatalogue`
+//! and kept current by stream `marketDefinition`s, so views search it
+//! locally instead of asking Betfair again.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use crate::clock::parse_utc_ms;
+use crate::market::cache::{runner_key, RunnerKey};
+
+/// Projections catalogue entries are fetched with.
+pub const CATALOGUE_PROJECTION: &[&str] = &[
+    "COMPETITION",
+    "EVENT",
+    "EVENT_TYPE",
+    "MARKET_START_TIME",
+    "MARKET_DESCRIPTION",
+    "RUNNER_DESCRIPTION",
+    "RUNNER_METADATA",
+];
+/// Markets per full fetch: MARKET_DESCRIPTION and RUNNER_METADATA weigh one
+/// point each against Betfair's 200 per request.
+pub const CATALOGUE_CHUNK: usize = 100;
+/// Listed markets fetched longer ago than this are fetched again.
+pub const DEFAULT_MAX_AGE_MS: u64 = 15 * 60 * 1000;
+/// `maxResults` Betfair accepts for a listing.
+pub const MAX_LIST_RESULTS: u32 = 1000;
+pub const DEFAULT_SEARCH_LIMIT: usize = 100;
+pub const MAX_SEARCH_LIMIT: usize = 1000;
+/// Markets that neither start nor were fetched within this are dropped on
+/// refresh.
+const KEEP_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// `eventType` / `competition`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueRef {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueEvent {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub venue: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_date: Option<String>,
+}
+
+/// The parts of `MARKET_DESCRIPTION` the app uses.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueDescription {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub betting_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspend_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turn_in_play_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bsp_market: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub race_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub each_way_divisor: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueRunner {
+    pub selection_id: u64,
+    #[serde(default)]
+    pub runner_name: String,
+    #[serde(default)]
+    pub handicap: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_priority: Option<u32>,
+    /// `RUNNER_METADATA` (jockey, trainer, form, ...); values may be null.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, Option<String>>,
+    /// From the stream: ACTIVE, REMOVED, WINNER, ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+}
+
+/// A `listMarketCatalogue` entry plus what the stream has said since.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogueMarket {
+    pub market_id: String,
+    #[serde(default)]
+    pub market_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_start_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_matched: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<CatalogueDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_type: Option<CatalogueRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub competition: Option<CatalogueRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<CatalogueEvent>,
+    #[serde(default)]
+    pub runners: Vec<CatalogueRunner>,
+    /// From the stream: OPEN, SUSPENDED, CLOSED, ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_play: Option<bool>,
+    /// When Betfair last sent the full entry; unset for markets only seen on
+    /// the stream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetched_ms: Option<u64>,
+}
+
+impl CatalogueMarket {
+    fn new(market_id: &str) -> Self {
+        Self {
+            market_id: market_id.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn start_ms(&self) -> Option<u64> {
+        self.market_start_time.as_deref().and_then(parse_utc_ms)
+    }
+
+    pub fn market_type(&self) -> Option<&str> {
+        self.description.as_ref()?.market_type.as_deref()
+    }
+
+    fn runner_mut(&mut self, key: RunnerKey) -> Option<&mut CatalogueRunner> {
+        self.runners
+            .iter_mut()
+            .find(|r| runner_key(r.selection_id, r.handicap) == key)
+    }
+
+    /// Keeps what only the stream knows when Betfair resends the entry.
+    fn keep_stream_fields(&mut self, old: CatalogueMarket) {
+        self.status = self.status.take().or(old.status);
+        self.in_play = self.in_play.or(old.in_play);
+        for old in old.runners {
+            if let Some(r) = self.runner_mut(runner_key(old.selection_id, old.handicap)) {
+                r.status = r.status.take().or(old.status);
+            }
+        }
+    }
+
+    /// Merges a stream `marketDefinition`; fields it leaves out are kept.
+    fn merge_definition(&mut self, def: &serde_json::Value) {
+        let text = |key: &str| def.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let flag = |key: &str| def.get(key).and_then(|v| v.as_bool());
+
+        // Historical files carry names the live stream leaves out.
+        if let Some(name) = text("name") {
+            self.market_name = name;
+        }
+        self.market_start_time = text("marketTime").or(self.market_start_time.take());
+        self.status = text("status").or(self.status.take());
+        self.in_play = flag("inPlay").or(self.in_play);
+
+        let d = self.description.get_or_insert_with(Default::default);
+        d.market_type = text("marketType").or(d.market_type.take());
+        d.betting_type = text("bettingType").or(d.betting_type.take());
+        d.market_time = text("marketTime").or(d.market_time.take());
+        d.suspend_time = text("suspendTime").or(d.suspend_time.take());
+        d.turn_in_play_enabled = flag("turnInPlayEnabled").or(d.turn_in_play_enabled);
+        d.bsp_market = flag("bspMarket").or(d.bsp_market);
+        d.race_type = text("raceType").or(d.race_type.take());
+        d.each_way_divisor = def
+            .get("eachWayDivisor")
+            .and_then(|v| v.as_f64())
+            .or(d.each_way_divisor);
+
+        let e = self.event.get_or_insert_with(Default::default);
+        e.id = text("eventId").or(e.id.take());
+        e.name = text("eventName").or(e.name.take());
+        e.country_code = text("countryCode").or(e.country_code.take());
+        e.timezone = text("timezone").or(e.timezone.take());
+        e.venue = text("venue").or(e.venue.take());
+        e.open_date = text("openDate").or(e.open_date.take());
+
+        if let Some(id) = text("eventTypeId") {
+            self.event_type.get_or_insert_with(Default::default).id = Some(id);
+        }
+        if let Some(id) = text("competitionId") {
+            self.competition.get_or_insert_with(Default::default).id = Some(id);
+        }
+
+        let runners = def.get("runners").and_then(|v| v.as_array());
+        for r in runners.into_iter().flatten() {
+            let Some(selection_id) = r.get("id").and_then(|v| v.as_u64()) else {
+                continue;
+            };
+            let handicap = r.get("hc").and_then(|v| v.as_f64()).unwrap_or(0.0);
+            let runner = match self.runner_mut(runner_key(selection_id, handicap)) {
+                Some(runner) => runner,
+                None => {
+                    self.runners.push(CatalogueRunner {
+                        selection_id,
+                        handicap,
+                        ..CatalogueRunner::default()
+                    });
+                    self.runners.last_mut().expect("just pushed")
+                }
+            };
+            let text = |key: &str| r.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            runner.status = text("status").or(runner.status.take());
+            if let Some(name) = text("name") {
+                runner.runner_name = name;
+            }
+            if let Some(p) = r.get("sortPriority").and_then(|v| v.as_u64()) {
+                runner.sort_priority = u32::try_from(p).ok();
+            }
+        }
+    }
+
+    fn matches(&self, q: &CatalogueQuery) -> bool {
+        let event = self.event.as_ref();
+        let any = |values: &[String], v: Option<&str>| {
+            values.is_empty() || v.is_some_and(|v| values.iter().any(|x| x == v))
+        };
+        if !q.include_closed && self.status.as_deref() == Some("CLOSED") {
+            return false;
+        }
+        if !any(
+            &q.event_type_ids,
+            self.event_type.as_ref().and_then(|t| t.id.as_deref()),
+        ) || !any(&q.market_types, self.market_type())
+            || !any(
+                &q.country_codes,
+                event.and_then(|e| e.country_code.as_deref()),
+            )
+        {
+            return false;
+        }
+        if q.start_from_ms.is_some() || q.start_to_ms.is_some() {
+            let Some(start) = self.start_ms() else {
+                return false;
+            };
+            if q.start_from_ms.is_some_and(|from| start < from)
+                || q.start_to_ms.is_some_and(|to| start > to)
+            {
+                return false;
+            }
+        }
+        let venue = event.and_then(|e| e.venue.as_deref());
+        let event_name = event.and_then(|e| e.name.as_deref());
+        let competition = self.competition.as_ref().and_then(|c| c.name.as_deref());
+        let runner = |needle: &str| {
+            self.runners
+                .iter()
+                .any(|r| contains(&r.runner_name, needle))
+        };
+        let field = |needle: &Option<String>, v: Option<&str>| match needle {
+            Some(n) => v.is_some_and(|v| contains(v, n)),
+            None => true,
+        };
+        field(&q.venue, venue)
+            && field(&q.event_name, event_name)
+            && q.runner_name.as_deref().is_none_or(runner)
+            && q.text.as_deref().is_none_or(|t| {
+                [
+                    Some(self.market_name.as_str()),
+                    event_name,
+                    venue,
+                    competition,
+                ]
+                .into_iter()
+                .flatten()
+                .any(|v| contains(v, t))
+                    || runner(t)
+            })
+    }
+}
+
+/// Case-insensitive substring match.
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack
+        .to_lowercase()
+        .contains(&needle.trim().to_lowercase())
+}
+
+/// `catalogue_search` filter; unset fields match everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CatalogueQuery {
+    /// Market, event, venue, competition or runner name.
+    pub text: Option<String>,
+    pub venue: Option<String>,
+    pub event_name: Option<String>,
+    pub runner_name: Option<String>,
+    pub event_type_ids: Vec<String>,
+    pub market_types: Vec<String>,
+    pub country_codes: Vec<String>,
+    /// Market start time window, UNIX ms.
+    pub start_from_ms: Option<u64>,
+    pub start_to_ms: Option<u64>,
+    pub include_closed: bool,
+    /// Default 100, max 1000.
+    pub limit: Option<usize>,
+}
+
+/// Markets by id. Betfair's entries replace earlier ones; stream
+/// definitions merge into them.
+pub struct CatalogueStore {
+    markets: Mutex<HashMap<String, CatalogueMarket>>,
+}
+
+impl Default for CatalogueStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CatalogueStore {
+    pub fn new() -> Self {
+        Self {
+            markets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CatalogueMarket>> {
+        self.markets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    pub fn market(&self, market_id: &str) -> Option<CatalogueMarket> {
+        self.lock().get(market_id).cloned()
+    }
+
+    /// Stores entries Betfair returned at `now_ms`.
+    pub fn merge(&self, markets: Vec<CatalogueMarket>, now_ms: u64) {
+        let mut stored = self.lock();
+        for mut m in markets {
+            if let Some(old) = stored.remove(&m.market_id) {
+                m.keep_stream_fields(old);
+            }
+            m.fetched_ms = Some(now_ms);
+            stored.insert(m.market_id.clone(), m);
+        }
+    }
+
+    pub fn merge_definition(&self, market_id: &str, def: &serde_json::Value) {
+        self.lock()
+            .entry(market_id.to_string())
+            .or_insert_with(|| CatalogueMarket::new(market_id))
+            .merge_definition(def);
+    }
+
+    /// Updates `totalMatched` from a listing; true if the market is stored.
+    pub fn set_total_matched(&self, market_id: &str, total_matched: Option<f64>) -> bool {
+        match self.lock().get_mut(market_id) {
+            Some(m) => {
+                m.total_matched = total_matched.or(m.total_matched);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Of `market_ids`, those never fetched or fetched before `since_ms`.
+    pub fn stale<'a>(&self, market_ids: &[&'a str], since_ms: u64) -> Vec<&'a str> {
+        let stored = self.lock();
+        market_ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                stored
+                    .get(*id)
+                    .and_then(|m| m.fetched_ms)
+                    .is_none_or(|at| at < since_ms)
+            })
+            .collect()
+    }
+
+    /// Drops closed markets and those that started over a day before
+    /// `now_ms`, unless Betfair still listed them since (long-running
+    /// markets).
+    pub fn prune(&self, now_ms: u64) -> usize {
+        let mut stored = self.lock();
+        let before = stored.len();
+        let cutoff = now_ms.saturating_sub(KEEP_MS);
+        stored.retain(|_, m| {
+            m.status.as_deref() != Some("CLOSED")
+                && (m.start_ms().is_none_or(|s| s >= cutoff)
+                    || m.fetched_ms.is_some_and(|at| at >= cutoff))
+        });
+        before - stored.len()
+    }
+
+    /// Matching markets, first to start first.
+    pub fn search(&self, query: &CatalogueQuery) -> Vec<CatalogueMarket> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .min(MAX_SEARCH_LIMIT);
+        let mut found: Vec<CatalogueMarket> = self
+            .lock()
+            .values()
+            .filter(|m| m.matches(query))
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| {
+            (a.start_ms().unwrap_or(u64::MAX), &a.market_id)
+                .cmp(&(b.start_ms().unwrap_or(u64::MAX), &b.market_id))
+        });
+        found.truncate(limit);
+        found
+    }
+}
@@ -0,0 +1,149 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::market::cache::RunnerState;
+use crate::stream::ocm::{Side, UnmatchedOrder};
+use crate::ticks::{key_price, price_key, Rounding, TickLadder};
+
+pub const DEFAULT_LADDER_TICKS: u32 = 10;
+pub const MAX_LADDER_TICKS: u32 = 100;
+
+/// Price-keyed ladder (`atb`/`atl`/`trd`, order `mb`/`ml`): price -> size.
+///
+/// Keys are prices in hundredths so float noise can't split a price point.
+#[derive(Debug, Clone, Default)]
+pub struct PriceLadder(BTreeMap<i64, f64>);
+
+impl PriceLadder {
+    /// `[price, size]` deltas; size 0 removes the price.
+    pub fn apply(&mut self, changes: &[[f64; 2]]) {
+        for [price, size] in changes {
+            if *size == 0.0 {
+                self.0.remove(&price_key(*price));
+            } else {
+                self.0.insert(price_key(*price), *size);
+            }
+        }
+    }
+
+    pub fn size_at(&self, price: f64) -> Option<f64> {
+        self.0.get(&price_key(price)).copied()
+    }
+
+    pub fn highest(&self) -> Option<f64> {
+        self.0.keys().next_back().map(|k| key_price(*k))
+    }
+
+    pub fn lowest(&self) -> Option<f64> {
+        self.0.keys().next().map(|k| key_price(*k))
+    }
+
+    pub fn total(&self) -> f64 {
+        self.0.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.0.iter().map(|(k, size)| (key_price(*k), *size))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LadderRow {
+    pub price: f64,
+    /// Available to back at this price (unmatched lays).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub atb: Option<f64>,
+    /// Available to lay at this price (unmatched backs).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub atl: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub traded: Option<f64>,
+    /// Our unmatched back / lay stakes resting at this price.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub own_back: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub own_lay: Option<f64>,
+}
+
+/// Payload of `market_ladder`: one row per tick, highest price first.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LadderView {
+    pub market_id: String,
+    pub selection_id: u64,
+    pub handicap: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ltp: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tv: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_back: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_lay: Option<f64>,
+    pub tick_ladder: TickLadder,
+    /// Sum of the traded ladder.
+    pub traded_total: f64,
+    pub rows: Vec<LadderRow>,
+}
+
+/// Builds `ticks` rows either side of the best price (or LTP when the book
+/// is empty).
+pub fn ladder_view(
+    market_id: &str,
+    tick_ladder: &TickLadder,
+    runner: &RunnerState,
+    orders: &[UnmatchedOrder],
+    ticks: u32,
+) -> LadderView {
+    let best_back = runner.atb.highest();
+    let best_lay = runner.atl.lowest();
+    let centre = best_back.or(best_lay).or(runner.ltp);
+
+    let mut rows = Vec::new();
+    if let Some(centre) = centre.and_then(|p| tick_ladder.round(p, Rounding::Down)) {
+        let ticks = i64::from(ticks);
+        let mut keys: Vec<i64> = (-ticks..=ticks)
+            .filter_map(|n| tick_ladder.offset(centre, n))
+            .map(price_key)
+            .collect();
+        keys.reverse();
+
+        let own = |side: Side, key: i64| {
+            let sum: f64 = orders
+                .iter()
+                .filter(|o| o.side == side && price_key(o.p) == key)
+                .map(|o| o.sr)
+                .sum();
+            (sum > 0.0).then_some(sum)
+        };
+        rows = keys
+            .into_iter()
+            .map(|key| LadderRow {
+                price: key_price(key),
+                atb: runner.atb.0.get(&key).copied(),
+                atl: runner.atl.0.get(&key).copied(),
+                traded: runner.trd.0.get(&key).copied(),
+                own_back: own(Side::Back, key),
+                own_lay: own(Side::Lay, key),
+            })
+            .collect();
+    }
+
+    LadderView {
+        market_id: market_id.to_string(),
+        selection_id: runner.selection_id,
+        handicap: runner.handicap,
+        ltp: runner.ltp,
+        tv: runner.tv,
+        best_back,
+        best_lay,
+        tick_ladder: *tick_ladder,
+        traded_total: runner.trd.total(),
+        rows,
+    }
+}
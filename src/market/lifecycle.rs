@@ -0,0 +1,216 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::market::cache::{runner_key, RunnerKey};
+
+/// What changed between two market definitions.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum LifecycleKind {
+    InPlay,
+    Suspended,
+    /// SUSPENDED back to OPEN.
+    Reopened,
+    #[serde(rename_all = "camelCase")]
+    BetDelayChanged {
+        from: u64,
+        to: u64,
+    },
+    /// A runner got its result: WINNER, LOSER or PLACED.
+    #[serde(rename_all = "camelCase")]
+    RunnerSettled {
+        selection_id: u64,
+        handicap: f64,
+        status: String,
+    },
+    Closed,
+}
+
+/// Payload of `EVENT_MARKET_LIFECYCLE`; also broadcast in-process.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LifecycleEvent {
+    pub market_id: String,
+    /// Publish time of the definition that carried the change.
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub kind: LifecycleKind,
+}
+
+fn status(def: &Value) -> Option<&str> {
+    def.get("status").and_then(|v| v.as_str())
+}
+
+fn in_play(def: &Value) -> bool {
+    def.get("inPlay").and_then(|v| v.as_bool()) == Some(true)
+}
+
+fn bet_delay(def: &Value) -> u64 {
+    def.get("betDelay").and_then(|v| v.as_u64()).unwrap_or(0)
+}
+
+fn runner_statuses(def: &Value) -> HashMap<RunnerKey, (u64, f64, &str)> {
+    def.get("runners")
+        .and_then(|v| v.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|r| {
+            let id = r.get("id")?.as_u64()?;
+            let hc = r.get("hc").and_then(|v| v.as_f64()).unwrap_or(0.0);
+            let status = r.get("status")?.as_str()?;
+            Some((runner_key(id, hc), (id, hc, status)))
+        })
+        .collect()
+}
+
+/// Transitions from `prev` to `next`, in the order they'd be read: state
+/// changes first, then results, then closure.
+pub fn diff(prev: &Value, next: &Value) -> Vec<LifecycleKind> {
+    let mut out = Vec::new();
+    let (was, now) = (status(prev), status(next));
+    if !in_play(prev) && in_play(next) {
+        out.push(LifecycleKind::InPlay);
+    }
+    if now == Some("SUSPENDED") && was != Some("SUSPENDED") {
+        out.push(LifecycleKind::Suspended);
+    }
+    if was == Some("SUSPENDED") && now == Some("OPEN") {
+        out.push(LifecycleKind::Reopened);
+    }
+    let (from, to) = (bet_delay(prev), bet_delay(next));
+    if from != to {
+        out.push(LifecycleKind::BetDelayChanged { from, to });
+    }
+
+    let before = runner_statuses(prev);
+    let mut settled: Vec<_> = runner_statuses(next)
+        .into_iter()
+        .filter(|(_, (_, _, s))| matches!(*s, "WINNER" | "LOSER" | "PLACED"))
+        .filter(|(key, (_, _, s))| before.get(key).map(|b| b.2) != Some(*s))
+        .collect();
+    settled.sort_by_key(|(key, _)| *key);
+    for (_, (selection_id, handicap, s)) in settled {
+        out.push(LifecycleKind::RunnerSettled {
+            selection_id,
+            handicap,
+            status: s.to_string(),
+        });
+    }
+
+    if now == Some("CLOSED") && was != Some("CLOSED") {
+        out.push(LifecycleKind::Closed);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::cache::MarketCache;
+    use crate::stream::mcm::MarketChangeMessage;
+    use serde_json::json;
+
+    fn def(status: &str, in_play: bool, bet_delay: u64, runners: &[(u64, &str)]) -> Value {
+        let runners: Vec<Value> = runners
+            .iter()
+            .map(|(id, s)| json!({ "id": id, "hc": 0.0, "status": s }))
+            .collect();
+        json!({
+            "status": status,
+            "inPlay": in_play,
+            "betDelay": bet_delay,
+            "runners": runners,
+        })
+    }
+
+    const ACTIVE: &[(u64, &str)] = &[(1, "ACTIVE"), (2, "ACTIVE")];
+
+    #[test]
+    fn going_in_play_with_a_bet_delay() {
+        let prev = def("OPEN", false, 0, ACTIVE);
+        let next = def("OPEN", true, 5, ACTIVE);
+        assert_eq!(
+            diff(&prev, &next),
+            [
+                LifecycleKind::InPlay,
+                LifecycleKind::BetDelayChanged { from: 0, to: 5 },
+            ]
+        );
+        assert!(diff(&next, &next).is_empty());
+    }
+
+    #[test]
+    fn suspend_then_reopen() {
+        let open = def("OPEN", true, 5, ACTIVE);
+        let suspended = def("SUSPENDED", true, 5, ACTIVE);
+        assert_eq!(diff(&open, &suspended), [LifecycleKind::Suspended]);
+        assert!(diff(&suspended, &suspended).is_empty());
+        assert_eq!(diff(&suspended, &open), [LifecycleKind::Reopened]);
+    }
+
+    #[test]
+    fn bet_delay_change_alone() {
+        let prev = def("OPEN", true, 5, ACTIVE);
+        let next = def("OPEN", true, 1, ACTIVE);
+        assert_eq!(
+            diff(&prev, &next),
+            [LifecycleKind::BetDelayChanged { from: 5, to: 1 }]
+        );
+    }
+
+    #[test]
+    fn settled_runners_then_closed() {
+        let suspended = def("SUSPENDED", true, 5, ACTIVE);
+        let closed = def("CLOSED", true, 5, &[(2, "LOSER"), (1, "WINNER")]);
+        assert_eq!(
+            diff(&suspended, &closed),
+            [
+                LifecycleKind::RunnerSettled {
+                    selection_id: 1,
+                    handicap: 0.0,
+                    status: "WINNER".into(),
+                },
+                LifecycleKind::RunnerSettled {
+                    selection_id: 2,
+                    handicap: 0.0,
+                    status: "LOSER".into(),
+                },
+                LifecycleKind::Closed,
+            ]
+        );
+        // Repeats of the final definition say nothing new.
+        assert!(diff(&closed, &closed).is_empty());
+    }
+
+    #[test]
+    fn removed_runners_are_not_settled() {
+        let prev = def("OPEN", false, 0, ACTIVE);
+        let next = def("OPEN", false, 0, &[(1, "ACTIVE"), (2, "REMOVED")]);
+        assert!(diff(&prev, &next).is_empty());
+    }
+
+    #[test]
+    fn first_definition_raises_nothing() {
+        let cache = MarketCache::new();
+        let frame = |pt: u64, def: Value| {
+            let line = json!({
+                "op": "mcm",
+                "pt": pt,
+                "mc": [{ "id": "1.1", "marketDefinition": def }],
+            });
+            MarketChangeMessage::parse(&line.to_string()).unwrap()
+        };
+        // Connecting to a market that is already suspended and in play.
+        cache.apply(1, &frame(1_000, def("SUSPENDED", true, 5, ACTIVE)));
+        assert!(cache.take_lifecycle().is_empty());
+
+        cache.apply(1, &frame(2_000, def("OPEN", true, 5, ACTIVE)));
+        let events = cache.take_lifecycle();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].market_id, "1.1");
+        assert_eq!(events[0].at_ms, 2_000);
+        assert_eq!(events[0].kind, LifecycleKind::Reopened);
+    }
+}
@@ -0,0 +1,20 @@
+pub mod bsp;
+pub mod cache;
+pub mod catalogue;
+pub mod ladder;
+pub mod lifecycle;
+pub mod navigation;
+pub mod removal;
+pub mod replay;
+
+/// Conflated per-market snapshot/diff, see `cache::MarketUpdate`.
+pub const EVENT_MARKET_UPDATE: &str = "betfair_market_update";
+
+/// A runner was withdrawn, see `removal::RunnerRemoved`.
+pub const EVENT_RUNNER_REMOVED: &str = "betfair_runner_removed";
+
+/// Market status transitions, see `lifecycle::LifecycleEvent`.
+pub const EVENT_MARKET_LIFECYCLE: &str = "betfair_market_lifecycle";
+
+/// Historical playback started, progressed or ended, see `replay::ReplayStatus`.
+pub const EVENT_REPLAY: &str = "betfair_replay";
@@ -0,0 +1,296 @@
+//! Betfair's navigation hierarchy (event types → competitions → events →
+//! markets), fetched one level at a time as the tree is expanded and kept
+//! until the level's TTL runs out.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::market::catalogue::{CatalogueEvent, CatalogueMarket, CatalogueRef};
+
+const EVENT_TYPES_TTL_MS: u64 = 60 * 60 * 1000;
+const COMPETITIONS_TTL_MS: u64 = 30 * 60 * 1000;
+const EVENTS_TTL_MS: u64 = 5 * 60 * 1000;
+const MARKETS_TTL_MS: u64 = 60 * 1000;
+
+/// Id prefix of the "Other events" node an event type with competitions
+/// gets for its events outside every competition.
+const OTHER_EVENTS_PREFIX: &str = "other:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NodeKind {
+    EventType,
+    Competition,
+    Event,
+    Market,
+}
+
+impl NodeKind {
+    /// How long a level of this kind of children is served from the cache.
+    pub fn ttl_ms(self) -> u64 {
+        match self {
+            NodeKind::EventType => EVENT_TYPES_TTL_MS,
+            NodeKind::Competition => COMPETITIONS_TTL_MS,
+            NodeKind::Event => EVENTS_TTL_MS,
+            NodeKind::Market => MARKETS_TTL_MS,
+        }
+    }
+}
+
+/// A node to expand; the root when absent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeRef {
+    pub kind: NodeKind,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavNode {
+    pub kind: NodeKind,
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub venue: Option<String>,
+    /// Event open date or market start time.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_play: Option<bool>,
+}
+
+impl NavNode {
+    fn new(kind: NodeKind, id: String, name: Option<String>) -> Self {
+        Self {
+            kind,
+            id,
+            name: name.unwrap_or_default(),
+            market_count: None,
+            country_code: None,
+            venue: None,
+            start_time: None,
+            market_type: None,
+            status: None,
+            in_play: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventTypeResult {
+    event_type: CatalogueRef,
+    market_count: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CompetitionResult {
+    competition: CatalogueRef,
+    market_count: Option<u32>,
+    competition_region: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventResult {
+    event: CatalogueEvent,
+    market_count: Option<u32>,
+}
+
+fn named(kind: NodeKind, r: CatalogueRef, market_count: Option<u32>) -> Option<NavNode> {
+    Some(NavNode {
+        market_count,
+        ..NavNode::new(kind, r.id?, r.name)
+    })
+}
+
+fn by_name(mut nodes: Vec<NavNode>) -> Vec<NavNode> {
+    nodes.sort_by_key(|n| n.name.to_lowercase());
+    nodes
+}
+
+/// Nodes from a `listEventTypes` result, by name.
+pub fn event_types(result: serde_json::Value) -> serde_json::Result<Vec<NavNode>> {
+    let items: Vec<EventTypeResult> = serde_json::from_value(result)?;
+    Ok(by_name(
+        items
+            .into_iter()
+            .filter_map(|r| named(NodeKind::EventType, r.event_type, r.market_count))
+            .collect(),
+    ))
+}
+
+/// Nodes from a `listCompetitions` result, by name.
+pub fn competitions(result: serde_json::Value) -> serde_json::Result<Vec<NavNode>> {
+    let items: Vec<CompetitionResult> = serde_json::from_value(result)?;
+    Ok(by_name(
+        items
+            .into_iter()
+            .filter_map(|r| {
+                let mut node = named(NodeKind::Competition, r.competition, r.market_count)?;
+                node.country_code = r.competition_region;
+                Some(node)
+            })
+            .collect(),
+    ))
+}
+
+/// Nodes from a `listEvents` result, first to open first.
+pub fn events(result: serde_json::Value) -> serde_json::Result<Vec<NavNode>> {
+    let items: Vec<EventResult> = serde_json::from_value(result)?;
+    let mut nodes: Vec<NavNode> = items
+        .into_iter()
+        .filter_map(|r| {
+            let e = r.event;
+            Some(NavNode {
+                market_count: r.market_count,
+                country_code: e.country_code,
+                venue: e.venue,
+                start_time: e.open_date,
+                ..NavNode::new(NodeKind::Event, e.id?, e.name)
+            })
+        })
+        .collect();
+    nodes.sort_by(|a, b| (&a.start_time, &a.name).cmp(&(&b.start_time, &b.name)));
+    Ok(nodes)
+}
+
+/// The event type behind an "Other events" node id.
+pub fn other_events_of(id: &str) -> Option<&str> {
+    id.strip_prefix(OTHER_EVENTS_PREFIX)
+}
+
+/// Events of `all` not listed under any competition.
+pub fn other_events(all: Vec<NavNode>, in_competitions: &[NavNode]) -> Vec<NavNode> {
+    let listed: HashSet<&str> = in_competitions.iter().map(|n| n.id.as_str()).collect();
+    all.into_iter()
+        .filter(|n| !listed.contains(n.id.as_str()))
+        .collect()
+}
+
+/// Node listed after an event type's competitions for `events` outside them.
+pub fn other_events_node(event_type_id: &str, events: &[NavNode]) -> NavNode {
+    NavNode {
+        market_count: Some(events.iter().filter_map(|n| n.market_count).sum()),
+        ..NavNode::new(
+            NodeKind::Competition,
+            format!("{OTHER_EVENTS_PREFIX}{event_type_id}"),
+            Some("Other events".to_string()),
+        )
+    }
+}
+
+pub fn market(m: &CatalogueMarket) -> NavNode {
+    let event = m.event.as_ref();
+    NavNode {
+        kind: NodeKind::Market,
+        id: m.market_id.clone(),
+        name: m.market_name.clone(),
+        market_count: None,
+        country_code: event.and_then(|e| e.country_code.clone()),
+        venue: event.and_then(|e| e.venue.clone()),
+        start_time: m.market_start_time.clone(),
+        market_type: m.market_type().map(str::to_string),
+        status: m.status.clone(),
+        in_play: m.in_play,
+    }
+}
+
+/// Children of one node, as served to the tree.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NavLevel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<NodeRef>,
+    /// Market type codes the level was listed for; empty for all.
+    pub market_types: Vec<String>,
+    pub children: Vec<NavNode>,
+    pub fetched_ms: u64,
+    pub expires_ms: u64,
+}
+
+type LevelKey = (Option<NodeRef>, Vec<String>);
+
+/// Levels fetched so far, by parent and market type filter.
+pub struct NavigationTree {
+    levels: Mutex<HashMap<LevelKey, NavLevel>>,
+}
+
+impl Default for NavigationTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NavigationTree {
+    pub fn new() -> Self {
+        Self {
+            levels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<LevelKey, NavLevel>> {
+        self.levels.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The cached level, unless it expired by `now_ms`.
+    pub fn level(
+        &self,
+        parent: Option<&NodeRef>,
+        market_types: &[String],
+        now_ms: u64,
+    ) -> Option<NavLevel> {
+        self.lock()
+            .get(&(parent.cloned(), market_types.to_vec()))
+            .filter(|l| l.expires_ms > now_ms)
+            .cloned()
+    }
+
+    pub fn insert(&self, level: NavLevel) {
+        let key = (level.parent.clone(), level.market_types.clone());
+        let mut levels = self.lock();
+        levels.retain(|_, l| l.expires_ms > level.fetched_ms);
+        levels.insert(key, level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(id: &str, market_count: Option<u32>) -> NavNode {
+        NavNode {
+            market_count,
+            ..NavNode::new(NodeKind::Event, id.to_string(), Some(id.to_string()))
+        }
+    }
+
+    #[test]
+    fn other_events_are_the_ones_outside_competitions() {
+        let all = vec![
+            event("1", Some(4)),
+            event("2", Some(1)),
+            event("3", Some(2)),
+        ];
+        let others = other_events(all, &[event("2", Some(1))]);
+        let ids: Vec<&str> = others.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["1", "3"]);
+
+        let node = other_events_node("1", &others);
+        assert_eq!(node.kind, NodeKind::Competition);
+        assert_eq!(other_events_of(&node.id), Some("1"));
+        assert_eq!(node.market_count, Some(6));
+        assert_eq!(other_events_of("12282"), None);
+    }
+}
@@ -0,0 +1,75 @@
+use serde::Serialize;
+
+use crate::clock::parse_utc_ms;
+use crate::market::cache::{runner_key, RunnerKey};
+use crate::ticks::MIN_ODDS;
+
+/// A withdrawn runner, from `marketDefinition.runners[]` with status REMOVED.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnerRemoval {
+    pub selection_id: u64,
+    pub handicap: f64,
+    /// `removalDate`; when Betfair leaves it out, when we first saw it.
+    pub removed_at_ms: u64,
+    /// Reduction factor in percent applied to bets matched on the other
+    /// runners before `removed_at_ms`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adjustment_factor: Option<f64>,
+}
+
+/// Payload of `EVENT_RUNNER_REMOVED`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnerRemoved {
+    pub market_id: String,
+    #[serde(flatten)]
+    pub removal: RunnerRemoval,
+    /// Sum of the factors of every removal in the market so far.
+    pub total_reduction_factor: f64,
+}
+
+/// REMOVED runners in a market definition.
+pub fn removals(def: &serde_json::Value, now_ms: u64) -> Vec<(RunnerKey, RunnerRemoval)> {
+    let Some(runners) = def.get("runners").and_then(|v| v.as_array()) else {
+        return Vec::new();
+    };
+    runners
+        .iter()
+        .filter(|r| r.get("status").and_then(|v| v.as_str()) == Some("REMOVED"))
+        .filter_map(|r| {
+            let selection_id = r.get("id")?.as_u64()?;
+            let handicap = r.get("hc").and_then(|v| v.as_f64()).unwrap_or(0.0);
+            let removal = RunnerRemoval {
+                selection_id,
+                handicap,
+                removed_at_ms: r
+                    .get("removalDate")
+                    .and_then(|v| v.as_str())
+                    .and_then(parse_utc_ms)
+                    .unwrap_or(now_ms),
+                adjustment_factor: r.get("adjustmentFactor").and_then(|v| v.as_f64()),
+            };
+            Some((runner_key(selection_id, handicap), removal))
+        })
+        .collect()
+}
+
+/// Reduction factor (percent) owed by a bet matched at `matched_at_ms`: the
+/// factors of the removals that came after it.
+pub fn reduction_factor<'a>(
+    removals: impl IntoIterator<Item = &'a RunnerRemoval>,
+    matched_at_ms: u64,
+) -> f64 {
+    removals
+        .into_iter()
+        .filter(|r| r.removed_at_ms > matched_at_ms)
+        .filter_map(|r| r.adjustment_factor)
+        .sum()
+}
+
+/// Betfair reduces the matched odds by the factor, never below 1.01.
+pub fn reduced_price(price: f64, factor: f64) -> f64 {
+    let reduced = price * (1.0 - factor.clamp(0.0, 100.0) / 100.0);
+    ((reduced * 100.0).round() / 100.0).max(MIN_ODDS)
+}
@@ -0,0 +1,129 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::market::cache::MarketCache;
+use crate::stream::mcm::MarketChangeMessage;
+use crate::ui_error::UiErrorPayload;
+
+/// Fastest playback `historical_replay_start` accepts; 0 means no pacing.
+pub const MAX_REPLAY_SPEED: f64 = 1000.0;
+
+/// Payload of `EVENT_REPLAY`.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayStatus {
+    pub running: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Markets played; empty for every market in the file.
+    pub market_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscriber_id: Option<String>,
+    pub speed: f64,
+    pub frames: u64,
+    /// Publish time of the last frame played.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pt: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<UiErrorPayload>,
+}
+
+/// A replayed runner removal or lifecycle event: the live payload plus
+/// `replay: true`, so the UI doesn't alert on it like a live one.
+#[derive(Debug, Serialize)]
+pub struct Replayed<'a, T> {
+    #[serde(flatten)]
+    pub event: &'a T,
+    pub replay: bool,
+}
+
+impl<'a, T> Replayed<'a, T> {
+    pub fn new(event: &'a T) -> Self {
+        Self {
+            event,
+            replay: true,
+        }
+    }
+}
+
+/// Playback of a historical file. Replayed markets live in their own cache,
+/// so they never mix with live markets, positions, stops or strategies.
+pub struct Replay {
+    pub markets: Arc<MarketCache>,
+    status: Mutex<ReplayStatus>,
+    /// Bumped on every start and stop; a playback runs while it holds the
+    /// current value.
+    generation: AtomicU64,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Replay {
+    pub fn new() -> Self {
+        Self {
+            markets: Arc::new(MarketCache::new()),
+            status: Mutex::new(ReplayStatus::default()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ReplayStatus> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn status(&self) -> ReplayStatus {
+        self.lock().clone()
+    }
+
+    /// Replaces whatever was playing; returns the new playback's generation.
+    ///
+    /// The status lock is held throughout, so a frame the old playback is
+    /// applying lands before the cache is cleared, never after.
+    pub fn start(&self, status: ReplayStatus) -> u64 {
+        let mut current = self.lock();
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        *current = status;
+        self.markets.retain(&Default::default());
+        generation
+    }
+
+    pub fn stop(&self) -> ReplayStatus {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        let mut status = self.lock();
+        status.running = false;
+        status.clone()
+    }
+
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.generation.load(Ordering::SeqCst) == generation
+    }
+
+    /// Applies a frame of playback `generation`, if it is still current.
+    pub fn apply(&self, generation: u64, msg: &MarketChangeMessage) -> bool {
+        let _status = self.lock();
+        if !self.is_current(generation) {
+            return false;
+        }
+        self.markets.apply(0, msg);
+        true
+    }
+
+    /// Updates the status of playback `generation`, if it is still current.
+    pub fn update(
+        &self,
+        generation: u64,
+        f: impl FnOnce(&mut ReplayStatus),
+    ) -> Option<ReplayStatus> {
+        let mut status = self.lock();
+        if !self.is_current(generation) {
+            return None;
+        }
+        f(&mut status);
+        Some(status.clone())
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::clock::now_ms;
+use crate::market::cache::{runner_key, RunnerKey};
+use crate::market::ladder::PriceLadder;
+use crate::stream::mcm::ChangeType;
+use crate::stream::ocm::{OrderChangeMessage, OrderRunnerChange, OrderStatus, UnmatchedOrder};
+use crate::ticks::price_key;
+
+/// (back, price key) of a matched ladder level.
+pub type MatchedLevel = (bool, i64);
+
+/// Latest order stream state of one bet, kept after it completes so
+/// commands can confirm fills and cancels.
+#[derive(Debug, Clone)]
+pub struct TrackedOrder {
+    pub market_id: String,
+    pub selection_id: u64,
+    pub handicap: f64,
+    pub order: UnmatchedOrder,
+}
+
+/// One selection's orders, as handed to the position engine.
+#[derive(Debug, Clone)]
+pub struct RunnerOrdersSnapshot {
+    pub key: RunnerKey,
+    pub unmatched: Vec<UnmatchedOrder>,
+    pub matched_backs: PriceLadder,
+    pub matched_lays: PriceLadder,
+    /// When size was first matched at each level, for reduction factors.
+    pub matched_at: HashMap<MatchedLevel, u64>,
+}
+
+/// Records when each level of `changes` first got size; emptied levels go.
+fn note_matched(
+    matched_at: &mut HashMap<MatchedLevel, u64>,
+    back: bool,
+    changes: &[[f64; 2]],
+    at_ms: u64,
+) {
+    for [price, size] in changes {
+        let level = (back, price_key(*price));
+        if *size == 0.0 {
+            matched_at.remove(&level);
+        } else {
+            matched_at.entry(level).or_insert(at_ms);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct RunnerOrders {
+    /// Bet id -> order; only executable orders are kept.
+    unmatched: BTreeMap<String, UnmatchedOrder>,
+    matched_backs: PriceLadder,
+    matched_lays: PriceLadder,
+    matched_at: HashMap<MatchedLevel, u64>,
+    /// `customerStrategyRef` -> (matched backs, matched lays).
+    strategies: HashMap<String, (PriceLadder, PriceLadder)>,
+}
+
+impl RunnerOrders {
+    fn apply(&mut self, orc: &OrderRunnerChange, at_ms: u64) {
+        if orc.full_image {
+            *self = Self::default();
+        }
+        for order in &orc.uo {
+            if order.status == OrderStatus::ExecutionComplete {
+                self.unmatched.remove(&order.id);
+            } else {
+                self.unmatched.insert(order.id.clone(), order.clone());
+            }
+        }
+        if let Some(mb) = &orc.mb {
+            self.matched_backs.apply(mb);
+            note_matched(&mut self.matched_at, true, mb, at_ms);
+        }
+        if let Some(ml) = &orc.ml {
+            self.matched_lays.apply(ml);
+            note_matched(&mut self.matched_at, false, ml, at_ms);
+        }
+        for (strategy_ref, change) in orc.smc.iter().flatten() {
+            let (backs, lays) = self.strategies.entry(strategy_ref.clone()).or_default();
+            if let Some(mb) = &change.mb {
+                backs.apply(mb);
+            }
+            if let Some(ml) = &change.ml {
+                lays.apply(ml);
+            }
+        }
+    }
+
+    /// Only the orders placed with `strategy_ref`; `None` if there are none.
+    fn for_strategy(&self, key: RunnerKey, strategy_ref: &str) -> Option<RunnerOrdersSnapshot> {
+        let unmatched: Vec<UnmatchedOrder> = self
+            .unmatched
+            .values()
+            .filter(|o| o.rfs.as_deref() == Some(strategy_ref))
+            .cloned()
+            .collect();
+        let matched = self.strategies.get(strategy_ref);
+        if unmatched.is_empty() && matched.is_none() {
+            return None;
+        }
+        let (matched_backs, matched_lays) = matched.cloned().unwrap_or_default();
+        Some(RunnerOrdersSnapshot {
+            key,
+            unmatched,
+            matched_backs,
+            matched_lays,
+            matched_at: self.matched_at.clone(),
+        })
+    }
+}
+
+/// Our orders as reported by the order stream, per market and selection.
+pub struct OrderCache {
+    markets: Mutex<HashMap<String, HashMap<RunnerKey, RunnerOrders>>>,
+    /// Bet id -> latest state, including completed bets.
+    bets: Mutex<HashMap<String, TrackedOrder>>,
+    /// Bumped after every applied frame.
+    version: watch::Sender<u64>,
+}
+
+impl Default for OrderCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrderCache {
+    pub fn new() -> Self {
+        Self {
+            markets: Mutex::new(HashMap::new()),
+            bets: Mutex::new(HashMap::new()),
+            version: watch::channel(0).0,
+        }
+    }
+
+    fn bets(&self) -> std::sync::MutexGuard<'_, HashMap<String, TrackedOrder>> {
+        self.bets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, HashMap<RunnerKey, RunnerOrders>>> {
+        self.markets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn apply(&self, msg: &OrderChangeMessage) {
+        if msg.ct == Some(ChangeType::Heartbeat) {
+            return;
+        }
+        {
+            let mut markets = self.lock();
+            let mut bets = self.bets();
+            if msg.starts_image() {
+                markets.clear();
+            }
+            let at_ms = msg.pt.unwrap_or_else(now_ms);
+            for oc in &msg.oc {
+                if oc.full_image {
+                    markets.remove(&oc.id);
+                }
+                let runners = markets.entry(oc.id.clone()).or_default();
+                for orc in &oc.orc {
+                    let handicap = orc.hc.unwrap_or(0.0);
+                    let key = runner_key(orc.id, handicap);
+                    runners.entry(key).or_default().apply(orc, at_ms);
+                    for order in &orc.uo {
+                        bets.insert(
+                            order.id.clone(),
+                            TrackedOrder {
+                                market_id: oc.id.clone(),
+                                selection_id: orc.id,
+                                handicap,
+                                order: order.clone(),
+                            },
+                        );
+                    }
+                }
+                if oc.closed {
+                    markets.remove(&oc.id);
+                    bets.retain(|_, t| t.market_id != oc.id);
+                }
+            }
+        }
+        self.version.send_modify(|v| *v = v.wrapping_add(1));
+    }
+
+    pub fn bet(&self, bet_id: &str) -> Option<TrackedOrder> {
+        self.bets().get(bet_id).cloned()
+    }
+
+    /// Waits up to `timeout` for the order stream to report `bet_id` in a
+    /// state `done` accepts; the last state seen otherwise.
+    pub async fn wait_for(
+        &self,
+        bet_id: &str,
+        timeout: Duration,
+        done: impl Fn(&TrackedOrder) -> bool,
+    ) -> Result<TrackedOrder, Option<TrackedOrder>> {
+        let mut changed = self.version.subscribe();
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let bet = self.bet(bet_id);
+            if let Some(t) = bet.as_ref().filter(|t| done(t)) {
+                return Ok(t.clone());
+            }
+            match tokio::time::timeout_at(deadline, changed.changed()).await {
+                Ok(Ok(())) => continue,
+                _ => return Err(bet),
+            }
+        }
+    }
+
+    /// Executable orders on a selection, oldest bet id first.
+    pub fn unmatched(
+        &self,
+        market_id: &str,
+        selection_id: u64,
+        handicap: f64,
+    ) -> Vec<UnmatchedOrder> {
+        self.lock()
+            .get(market_id)
+            .and_then(|runners| runners.get(&runner_key(selection_id, handicap)))
+            .map(|r| r.unmatched.values().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Executable orders across every selection of a market.
+    pub fn market_unmatched(&self, market_id: &str) -> Vec<UnmatchedOrder> {
+        self.lock()
+            .get(market_id)
+            .map(|runners| {
+                runners
+                    .values()
+                    .flat_map(|r| r.unmatched.values().cloned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Every selection we have orders on in a market; `None` if none.
+    pub fn market(&self, market_id: &str) -> Option<Vec<RunnerOrdersSnapshot>> {
+        let markets = self.lock();
+        let runners = markets.get(market_id)?;
+        Some(
+            runners
+                .iter()
+                .map(|(key, r)| RunnerOrdersSnapshot {
+                    key: *key,
+                    unmatched: r.unmatched.values().cloned().collect(),
+                    matched_backs: r.matched_backs.clone(),
+                    matched_lays: r.matched_lays.clone(),
+                    matched_at: r.matched_at.clone(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Like `market`, limited to one `customerStrategyRef`.
+    pub fn strategy_market(
+        &self,
+        market_id: &str,
+        strategy_ref: &str,
+    ) -> Option<Vec<RunnerOrdersSnapshot>> {
+        let markets = self.lock();
+        let runners: Vec<RunnerOrdersSnapshot> = markets
+            .get(market_id)?
+            .iter()
+            .filter_map(|(key, r)| r.for_strategy(*key, strategy_ref))
+            .collect();
+        (!runners.is_empty()).then_some(runners)
+    }
+
+    /// Markets with orders placed under `strategy_ref`.
+    pub fn strategy_markets(&self, strategy_ref: &str) -> Vec<String> {
+        self.lock()
+            .iter()
+            .filter(|(_, runners)| {
+                runners.values().any(|r| {
+                    r.strategies.contains_key(strategy_ref)
+                        || r.unmatched
+                            .values()
+                            .any(|o| o.rfs.as_deref() == Some(strategy_ref))
+                })
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Matched backs and lays by price.
+    pub fn matched(
+        &self,
+        market_id: &str,
+        selection_id: u64,
+        handicap: f64,
+    ) -> (PriceLadder, PriceLadder) {
+        self.lock()
+            .get(market_id)
+            .and_then(|runners| runners.get(&runner_key(selection_id, handicap)))
+            .map(|r| (r.matched_backs.clone(), r.matched_lays.clone()))
+            .unwrap_or_default()
+    }
+
+    /// Bets on a withdrawn runner are void; the order stream confirms with
+    /// `sv`, this drops them from positions straight away.
+    pub fn void_runner(&self, market_id: &str, selection_id: u64, handicap: f64) {
+        if let Some(runners) = self.lock().get_mut(market_id) {
+            runners.remove(&runner_key(selection_id, handicap));
+        }
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+        self.bets().clear();
+    }
+}
@@ -0,0 +1,289 @@
+use serde::Serialize;
+
+use crate::orders::place::{limit_order, OrderSide};
+use crate::orders::position::{hedge, MarketPosition};
+use crate::ticks::{Rounding, TickLadder};
+
+/// Betfair's minimum stake in GBP; other currencies pass their own.
+pub const DEFAULT_MIN_STAKE: f64 = 1.0;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HedgeLeg {
+    pub selection_id: u64,
+    pub handicap: f64,
+    pub side: OrderSide,
+    pub price: f64,
+    pub stake: f64,
+    /// The selection's P&L, win or lose, once this leg is matched.
+    pub profit: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SkipReason {
+    /// Nothing to hedge on the selection.
+    Level,
+    /// No price on the side the hedge needs.
+    NoPrice,
+    BelowMinStake,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedLeg {
+    pub selection_id: u64,
+    pub handicap: f64,
+    pub reason: SkipReason,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stake: Option<f64>,
+}
+
+/// Orders that green up a market, or one selection of it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HedgePlan {
+    pub market_id: String,
+    pub legs: Vec<HedgeLeg>,
+    pub skipped: Vec<SkippedLeg>,
+    /// `placeOrders` instructions for `legs`.
+    pub instructions: Vec<serde_json::Value>,
+    /// Market P&L, whoever wins, if every leg is matched and nothing was
+    /// skipped; otherwise unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub green_profit: Option<f64>,
+}
+
+/// Builds the hedge from a position. `only` limits it to one selection
+/// (selection id, handicap); prices are snapped onto the market's ladder and
+/// legs below `min_stake` are skipped.
+pub fn plan(
+    position: &MarketPosition,
+    tick_ladder: &TickLadder,
+    only: Option<(u64, f64)>,
+    min_stake: f64,
+) -> HedgePlan {
+    let mut legs = Vec::new();
+    let mut skipped = Vec::new();
+    let wanted = |sel: u64, hc: f64| only.is_none_or(|(s, h)| s == sel && h == hc);
+
+    for r in position
+        .runners
+        .iter()
+        .filter(|r| wanted(r.selection_id, r.handicap))
+    {
+        let skip = |reason, stake| SkippedLeg {
+            selection_id: r.selection_id,
+            handicap: r.handicap,
+            reason,
+            stake,
+        };
+        if (r.win - r.lose).abs() < 0.005 {
+            if r.matched_back > 0.0 || r.matched_lay > 0.0 {
+                skipped.push(skip(SkipReason::Level, None));
+            }
+            continue;
+        }
+        let Some(h) = &r.hedge else {
+            skipped.push(skip(SkipReason::NoPrice, None));
+            continue;
+        };
+        // Best prices are on the ladder already; rounding guards against a
+        // stale ladder type, and the stake follows the rounded price.
+        let rounding = match h.side {
+            OrderSide::Back => Rounding::Down,
+            OrderSide::Lay => Rounding::Up,
+        };
+        let Some(price) = tick_ladder.round(h.price, rounding) else {
+            skipped.push(skip(SkipReason::NoPrice, None));
+            continue;
+        };
+        let Some(h) = hedge(r.win, r.lose, Some(price), Some(price), tick_ladder) else {
+            continue;
+        };
+        if h.stake < min_stake {
+            skipped.push(skip(SkipReason::BelowMinStake, Some(h.stake)));
+            continue;
+        }
+        legs.push(HedgeLeg {
+            selection_id: r.selection_id,
+            handicap: r.handicap,
+            side: h.side,
+            price: h.price,
+            stake: h.stake,
+            profit: h.profit,
+        });
+    }
+
+    let complete = skipped.iter().all(|s| s.reason == SkipReason::Level);
+    let green_profit = (complete && only.is_none()).then(|| {
+        // Every selection ends level: at its leg's profit, or already there.
+        let total: f64 = position
+            .runners
+            .iter()
+            .map(|r| {
+                legs.iter()
+                    .find(|l| l.selection_id == r.selection_id && l.handicap == r.handicap)
+                    .map_or(r.lose, |l| l.profit)
+            })
+            .sum();
+        (total * 100.0).round() / 100.0
+    });
+
+    HedgePlan {
+        market_id: position.market_id.clone(),
+        instructions: legs
+            .iter()
+            .map(|l| limit_order(l.selection_id, l.handicap, l.side, l.stake, l.price))
+            .collect(),
+        legs,
+        skipped,
+        green_profit,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orders::position::{Hedge, RunnerPosition};
+
+    /// A runner with matched bets worth `win` / `lose`, hedged at `price`.
+    fn runner(selection_id: u64, win: f64, lose: f64, price: Option<f64>) -> RunnerPosition {
+        RunnerPosition {
+            selection_id,
+            handicap: 0.0,
+            matched_back: 10.0,
+            matched_lay: 0.0,
+            back_avg_price: None,
+            lay_avg_price: None,
+            if_wins: 0.0,
+            win,
+            lose,
+            unmatched_liability: 0.0,
+            hedge: price.and_then(|p| hedge(win, lose, Some(p), Some(p), &TickLadder::Classic)),
+            result: None,
+        }
+    }
+
+    fn position(runners: Vec<RunnerPosition>) -> MarketPosition {
+        MarketPosition {
+            market_id: "1.1".into(),
+            runners,
+            liability: 0.0,
+            unmatched_liability: 0.0,
+            green_profit: None,
+            realised: None,
+        }
+    }
+
+    fn plan_for(p: &MarketPosition, min_stake: f64) -> HedgePlan {
+        plan(p, &TickLadder::Classic, None, min_stake)
+    }
+
+    #[test]
+    fn stake_is_the_difference_over_the_odds() {
+        // Lay (20 - -5) / 5.0 = 5: -5 + 5 = 0 either way.
+        let Some(Hedge {
+            side,
+            stake,
+            profit,
+            ..
+        }) = hedge(20.0, -5.0, None, Some(5.0), &TickLadder::Classic)
+        else {
+            panic!("no lay hedge");
+        };
+        assert_eq!((side, stake, profit), (OrderSide::Lay, 5.0, 0.0));
+
+        // Back (10 - -10) / 3.0 = 6.67 to the penny: 10 - 6.67 = 3.33.
+        let h = hedge(-10.0, 10.0, Some(3.0), None, &TickLadder::Classic).unwrap();
+        assert_eq!((h.side, h.stake, h.profit), (OrderSide::Back, 6.67, 3.33));
+
+        // Level already, or no price on the side needed.
+        assert!(hedge(4.0, 4.0, Some(3.0), Some(3.1), &TickLadder::Classic).is_none());
+        assert!(hedge(20.0, -5.0, Some(5.0), None, &TickLadder::Classic).is_none());
+    }
+
+    #[test]
+    fn line_markets_hedge_at_even_money() {
+        let line = TickLadder::LineRange {
+            min: 0.5,
+            max: 99.5,
+            interval: 1.0,
+        };
+        let h = hedge(10.0, -10.0, None, Some(45.5), &line).unwrap();
+        assert_eq!((h.side, h.stake, h.profit), (OrderSide::Lay, 10.0, 0.0));
+    }
+
+    #[test]
+    fn plan_greens_every_selection() {
+        let p = position(vec![
+            runner(1, 20.0, -5.0, Some(5.0)),
+            runner(2, -10.0, 10.0, Some(3.0)),
+            // Matched, but level.
+            runner(3, 2.0, 2.0, Some(4.0)),
+        ]);
+        let plan = plan_for(&p, DEFAULT_MIN_STAKE);
+        let legs: Vec<_> = plan
+            .legs
+            .iter()
+            .map(|l| (l.selection_id, l.side, l.price, l.stake, l.profit))
+            .collect();
+        assert_eq!(
+            legs,
+            [
+                (1, OrderSide::Lay, 5.0, 5.0, 0.0),
+                (2, OrderSide::Back, 3.0, 6.67, 3.33),
+            ]
+        );
+        assert_eq!(plan.skipped.len(), 1);
+        assert_eq!(plan.skipped[0].reason, SkipReason::Level);
+        // 0 + 3.33 + 2 already level.
+        assert_eq!(plan.green_profit, Some(5.33));
+        assert_eq!(plan.instructions.len(), 2);
+        assert_eq!(plan.instructions[1]["limitOrder"]["size"], 6.67);
+    }
+
+    #[test]
+    fn plan_snaps_prices_and_recomputes_the_stake() {
+        // A stale 4.95 lays at 5.0 (up for a lay), so 25 / 5.0 = 5.
+        let mut r = runner(1, 20.0, -5.0, None);
+        r.hedge = hedge(20.0, -5.0, None, Some(4.95), &TickLadder::Finest);
+        let plan = plan(&position(vec![r]), &TickLadder::Classic, None, 1.0);
+        assert_eq!((plan.legs[0].price, plan.legs[0].stake), (5.0, 5.0));
+    }
+
+    #[test]
+    fn plan_skips_legs_below_the_minimum_stake() {
+        let p = position(vec![
+            // 1 / 2.0 = 0.5 to lay.
+            runner(1, 1.0, 0.0, Some(2.0)),
+            runner(2, 20.0, -5.0, Some(5.0)),
+        ]);
+        let plan = plan_for(&p, DEFAULT_MIN_STAKE);
+        assert_eq!(plan.legs.len(), 1);
+        assert_eq!(plan.skipped[0].reason, SkipReason::BelowMinStake);
+        assert_eq!(plan.skipped[0].stake, Some(0.5));
+        assert_eq!(plan.green_profit, None);
+
+        // A smaller currency minimum lets it through.
+        let plan = plan_for(&p, 0.5);
+        assert_eq!(plan.legs.len(), 2);
+        assert_eq!(plan.green_profit, Some(0.5));
+    }
+
+    #[test]
+    fn plan_for_one_selection_has_no_market_green_profit() {
+        let p = position(vec![
+            runner(1, 20.0, -5.0, Some(5.0)),
+            runner(2, 5.0, 0.0, None),
+        ]);
+        let plan = plan(&p, &TickLadder::Classic, Some((1, 0.0)), 1.0);
+        assert_eq!(plan.legs.len(), 1);
+        assert!(plan.skipped.is_empty());
+        assert_eq!(plan.green_profit, None);
+
+        let plan = plan_for(&p, 1.0);
+        assert_eq!(plan.skipped[0].reason, SkipReason::NoPrice);
+        assert_eq!(plan.green_profit, None);
+    }
+}
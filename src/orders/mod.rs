@@ -0,0 +1,12 @@
+pub mod cache;
+pub mod hedge;
+pub mod place;
+pub mod position;
+pub mod result;
+pub mod stops;
+
+/// Live P&L for a market with orders, see `position::MarketPosition`.
+pub const EVENT_POSITION: &str = "betfair_position";
+
+/// A stop was created, trailed, fired or finished; payload `stops::StopOrder`.
+pub const EVENT_STOP: &str = "betfair_stop";
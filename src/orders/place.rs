@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::clock::now_ms;
+use crate::stream::ocm::Side;
+use crate::ui_error::UiErrorPayload;
+
+/// `PlaceInstruction.side` as the Betting API spells it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OrderSide {
+    Back,
+    Lay,
+}
+
+impl From<Side> for OrderSide {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Back => Self::Back,
+            Side::Lay => Self::Lay,
+        }
+    }
+}
+
+fn check_liability(liability: f64) -> Result<f64, UiErrorPayload> {
+    if liability.is_finite() && liability > 0.0 {
+        return Ok((liability * 100.0).round() / 100.0);
+    }
+    Err(UiErrorPayload::with_values(
+        "errors:validation.invalidLiability",
+        json!({ "liability": liability }),
+    ))
+}
+
+/// What happens to the unmatched part of a LIMIT order at the off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Persistence {
+    #[default]
+    Lapse,
+    Persist,
+    MarketOnClose,
+}
+
+impl Persistence {
+    /// Short code used by the order stream (`uo.pt`).
+    pub fn stream_code(self) -> &'static str {
+        match self {
+            Self::Lapse => "L",
+            Self::Persist => "P",
+            Self::MarketOnClose => "MOC",
+        }
+    }
+}
+
+/// LIMIT order that lapses at the off (`persistenceType` LAPSE).
+pub fn limit_order(
+    selection_id: u64,
+    handicap: f64,
+    side: OrderSide,
+    size: f64,
+    price: f64,
+) -> serde_json::Value {
+    limit_order_with(
+        selection_id,
+        handicap,
+        side,
+        size,
+        price,
+        Persistence::Lapse,
+        None,
+    )
+}
+
+pub fn limit_order_with(
+    selection_id: u64,
+    handicap: f64,
+    side: OrderSide,
+    size: f64,
+    price: f64,
+    persistence: Persistence,
+    order_ref: Option<&str>,
+) -> serde_json::Value {
+    let mut ins = json!({
+        "orderType": "LIMIT",
+        "selectionId": selection_id,
+        "handicap": handicap,
+        "side": side,
+        "limitOrder": { "size": size, "price": price, "persistenceType": persistence },
+    });
+    if let Some(r) = order_ref {
+        ins["customerOrderRef"] = json!(r);
+    }
+    ins
+}
+
+/// LIMIT_ON_CLOSE: takes part in BSP reconciliation only if the starting
+/// price is at least (back) / at most (lay) `price`. `liability` is the
+/// backer's stake or the layer's liability. Callers check `price` against
+/// the market's ladder; only non-finite prices are rejected here.
+pub fn limit_on_close(
+    selection_id: u64,
+    handicap: f64,
+    side: OrderSide,
+    liability: f64,
+    price: f64,
+) -> Result<serde_json::Value, UiErrorPayload> {
+    if !price.is_finite() {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.invalidPrice",
+            json!({ "price": price }),
+        ));
+    }
+    Ok(json!({
+        "orderType": "LIMIT_ON_CLOSE",
+        "selectionId": selection_id,
+        "handicap": handicap,
+        "side": side,
+        "limitOnCloseOrder": { "liability": check_liability(liability)?, "price": price },
+    }))
+}
+
+/// MARKET_ON_CLOSE: matched at whatever the BSP turns out to be.
+pub fn market_on_close(
+    selection_id: u64,
+    handicap: f64,
+    side: OrderSide,
+    liability: f64,
+) -> Result<serde_json::Value, UiErrorPayload> {
+    Ok(json!({
+        "orderType": "MARKET_ON_CLOSE",
+        "selectionId": selection_id,
+        "handicap": handicap,
+        "side": side,
+        "marketOnCloseOrder": { "liability": check_liability(liability)? },
+    }))
+}
+
+/// `placeOrders` params for one market. Without a `customer_ref` one is
+/// generated so a retried request can't be placed twice.
+pub fn place_orders_params(
+    market_id: &str,
+    instructions: Vec<serde_json::Value>,
+    customer_ref: Option<&str>,
+    strategy_ref: Option<&str>,
+) -> serde_json::Value {
+    let customer_ref = customer_ref.map_or_else(new_customer_ref, str::to_string);
+    let mut params = json!({
+        "marketId": market_id,
+        "instructions": instructions,
+        "customerRef": customer_ref,
+    });
+    if let Some(r) = strategy_ref {
+        params["customerStrategyRef"] = json!(r);
+    }
+    params
+}
+
+pub fn cancel_instruction(bet_id: &str, size_reduction: Option<f64>) -> serde_json::Value {
+    let mut ins = json!({ "betId": bet_id });
+    if let Some(size) = size_reduction {
+        ins["sizeReduction"] = json!(size);
+    }
+    ins
+}
+
+pub fn replace_instruction(bet_id: &str, new_price: f64) -> serde_json::Value {
+    json!({ "betId": bet_id, "newPrice": new_price })
+}
+
+pub fn update_instruction(bet_id: &str, persistence: Persistence) -> serde_json::Value {
+    json!({ "betId": bet_id, "newPersistenceType": persistence })
+}
+
+/// Unique per call: millisecond clock plus a counter, well inside 32 chars.
+pub fn new_customer_ref() -> String {
+    static SEQ: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "bsa-{:x}-{:x}",
+        now_ms(),
+        SEQ.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Betfair caps `customerStrategyRef` at 15 characters and the other refs
+/// at 32.
+pub fn validate_ref(value: &str, max: usize) -> Result<(), UiErrorPayload> {
+    if value.is_empty() || value.chars().count() > max {
+        return Err(UiErrorPayload::with_values(
+            "errors:validation.refLength",
+            json!({ "max": max }),
+        ));
+    }
+    Ok(())
+}
+
+pub const MAX_STRATEGY_REF: usize = 15;
+pub const MAX_ORDER_REF: usize = 32;
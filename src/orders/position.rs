@@ -0,0 +1,476 @@
+//! Per-market positions from the order cache: "if wins" P&L per selection,
+//! liability, the hedge that greens each selection up at current prices and
+//! realised P&L once results are in. Amounts are before commission; matched
+//! odds are reduced for runners withdrawn after the bet was matched.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::market::cache::{runner_key, MarketState, RunnerKey};
+use crate::market::ladder::PriceLadder;
+use crate::market::removal::{reduced_price, reduction_factor};
+use crate::orders::cache::RunnerOrdersSnapshot;
+use crate::orders::place::OrderSide;
+use crate::risk::limits::order_liability;
+use crate::ticks::{key_price, price_key, TickLadder};
+
+/// Order that equalises a selection's win and lose outcomes.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hedge {
+    pub side: OrderSide,
+    pub price: f64,
+    pub stake: f64,
+    /// What the selection is worth win or lose once hedged.
+    pub profit: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnerPosition {
+    pub selection_id: u64,
+    pub handicap: f64,
+    pub matched_back: f64,
+    pub matched_lay: f64,
+    /// Average matched odds per side.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub back_avg_price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lay_avg_price: Option<f64>,
+    /// Market P&L if this selection wins (matched bets only).
+    pub if_wins: f64,
+    /// This selection's own bets if it wins / loses.
+    pub win: f64,
+    pub lose: f64,
+    /// Worst case of the unmatched orders if they were matched.
+    pub unmatched_liability: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hedge: Option<Hedge>,
+    /// WINNER, LOSER, PLACED or REMOVED once known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+}
+
+/// Payload of `EVENT_POSITION`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketPosition {
+    pub market_id: String,
+    pub runners: Vec<RunnerPosition>,
+    /// `-min(if_wins)` when that is a loss, else 0.
+    pub liability: f64,
+    pub unmatched_liability: f64,
+    /// Locked-in profit if every selection with a position were hedged at
+    /// current prices; unset when a price is missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub green_profit: Option<f64>,
+    /// Set once every selection with a position has a result.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub realised: Option<f64>,
+}
+
+fn money(x: f64) -> f64 {
+    (x * 100.0).round() / 100.0
+}
+
+/// (stake, profit if the selection wins) of a matched ladder, per unit odds.
+fn side_totals(ladder: &PriceLadder, tick_ladder: &TickLadder) -> (f64, f64) {
+    ladder
+        .iter()
+        .fold((0.0, 0.0), |(stake, win), (price, size)| {
+            (stake + size, win + size * (tick_ladder.odds(price) - 1.0))
+        })
+}
+
+/// `ladder` at the odds it settles at: each level reduced by the factors of
+/// removals after it was first matched. Levels of unknown age stay as they are.
+fn reduced_ladder(
+    ladder: &PriceLadder,
+    back: bool,
+    orders: &RunnerOrdersSnapshot,
+    market: Option<&MarketState>,
+) -> PriceLadder {
+    let Some(market) = market else {
+        return ladder.clone();
+    };
+    let mut reduced = PriceLadder::default();
+    for (price, size) in ladder.iter() {
+        let factor = orders
+            .matched_at
+            .get(&(back, price_key(price)))
+            .map_or(0.0, |at| reduction_factor(market.removals(), *at));
+        let price = if factor > 0.0 {
+            reduced_price(price, factor)
+        } else {
+            price
+        };
+        let size = reduced.size_at(price).unwrap_or(0.0) + size;
+        reduced.apply(&[[price, size]]);
+    }
+    reduced
+}
+
+fn avg_price(ladder: &PriceLadder, stake: f64) -> Option<f64> {
+    (stake > 0.0).then(|| money(ladder.iter().map(|(p, s)| p * s).sum::<f64>() / stake))
+}
+
+/// Hedge equalising `win` and `lose`: lay when winning pays more, back when
+/// losing does. Stakes are rounded to pennies.
+pub fn hedge(
+    win: f64,
+    lose: f64,
+    best_back: Option<f64>,
+    best_lay: Option<f64>,
+    tick_ladder: &TickLadder,
+) -> Option<Hedge> {
+    let diff = win - lose;
+    if diff.abs() < 0.005 {
+        return None;
+    }
+    let (side, price) = if diff > 0.0 {
+        (OrderSide::Lay, best_lay?)
+    } else {
+        (OrderSide::Back, best_back?)
+    };
+    let stake = money(diff.abs() / tick_ladder.odds(price));
+    let profit = match side {
+        OrderSide::Lay => lose + stake,
+        OrderSide::Back => lose - stake,
+    };
+    Some(Hedge {
+        side,
+        price,
+        stake,
+        profit: money(profit),
+    })
+}
+
+fn results(market: Option<&MarketState>) -> HashMap<RunnerKey, String> {
+    let runners = market
+        .and_then(|m| m.market_definition.as_ref())
+        .and_then(|d| d.get("runners"))
+        .and_then(|v| v.as_array());
+    runners
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|r| {
+            let id = r.get("id")?.as_u64()?;
+            let hc = r.get("hc").and_then(|v| v.as_f64()).unwrap_or(0.0);
+            let status = r.get("status")?.as_str()?;
+            matches!(status, "WINNER" | "LOSER" | "PLACED" | "REMOVED")
+                .then(|| (runner_key(id, hc), status.to_string()))
+        })
+        .collect()
+}
+
+pub fn market_position(
+    market_id: &str,
+    market: Option<&MarketState>,
+    orders: &[RunnerOrdersSnapshot],
+) -> MarketPosition {
+    let tick_ladder = market.map(|m| m.tick_ladder).unwrap_or_default();
+    let results = results(market);
+
+    // Every active runner of the market, plus any we only know from orders.
+    let mut keys: BTreeMap<RunnerKey, Option<&RunnerOrdersSnapshot>> = market
+        .map(|m| {
+            m.runners
+                .iter()
+                .filter(|(_, r)| r.removal.is_none())
+                .map(|(k, _)| (*k, None))
+                .collect()
+        })
+        .unwrap_or_default();
+    for o in orders {
+        keys.insert(o.key, Some(o));
+    }
+
+    struct Sides {
+        back: f64,
+        lay: f64,
+        back_avg: Option<f64>,
+        lay_avg: Option<f64>,
+        win: f64,
+        lose: f64,
+        unmatched: f64,
+    }
+    let sides: Vec<(RunnerKey, Sides)> = keys
+        .iter()
+        .map(|(key, o)| {
+            let (backs, lays) = o.map_or_else(Default::default, |o| {
+                (
+                    reduced_ladder(&o.matched_backs, true, o, market),
+                    reduced_ladder(&o.matched_lays, false, o, market),
+                )
+            });
+            let (back, back_win) = side_totals(&backs, &tick_ladder);
+            let (lay, lay_win) = side_totals(&lays, &tick_ladder);
+            let unmatched = o.map_or(0.0, |o| {
+                o.unmatched
+                    .iter()
+                    .map(|u| order_liability(u, &tick_ladder))
+                    .sum()
+            });
+            let sides = Sides {
+                back,
+                lay,
+                back_avg: avg_price(&backs, back),
+                lay_avg: avg_price(&lays, lay),
+                win: back_win - lay_win,
+                lose: lay - back,
+                unmatched,
+            };
+            (*key, sides)
+        })
+        .collect();
+
+    let total_lose: f64 = sides.iter().map(|(_, s)| s.lose).sum();
+    let runners: Vec<RunnerPosition> = sides
+        .iter()
+        .map(|(key, s)| {
+            let state = market.and_then(|m| m.runners.get(key));
+            RunnerPosition {
+                selection_id: key.0,
+                handicap: key_price(key.1),
+                matched_back: money(s.back),
+                matched_lay: money(s.lay),
+                back_avg_price: s.back_avg,
+                lay_avg_price: s.lay_avg,
+                if_wins: money(total_lose - s.lose + s.win),
+                win: money(s.win),
+                lose: money(s.lose),
+                unmatched_liability: money(s.unmatched),
+                hedge: hedge(
+                    s.win,
+                    s.lose,
+                    state.and_then(|r| r.best_back()),
+                    state.and_then(|r| r.best_lay()),
+                    &tick_ladder,
+                ),
+                result: results.get(key).cloned(),
+            }
+        })
+        .collect();
+
+    let has_position = |r: &&RunnerPosition| r.matched_back > 0.0 || r.matched_lay > 0.0;
+    let worst = runners
+        .iter()
+        .map(|r| r.if_wins)
+        .fold(f64::INFINITY, f64::min);
+    let green_profit = runners
+        .iter()
+        .map(|r| match &r.hedge {
+            Some(h) => Some(h.profit),
+            // Already level, or nothing on it.
+            None if (r.win - r.lose).abs() < 0.005 => Some(r.lose),
+            None => None,
+        })
+        .sum::<Option<f64>>()
+        .map(money);
+    let realised = runners
+        .iter()
+        .filter(has_position)
+        .map(|r| match r.result.as_deref()? {
+            "WINNER" | "PLACED" => Some(r.win),
+            "LOSER" => Some(r.lose),
+            _ => Some(0.0),
+        })
+        .sum::<Option<f64>>()
+        .filter(|_| runners.iter().any(|r| has_position(&r)))
+        .map(money);
+
+    MarketPosition {
+        market_id: market_id.to_string(),
+        liability: if worst.is_finite() && worst < 0.0 {
+            money(-worst)
+        } else {
+            0.0
+        },
+        unmatched_liability: money(runners.iter().map(|r| r.unmatched_liability).sum()),
+        green_profit,
+        realised,
+        runners,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::cache::MarketCache;
+    use crate::stream::mcm::MarketChangeMessage;
+    use crate::stream::ocm::UnmatchedOrder;
+    use serde_json::{json, Value};
+
+    const MARKET: &str = "1.1";
+
+    /// Three runners: 1 and 2 with a lay price to hedge at, 3 without.
+    fn market(statuses: [&str; 3], removal: Option<Value>) -> MarketState {
+        let mut runners: Vec<Value> = statuses
+            .iter()
+            .enumerate()
+            .map(|(i, status)| json!({ "id": i + 1, "status": status }))
+            .collect();
+        if let Some(removal) = removal {
+            runners[2] = removal;
+        }
+        let msg = json!({
+            "op": "mcm",
+            "pt": 1,
+            "mc": [{
+                "id": MARKET,
+                "img": true,
+                "marketDefinition": { "status": "OPEN", "runners": runners },
+                "rc": [
+                    { "id": 1, "atb": [[1.9, 10]], "atl": [[2.0, 10]] },
+                    { "id": 2, "atb": [[4.8, 10]], "atl": [[5.0, 10]] },
+                ],
+            }],
+        });
+        let cache = MarketCache::new();
+        cache.apply(0, &MarketChangeMessage::parse(&msg.to_string()).unwrap());
+        cache.market(MARKET).unwrap()
+    }
+
+    fn orders(
+        selection_id: u64,
+        backs: &[[f64; 2]],
+        lays: &[[f64; 2]],
+        matched_at_ms: u64,
+    ) -> RunnerOrdersSnapshot {
+        let mut o = RunnerOrdersSnapshot {
+            key: runner_key(selection_id, 0.0),
+            unmatched: Vec::new(),
+            matched_backs: PriceLadder::default(),
+            matched_lays: PriceLadder::default(),
+            matched_at: HashMap::new(),
+        };
+        o.matched_backs.apply(backs);
+        o.matched_lays.apply(lays);
+        for (back, levels) in [(true, backs), (false, lays)] {
+            for [price, _] in levels {
+                o.matched_at
+                    .insert((back, price_key(*price)), matched_at_ms);
+            }
+        }
+        o
+    }
+
+    /// Runner 1: back 10 @ 3.0, lay 10 @ 2.5 (win +5, lose 0).
+    /// Runner 2: back 5 @ 4.0 (win +15, lose -5).
+    /// Runner 3: an unmatched lay of 2 @ 10.
+    fn book() -> Vec<RunnerOrdersSnapshot> {
+        let mut third = orders(3, &[], &[], 0);
+        third.unmatched.push(
+            serde_json::from_value::<UnmatchedOrder>(json!({
+                "id": "9", "p": 10.0, "s": 2.0, "side": "L", "status": "E", "sr": 2.0,
+            }))
+            .unwrap(),
+        );
+        vec![
+            orders(1, &[[3.0, 10.0]], &[[2.5, 10.0]], 0),
+            orders(2, &[[4.0, 5.0]], &[], 0),
+            third,
+        ]
+    }
+
+    fn runner(position: &MarketPosition, selection_id: u64) -> &RunnerPosition {
+        position
+            .runners
+            .iter()
+            .find(|r| r.selection_id == selection_id)
+            .unwrap()
+    }
+
+    #[test]
+    fn if_wins_and_liability_from_matched_bets() {
+        let m = market(["ACTIVE"; 3], None);
+        let p = market_position(MARKET, Some(&m), &book());
+
+        let r1 = runner(&p, 1);
+        assert_eq!((r1.matched_back, r1.matched_lay), (10.0, 10.0));
+        assert_eq!(
+            (r1.back_avg_price, r1.lay_avg_price),
+            (Some(3.0), Some(2.5))
+        );
+        assert_eq!((r1.win, r1.lose), (5.0, 0.0));
+        let r2 = runner(&p, 2);
+        assert_eq!((r2.win, r2.lose), (15.0, -5.0));
+
+        // Everyone else's lose (-5 from runner 2) plus the winner's own win.
+        assert_eq!(r1.if_wins, 0.0);
+        assert_eq!(r2.if_wins, 15.0);
+        assert_eq!(runner(&p, 3).if_wins, -5.0);
+        assert_eq!(p.liability, 5.0);
+        // Laying 2 @ 10 risks 2 * 9.
+        assert_eq!(runner(&p, 3).unmatched_liability, 18.0);
+        assert_eq!(p.unmatched_liability, 18.0);
+        assert_eq!(p.realised, None);
+    }
+
+    #[test]
+    fn green_up_lays_off_the_difference() {
+        let m = market(["ACTIVE"; 3], None);
+        let p = market_position(MARKET, Some(&m), &book());
+
+        // Runner 1: lay (5 - 0) / 2.0 = 2.5, leaving 2.5 win or lose.
+        let h1 = runner(&p, 1).hedge.clone().unwrap();
+        assert_eq!(
+            (h1.side, h1.price, h1.stake, h1.profit),
+            (OrderSide::Lay, 2.0, 2.5, 2.5)
+        );
+        // Runner 2: lay (15 + 5) / 5.0 = 4, leaving -1 win or lose.
+        let h2 = runner(&p, 2).hedge.clone().unwrap();
+        assert_eq!(
+            (h2.side, h2.price, h2.stake, h2.profit),
+            (OrderSide::Lay, 5.0, 4.0, -1.0)
+        );
+        assert!(runner(&p, 3).hedge.is_none());
+        assert_eq!(p.green_profit, Some(1.5));
+    }
+
+    #[test]
+    fn green_profit_needs_a_price_for_every_hedge() {
+        let m = market(["ACTIVE"; 3], None);
+        let orders = vec![orders(3, &[[6.0, 2.0]], &[], 0)];
+        let p = market_position(MARKET, Some(&m), &orders);
+        assert!(runner(&p, 3).hedge.is_none());
+        assert_eq!(p.green_profit, None);
+    }
+
+    #[test]
+    fn realised_once_every_position_has_a_result() {
+        let m = market(["LOSER", "WINNER", "LOSER"], None);
+        let p = market_position(MARKET, Some(&m), &book());
+        assert_eq!(runner(&p, 2).result.as_deref(), Some("WINNER"));
+        // Runner 1 loses (0), runner 2 wins (+15).
+        assert_eq!(p.realised, Some(15.0));
+
+        let partial = market(["LOSER", "ACTIVE", "LOSER"], None);
+        let p = market_position(MARKET, Some(&partial), &book());
+        assert_eq!(p.realised, None);
+    }
+
+    #[test]
+    fn removals_reduce_odds_matched_before_them() {
+        let removal = json!({
+            "id": 3,
+            "status": "REMOVED",
+            "removalDate": "2026-01-01T00:00:00.000Z",
+            "adjustmentFactor": 25.0,
+        });
+        let m = market(["ACTIVE"; 3], Some(removal));
+        let removed_at = m.removals().next().unwrap().removed_at_ms;
+
+        // Back 5 @ 4.0 before the removal settles at 4.0 * 0.75 = 3.0.
+        let before = vec![orders(2, &[[4.0, 5.0]], &[], removed_at - 1)];
+        let r = market_position(MARKET, Some(&m), &before).runners;
+        let r2 = r.iter().find(|r| r.selection_id == 2).unwrap();
+        assert_eq!((r2.back_avg_price, r2.win), (Some(3.0), 10.0));
+
+        let after = vec![orders(2, &[[4.0, 5.0]], &[], removed_at + 1)];
+        let r = market_position(MARKET, Some(&m), &after).runners;
+        let r2 = r.iter().find(|r| r.selection_id == 2).unwrap();
+        assert_eq!((r2.back_avg_price, r2.win), (Some(4.0), 15.0));
+    }
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::orders::cache::TrackedOrder;
+use crate::orders::place::OrderSide;
+use crate::stream::ocm::OrderStatus;
+use crate::ui_error::UiErrorPayload;
+
+/// One bet after a place / cancel / replace / update, from the order stream
+/// when it confirmed in time, otherwise from the Betting API report.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderResult {
+    pub bet_id: String,
+    pub market_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selection_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handicap: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<OrderSide>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<f64>,
+    /// Stream persistence code: L, P or MOC.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub persistence: Option<String>,
+    /// `executable` or `executionComplete`; unset if neither source said.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<OrderState>,
+    pub size_matched: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_price_matched: Option<f64>,
+    pub size_remaining: f64,
+    pub size_cancelled: f64,
+    pub size_lapsed: f64,
+    pub size_voided: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub customer_order_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub customer_strategy_ref: Option<String>,
+    /// The order stream reported the expected state before the timeout.
+    pub confirmed: bool,
+    /// Instruction-level Betfair error, e.g. BET_TAKEN_OR_LAPSED.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderState {
+    Executable,
+    ExecutionComplete,
+}
+
+impl OrderResult {
+    pub fn from_tracked(t: &TrackedOrder, confirmed: bool) -> Self {
+        let o = &t.order;
+        Self {
+            bet_id: o.id.clone(),
+            market_id: t.market_id.clone(),
+            selection_id: Some(t.selection_id),
+            handicap: Some(t.handicap),
+            side: Some(o.side.into()),
+            price: Some(o.p),
+            size: Some(o.s),
+            persistence: o.pt.clone(),
+            status: Some(match o.status {
+                OrderStatus::Executable => OrderState::Executable,
+                OrderStatus::ExecutionComplete => OrderState::ExecutionComplete,
+            }),
+            size_matched: o.sm,
+            average_price_matched: o.avp.filter(|_| o.sm > 0.0),
+            size_remaining: o.sr,
+            size_cancelled: o.sc,
+            size_lapsed: o.sl,
+            size_voided: o.sv,
+            customer_order_ref: o.rfo.clone(),
+            customer_strategy_ref: o.rfs.clone(),
+            confirmed,
+            error_code: None,
+        }
+    }
+
+    /// From a `placeOrders` instruction report (also the place half of a
+    /// replace).
+    pub fn from_place_report(market_id: &str, report: &Value) -> Self {
+        let num = |p: &str| report.pointer(p).and_then(|v| v.as_f64());
+        let text = |p: &str| {
+            report
+                .pointer(p)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        let size = num("/instruction/limitOrder/size");
+        let size_matched = num("/sizeMatched").unwrap_or(0.0);
+        let side = match text("/instruction/side").as_deref() {
+            Some("BACK") => Some(OrderSide::Back),
+            Some("LAY") => Some(OrderSide::Lay),
+            _ => None,
+        };
+        Self {
+            bet_id: text("/betId").unwrap_or_default(),
+            market_id: market_id.to_string(),
+            selection_id: report
+                .pointer("/instruction/selectionId")
+                .and_then(|v| v.as_u64()),
+            handicap: num("/instruction/handicap"),
+            side,
+            price: num("/instruction/limitOrder/price"),
+            size,
+            persistence: None,
+            status: match text("/orderStatus").as_deref() {
+                Some("EXECUTABLE") => Some(OrderState::Executable),
+                Some("EXECUTION_COMPLETE") => Some(OrderState::ExecutionComplete),
+                _ => None,
+            },
+            size_matched,
+            average_price_matched: num("/averagePriceMatched").filter(|_| size_matched > 0.0),
+            size_remaining: size.map_or(0.0, |s| (s - size_matched).max(0.0)),
+            size_cancelled: 0.0,
+            size_lapsed: 0.0,
+            size_voided: 0.0,
+            customer_order_ref: text("/instruction/customerOrderRef"),
+            customer_strategy_ref: None,
+            confirmed: false,
+            error_code: text("/errorCode"),
+        }
+    }
+
+    /// Bare result for a bet the stream hasn't reported, e.g. a cancel or
+    /// update that timed out.
+    pub fn unconfirmed(market_id: &str, bet_id: &str, error_code: Option<String>) -> Self {
+        Self {
+            bet_id: bet_id.to_string(),
+            market_id: market_id.to_string(),
+            selection_id: None,
+            handicap: None,
+            side: None,
+            price: None,
+            size: None,
+            persistence: None,
+            status: None,
+            size_matched: 0.0,
+            average_price_matched: None,
+            size_remaining: 0.0,
+            size_cancelled: 0.0,
+            size_lapsed: 0.0,
+            size_voided: 0.0,
+            customer_order_ref: None,
+            customer_strategy_ref: None,
+            confirmed: false,
+            error_code,
+        }
+    }
+}
+
+/// Whole-request failure of a Betting API order call (`status: FAILURE`)
+/// as an error naming Betfair's code; the per-instruction code wins when
+/// there is exactly one instruction.
+pub fn check_report(report: &Value) -> Result<&[Value], UiErrorPayload> {
+    let reports = report
+        .get("instructionReports")
+        .and_then(|v| v.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    if report.get("status").and_then(|v| v.as_str()) != Some("FAILURE") {
+        return Ok(reports);
+    }
+    let code = match reports {
+        [only] => only.get("errorCode"),
+        _ => None,
+    }
+    .or_else(|| report.get("errorCode"))
+    .and_then(|v| v.as_str())
+    .unwrap_or("UNKNOWN");
+    Err(UiErrorPayload::with_values(
+        "errors:orders.rejected",
+        serde_json::json!({ "code": code }),
+    ))
+}
@@ -0,0 +1,604 @@
+//! Client-side stop-loss, take-profit and trailing stops. Betfair has no stop
+//! orders, so each stop watches the hedge price of one selection and, once
+//! crossed, the selection is closed out with `hedge::plan`. Stops live in
+//! `stops.json` so they survive restarts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::warn;
+
+use crate::clock::now_ms;
+use crate::orders::place::OrderSide;
+use crate::orders::position::RunnerPosition;
+use crate::ticks::{Rounding, TickLadder};
+use crate::ui_error::UiErrorPayload;
+
+pub const STOPS_FILE_NAME: &str = "stops.json";
+
+/// Finished stops kept for the UI; older ones are dropped on save.
+const MAX_FINISHED: usize = 500;
+
+/// Trailing moves can come on every publish, so they are written at most this
+/// often; a crash loses the last few moves, never a new or fired stop.
+const TRAIL_SAVE_INTERVAL_MS: u64 = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StopKind {
+    StopLoss,
+    TakeProfit,
+    /// Stop-loss that follows the price `trail_ticks` behind its best level.
+    TrailingStop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StopStatus {
+    Active,
+    /// Fired; the hedge was sent (or is being sent).
+    Triggered,
+    /// Fired but the hedge could not be placed.
+    Failed,
+    Cancelled,
+    /// The position was closed, flipped or settled before the stop fired.
+    Closed,
+}
+
+impl StopStatus {
+    pub fn is_finished(self) -> bool {
+        self != Self::Active
+    }
+}
+
+/// What happened when a stop fired.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StopTrigger {
+    pub at_ms: u64,
+    /// Hedge price that crossed the trigger.
+    pub price: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bet_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<UiErrorPayload>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StopOrder {
+    /// Also the `customerRef` of the hedge and of its audit entries.
+    pub id: String,
+    pub market_id: String,
+    pub selection_id: u64,
+    pub handicap: f64,
+    pub kind: StopKind,
+    /// Side of the position being protected: a BACK position is closed by
+    /// laying, so it is watched against the best lay price, and vice versa.
+    pub position_side: OrderSide,
+    /// Fires once the hedge price reaches this; moves with a trailing stop.
+    pub trigger_price: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trail_ticks: Option<u32>,
+    /// Best hedge price seen so far (trailing stops).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor_price: Option<f64>,
+    pub min_stake: f64,
+    pub status: StopStatus,
+    pub created_at_ms: u64,
+    pub updated_at_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<StopTrigger>,
+}
+
+/// +1 when a rising price hurts the position (we backed), -1 when we laid.
+fn adverse(position_side: OrderSide) -> i64 {
+    match position_side {
+        OrderSide::Back => 1,
+        OrderSide::Lay => -1,
+    }
+}
+
+/// Side of an open position: BACK when the selection winning pays more than
+/// it losing. `None` once level or settled.
+pub fn position_side(runner: &RunnerPosition) -> Option<OrderSide> {
+    let diff = runner.win - runner.lose;
+    if runner.result.is_some() || diff.abs() < 0.005 {
+        return None;
+    }
+    Some(if diff > 0.0 {
+        OrderSide::Back
+    } else {
+        OrderSide::Lay
+    })
+}
+
+/// The price a stop is compared against: what the hedge would get now.
+pub fn hedge_price(
+    position_side: OrderSide,
+    best_back: Option<f64>,
+    best_lay: Option<f64>,
+) -> Option<f64> {
+    match position_side {
+        OrderSide::Back => best_lay,
+        OrderSide::Lay => best_back,
+    }
+}
+
+/// Trigger price `ticks` from `from`: against the position for stop-losses,
+/// in its favour for take-profits.
+pub fn trigger_from(
+    ladder: &TickLadder,
+    kind: StopKind,
+    position_side: OrderSide,
+    from: f64,
+    ticks: u32,
+) -> Option<f64> {
+    let toward = match kind {
+        StopKind::StopLoss | StopKind::TrailingStop => adverse(position_side),
+        StopKind::TakeProfit => -adverse(position_side),
+    };
+    let start = ladder.round(from, Rounding::Nearest)?;
+    ladder.offset(start, toward * i64::from(ticks))
+}
+
+/// Whether a hedge price at `price` has reached `trigger`.
+pub fn crossed(kind: StopKind, position_side: OrderSide, trigger: f64, price: f64) -> bool {
+    let dir = adverse(position_side) as f64;
+    match kind {
+        StopKind::StopLoss | StopKind::TrailingStop => dir * (price - trigger) >= 0.0,
+        StopKind::TakeProfit => dir * (trigger - price) >= 0.0,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Evaluation {
+    Unchanged,
+    /// A trailing stop followed the price.
+    Trailed,
+    Fire,
+}
+
+impl StopOrder {
+    /// Checks an active stop against the current hedge price.
+    pub fn evaluate(&mut self, price: f64, ladder: &TickLadder) -> Evaluation {
+        let dir = adverse(self.position_side) as f64;
+        let mut out = Evaluation::Unchanged;
+        if let (StopKind::TrailingStop, Some(ticks)) = (self.kind, self.trail_ticks) {
+            let better = self.anchor_price.is_none_or(|a| dir * (a - price) > 0.0);
+            if better {
+                if let Some(trigger) =
+                    trigger_from(ladder, self.kind, self.position_side, price, ticks)
+                {
+                    self.anchor_price = Some(price);
+                    self.trigger_price = trigger;
+                    out = Evaluation::Trailed;
+                }
+            }
+        }
+        if crossed(self.kind, self.position_side, self.trigger_price, price) {
+            Evaluation::Fire
+        } else {
+            out
+        }
+    }
+}
+
+/// Unique per call, well inside the 32-char `customerRef` limit.
+pub fn new_stop_id() -> String {
+    static SEQ: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "stop-{:x}-{:x}",
+        now_ms(),
+        SEQ.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+struct Book {
+    path: Option<PathBuf>,
+    stops: BTreeMap<String, StopOrder>,
+    saved_ms: u64,
+    load_error: Option<UiErrorPayload>,
+}
+
+/// Result of `stops_status`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StopBookStatus {
+    /// Stops are being saved to `stops.json`.
+    pub persisted: bool,
+    pub active: usize,
+    /// Why the saved stops couldn't be loaded, if they couldn't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_error: Option<UiErrorPayload>,
+}
+
+fn load(path: &Path) -> std::io::Result<Vec<StopOrder>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_slice(&std::fs::read(path)?)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Every stop, active or finished, mirrored to `stops.json` on each change
+/// (trailing moves at most every `TRAIL_SAVE_INTERVAL_MS`).
+///
+/// Like the audit log it only persists once `open` is called with the app
+/// data directory.
+pub struct StopBook {
+    inner: Mutex<Book>,
+}
+
+impl Default for StopBook {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StopBook {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Book {
+                path: None,
+                stops: BTreeMap::new(),
+                saved_ms: 0,
+                load_error: None,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Book> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Loads `<dir>/stops.json`, keeping any stops created before that. A
+    /// file that can't be read is moved to `stops.json.corrupt` and saving
+    /// starts afresh; `status` reports it.
+    pub fn open(&self, dir: &Path) -> std::io::Result<usize> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(STOPS_FILE_NAME);
+        let (loaded, load_error) = match load(&path) {
+            Ok(loaded) => (loaded, None),
+            Err(e) => {
+                let aside = path.with_extension("json.corrupt");
+                warn!(error = %e, path = %aside.display(), "stops file unreadable, moved aside");
+                if let Err(e) = std::fs::rename(&path, &aside) {
+                    warn!(error = %e, "moving the stops file aside failed");
+                }
+                let error = UiErrorPayload::with_values(
+                    "errors:stops.fileUnreadable",
+                    serde_json::json!({
+                        "path": aside.display().to_string(),
+                        "error": e.to_string(),
+                    }),
+                );
+                (Vec::new(), Some(error))
+            }
+        };
+        let mut book = self.lock();
+        book.load_error = load_error;
+        for stop in loaded {
+            book.stops.entry(stop.id.clone()).or_insert(stop);
+        }
+        book.path = Some(path);
+        let active = book
+            .stops
+            .values()
+            .filter(|s| !s.status.is_finished())
+            .count();
+        save(&mut book);
+        Ok(active)
+    }
+
+    pub fn status(&self) -> StopBookStatus {
+        let book = self.lock();
+        StopBookStatus {
+            persisted: book.path.is_some(),
+            active: book
+                .stops
+                .values()
+                .filter(|s| !s.status.is_finished())
+                .count(),
+            load_error: book.load_error.clone(),
+        }
+    }
+
+    pub fn list(&self, market_id: Option<&str>) -> Vec<StopOrder> {
+        self.lock()
+            .stops
+            .values()
+            .filter(|s| market_id.is_none_or(|m| s.market_id == m))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<StopOrder> {
+        self.lock().stops.get(id).cloned()
+    }
+
+    pub fn active(&self, market_id: &str) -> Vec<StopOrder> {
+        self.lock()
+            .stops
+            .values()
+            .filter(|s| s.market_id == market_id && !s.status.is_finished())
+            .cloned()
+            .collect()
+    }
+
+    pub fn insert(&self, stop: StopOrder) {
+        let mut book = self.lock();
+        book.stops.insert(stop.id.clone(), stop);
+        save(&mut book);
+    }
+
+    /// Applies `f` to an active stop and saves; `None` if it is unknown or
+    /// already finished, so a stop only ever fires once.
+    pub fn update_active(&self, id: &str, f: impl FnOnce(&mut StopOrder)) -> Option<StopOrder> {
+        self.update_if(id, |s| !s.status.is_finished(), f)
+    }
+
+    pub fn update(&self, id: &str, f: impl FnOnce(&mut StopOrder)) -> Option<StopOrder> {
+        self.update_if(id, |_| true, f)
+    }
+
+    /// `update_active` for a trailing move: saved once `TRAIL_SAVE_INTERVAL_MS`
+    /// has passed since the last save, otherwise with the next change.
+    pub fn trail(&self, id: &str, f: impl FnOnce(&mut StopOrder)) -> Option<StopOrder> {
+        let mut book = self.lock();
+        let stop = book.stops.get_mut(id).filter(|s| !s.status.is_finished())?;
+        f(stop);
+        let now = now_ms();
+        stop.updated_at_ms = now;
+        let out = stop.clone();
+        if now.saturating_sub(book.saved_ms) >= TRAIL_SAVE_INTERVAL_MS {
+            save(&mut book);
+        }
+        Some(out)
+    }
+
+    fn update_if(
+        &self,
+        id: &str,
+        pred: impl FnOnce(&StopOrder) -> bool,
+        f: impl FnOnce(&mut StopOrder),
+    ) -> Option<StopOrder> {
+        let mut book = self.lock();
+        let stop = book.stops.get_mut(id).filter(|s| pred(s))?;
+        f(stop);
+        stop.updated_at_ms = now_ms();
+        let out = stop.clone();
+        save(&mut book);
+        Some(out)
+    }
+}
+
+fn save(book: &mut Book) {
+    let finished = book
+        .stops
+        .values()
+        .filter(|s| s.status.is_finished())
+        .count();
+    if finished > MAX_FINISHED {
+        let mut old: Vec<(u64, String)> = book
+            .stops
+            .values()
+            .filter(|s| s.status.is_finished())
+            .map(|s| (s.updated_at_ms, s.id.clone()))
+            .collect();
+        old.sort();
+        for (_, id) in old.into_iter().take(finished - MAX_FINISHED) {
+            book.stops.remove(&id);
+        }
+    }
+    book.saved_ms = now_ms();
+    let Some(path) = &book.path else {
+        return;
+    };
+    let stops: Vec<&StopOrder> = book.stops.values().collect();
+    // Write then rename so a crash never leaves a half-written file.
+    let tmp = path.with_extension("json.tmp");
+    let result = serde_json::to_vec_pretty(&stops)
+        .map_err(std::io::Error::other)
+        .and_then(|bytes| std::fs::write(&tmp, bytes))
+        .and_then(|_| std::fs::rename(&tmp, path));
+    if let Err(e) = result {
+        warn!(error = %e, "saving stops failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ticks::MAX_ODDS;
+
+    fn stop(kind: StopKind, position_side: OrderSide, trigger_price: f64) -> StopOrder {
+        StopOrder {
+            id: new_stop_id(),
+            market_id: "1.1".into(),
+            selection_id: 1,
+            handicap: 0.0,
+            kind,
+            position_side,
+            trigger_price,
+            trail_ticks: None,
+            anchor_price: None,
+            min_stake: 1.0,
+            status: StopStatus::Active,
+            created_at_ms: 0,
+            updated_at_ms: 0,
+            trigger: None,
+        }
+    }
+
+    fn trailing(position_side: OrderSide, from: f64, ticks: u32) -> StopOrder {
+        let kind = StopKind::TrailingStop;
+        let trigger = trigger_from(&TickLadder::Classic, kind, position_side, from, ticks).unwrap();
+        StopOrder {
+            trail_ticks: Some(ticks),
+            anchor_price: Some(from),
+            ..stop(kind, position_side, trigger)
+        }
+    }
+
+    #[test]
+    fn back_positions_stop_out_as_the_lay_price_rises() {
+        use StopKind::*;
+        // Backed: closed by laying, so a higher lay price is a loss.
+        assert!(crossed(StopLoss, OrderSide::Back, 3.0, 3.0));
+        assert!(crossed(StopLoss, OrderSide::Back, 3.0, 3.1));
+        assert!(!crossed(StopLoss, OrderSide::Back, 3.0, 2.98));
+        assert!(crossed(TakeProfit, OrderSide::Back, 2.0, 2.0));
+        assert!(crossed(TakeProfit, OrderSide::Back, 2.0, 1.99));
+        assert!(!crossed(TakeProfit, OrderSide::Back, 2.0, 2.02));
+    }
+
+    #[test]
+    fn lay_positions_stop_out_as_the_back_price_falls() {
+        use StopKind::*;
+        assert!(crossed(StopLoss, OrderSide::Lay, 2.0, 2.0));
+        assert!(crossed(StopLoss, OrderSide::Lay, 2.0, 1.99));
+        assert!(!crossed(StopLoss, OrderSide::Lay, 2.0, 2.02));
+        assert!(crossed(TakeProfit, OrderSide::Lay, 3.0, 3.1));
+        assert!(!crossed(TakeProfit, OrderSide::Lay, 3.0, 2.98));
+
+        let mut s = stop(StopLoss, OrderSide::Lay, 2.0);
+        assert_eq!(
+            s.evaluate(2.02, &TickLadder::Classic),
+            Evaluation::Unchanged
+        );
+        assert_eq!(s.evaluate(1.98, &TickLadder::Classic), Evaluation::Fire);
+    }
+
+    #[test]
+    fn trailing_stop_follows_a_back_position_down_only() {
+        let ladder = TickLadder::Classic;
+        // 3 ticks above 2.00.
+        let mut s = trailing(OrderSide::Back, 2.0, 3);
+        assert_eq!(s.trigger_price, 2.06);
+
+        assert_eq!(s.evaluate(1.9, &ladder), Evaluation::Trailed);
+        assert_eq!((s.anchor_price, s.trigger_price), (Some(1.9), 1.93));
+        // A move against the position leaves the trigger where it is.
+        assert_eq!(s.evaluate(1.92, &ladder), Evaluation::Unchanged);
+        assert_eq!((s.anchor_price, s.trigger_price), (Some(1.9), 1.93));
+        assert_eq!(s.evaluate(1.93, &ladder), Evaluation::Fire);
+    }
+
+    #[test]
+    fn trailing_stop_follows_a_lay_position_up_only() {
+        let ladder = TickLadder::Classic;
+        let mut s = trailing(OrderSide::Lay, 3.0, 2);
+        assert_eq!(s.trigger_price, 2.96);
+
+        assert_eq!(s.evaluate(3.2, &ladder), Evaluation::Trailed);
+        // Two 0.05 ticks below 3.20.
+        assert_eq!((s.anchor_price, s.trigger_price), (Some(3.2), 3.1));
+        assert_eq!(s.evaluate(3.15, &ladder), Evaluation::Unchanged);
+        assert_eq!(s.trigger_price, 3.1);
+        assert_eq!(s.evaluate(3.1, &ladder), Evaluation::Fire);
+    }
+
+    #[test]
+    fn trigger_from_stays_on_the_ladder() {
+        use StopKind::*;
+        let l = TickLadder::Classic;
+        assert_eq!(
+            trigger_from(&l, StopLoss, OrderSide::Back, 1.99, 2),
+            Some(2.02)
+        );
+        assert_eq!(
+            trigger_from(&l, TakeProfit, OrderSide::Back, 2.02, 2),
+            Some(1.99)
+        );
+        assert_eq!(
+            trigger_from(&l, StopLoss, OrderSide::Lay, 110.0, 1),
+            Some(100.0)
+        );
+        // Off-ladder starts snap to the nearest tick first.
+        assert_eq!(
+            trigger_from(&l, StopLoss, OrderSide::Back, 2.01, 1),
+            Some(2.02)
+        );
+        // Nothing beyond either end.
+        assert_eq!(
+            trigger_from(&l, StopLoss, OrderSide::Back, MAX_ODDS, 1),
+            None
+        );
+        assert_eq!(trigger_from(&l, TakeProfit, OrderSide::Back, 1.02, 2), None);
+        assert_eq!(
+            trigger_from(&l, StopLoss, OrderSide::Lay, 1.02, 1),
+            Some(1.01)
+        );
+        assert_eq!(
+            trigger_from(&l, StopLoss, OrderSide::Back, f64::NAN, 1),
+            None
+        );
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stops-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn stop_book_round_trips_through_the_file() {
+        let dir = temp_dir("round-trip");
+        let book = StopBook::new();
+        assert_eq!(book.open(&dir).unwrap(), 0);
+        let active = stop(StopKind::StopLoss, OrderSide::Back, 3.0);
+        let done = stop(StopKind::TakeProfit, OrderSide::Back, 2.0);
+        book.insert(active.clone());
+        book.insert(done.clone());
+        book.update_active(&done.id, |s| s.status = StopStatus::Cancelled)
+            .unwrap();
+        // Finished stops only change through `update`.
+        assert!(book
+            .update_active(&done.id, |s| s.trigger_price = 9.0)
+            .is_none());
+
+        let reopened = StopBook::new();
+        assert_eq!(reopened.open(&dir).unwrap(), 1);
+        assert_eq!(reopened.list(None).len(), 2);
+        let loaded = reopened.get(&active.id).unwrap();
+        assert_eq!(
+            (loaded.kind, loaded.trigger_price),
+            (StopKind::StopLoss, 3.0)
+        );
+        assert_eq!(
+            reopened.get(&done.id).unwrap().status,
+            StopStatus::Cancelled
+        );
+        assert_eq!(reopened.active("1.1").len(), 1);
+        assert!(reopened.status().load_error.is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unreadable_stops_file_is_moved_aside() {
+        let dir = temp_dir("corrupt");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(STOPS_FILE_NAME), "[{\"id\": \"stop-1\", ").unwrap();
+
+        let book = StopBook::new();
+        assert_eq!(book.open(&dir).unwrap(), 0);
+        let status = book.status();
+        assert!(status.persisted);
+        assert_eq!(
+            status.load_error.map(|e| e.key),
+            Some("errors:stops.fileUnreadable".to_string())
+        );
+        assert!(dir.join("stops.json.corrupt").exists());
+
+        // New stops are still saved.
+        let s = stop(StopKind::StopLoss, OrderSide::Back, 3.0);
+        book.insert(s.clone());
+        let reopened = StopBook::new();
+        assert_eq!(reopened.open(&dir).unwrap(), 1);
+        assert!(reopened.get(&s.id).is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,229 @@
+//! Keeps credentials and account identifiers out of logs, errors and events.
+//!
+//! Three layers, so one slip doesn't leak a secret:
+//! - `secret()` masks a value at the call site (`app_key = %redact::secret(k)`);
+//! - the tracing field formatter masks any field whose *name* looks sensitive;
+//! - `RedactingMakeWriter` scrubs every *registered* secret value (AppKey,
+//!   session token, username) from the final log text, whatever the field name.
+//!
+//! Redaction is on by default. Debug builds can opt out with
+//! `BETFAIR_LOG_SECRETS=1` when chasing an auth problem locally.
+
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{OnceLock, RwLock};
+use tracing::field::Field;
+use tracing_subscriber::field::MakeExt;
+use tracing_subscriber::fmt::format::{debug_fn, Writer};
+use tracing_subscriber::fmt::{FormatFields, MakeWriter};
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Secrets shorter than this are not scrubbed from text (too many false hits).
+const MIN_SECRET_LEN: usize = 4;
+
+/// Normalized (lowercase, no `_`/`-`) field and JSON key names treated as secret.
+const SECRET_NAMES: [&str; 10] = [
+    "appkey",
+    "xapplication",
+    "session",
+    "sessiontoken",
+    "xauthentication",
+    "token",
+    "password",
+    "username",
+    "accountid",
+    "accountids",
+];
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static SECRETS: OnceLock<RwLock<Vec<String>>> = OnceLock::new();
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Turns redaction on or off. Release builds ignore attempts to disable it.
+pub fn set_enabled(on: bool) {
+    ENABLED.store(on || !cfg!(debug_assertions), Ordering::Relaxed);
+}
+
+/// Applies the `BETFAIR_LOG_SECRETS=1` debug opt-in.
+pub fn init_from_env() {
+    let opt_out = std::env::var("BETFAIR_LOG_SECRETS").is_ok_and(|v| v.trim() == "1");
+    set_enabled(!opt_out);
+}
+
+fn secrets() -> &'static RwLock<Vec<String>> {
+    SECRETS.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+/// Remembers a secret value so it is scrubbed from any log text.
+pub fn register_secret(value: &str) {
+    let value = value.trim();
+    if value.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut list = secrets().write().unwrap_or_else(|e| e.into_inner());
+    if !list.iter().any(|s| s == value) {
+        list.push(value.to_string());
+        // Longest first so a secret containing another is replaced whole.
+        list.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+pub fn forget_secret(value: &str) {
+    let value = value.trim();
+    secrets()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|s| s != value);
+}
+
+pub fn is_secret_name(name: &str) -> bool {
+    let normalized: String = name
+        .chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    SECRET_NAMES.contains(&normalized.as_str())
+}
+
+/// Replaces every registered secret in `text`.
+pub fn scrub_text(text: &str) -> String {
+    if !enabled() {
+        return text.to_string();
+    }
+    scrub_registered(text)
+}
+
+/// Like `scrub_text` but ignores the opt-in; for anything exported.
+pub fn mask_text(text: &str) -> String {
+    scrub_registered(text)
+}
+
+fn scrub_registered(text: &str) -> String {
+    let list = secrets().read().unwrap_or_else(|e| e.into_inner());
+    let mut out = text.to_string();
+    for s in list.iter() {
+        if out.contains(s.as_str()) {
+            out = out.replace(s.as_str(), REDACTED);
+        }
+    }
+    out
+}
+
+/// Masks secret-named keys recursively, for payloads that leave Rust
+/// (errors, events). Honours the debug opt-in.
+pub fn redact_json(value: serde_json::Value) -> serde_json::Value {
+    if !enabled() {
+        return value;
+    }
+    mask_json(value)
+}
+
+/// Like `redact_json` but ignores the opt-in; for anything persisted.
+pub fn mask_json(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => serde_json::Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    if is_secret_name(&k) {
+                        (k, serde_json::Value::String(REDACTED.to_string()))
+                    } else {
+                        (k, mask_json(v))
+                    }
+                })
+                .collect(),
+        ),
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(mask_json).collect())
+        }
+        serde_json::Value::String(s) => serde_json::Value::String(scrub_registered(&s)),
+        other => other,
+    }
+}
+
+/// Display wrapper for logging a sensitive value: `%redact::secret(value)`.
+pub fn secret(value: &str) -> Secret<'_> {
+    Secret(value)
+}
+
+pub struct Secret<'a>(&'a str);
+
+impl fmt::Display for Secret<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if enabled() {
+            f.write_str(REDACTED)
+        } else {
+            f.write_str(self.0)
+        }
+    }
+}
+
+impl fmt::Debug for Secret<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+fn format_field(w: &mut Writer<'_>, field: &Field, value: &dyn fmt::Debug) -> fmt::Result {
+    let name = field.name();
+    if name == "message" {
+        write!(w, "{value:?}")
+    } else if enabled() && is_secret_name(name) {
+        write!(w, "{name}={REDACTED}")
+    } else {
+        write!(w, "{name}={value:?}")
+    }
+}
+
+/// Field formatter for `tracing_subscriber::fmt` that masks secret-named fields.
+pub fn field_formatter() -> impl for<'w> FormatFields<'w> + Send + Sync + 'static {
+    debug_fn(format_field as fn(&mut Writer<'_>, &Field, &dyn fmt::Debug) -> fmt::Result)
+        .delimited(" ")
+}
+
+/// Wraps a `MakeWriter` so every formatted line passes through `scrub_text`.
+#[derive(Clone)]
+pub struct RedactingMakeWriter<M> {
+    inner: M,
+}
+
+impl<M> RedactingMakeWriter<M> {
+    pub fn new(inner: M) -> Self {
+        Self { inner }
+    }
+}
+
+impl<'a, M> MakeWriter<'a> for RedactingMakeWriter<M>
+where
+    M: MakeWriter<'a>,
+{
+    type Writer = RedactingWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: self.inner.make_writer(),
+        }
+    }
+}
+
+pub struct RedactingWriter<W> {
+    inner: W,
+}
+
+impl<W: io::Write> io::Write for RedactingWriter<W> {
+    // The fmt layer writes each event with a single `write_all`, so a secret
+    // is never split across calls.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.inner.write_all(scrub_text(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::clock::now_ms;
+use crate::ui_error::UiErrorPayload;
+
+pub const EVENT_KILL_SWITCH: &str = "betfair_kill_switch";
+
+/// Global shortcut that engages the kill switch (registered in `main.rs`).
+pub const KILL_SWITCH_SHORTCUT: &str = "CommandOrControl+Shift+K";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KillSwitchReason {
+    Manual,
+    Shortcut,
+    StreamDisconnected,
+    SessionExpired,
+    MarketInPlay,
+}
+
+/// Conditions that engage the kill switch without user action.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KillSwitchTriggers {
+    /// Stream dropped unexpectedly while unmatched orders are open.
+    pub on_stream_disconnect: bool,
+    /// Betfair rejected the session token (expired or invalidated).
+    pub on_session_expiry: bool,
+    /// A market with our unmatched orders went in-play. Off by default.
+    #[serde(default)]
+    pub on_in_play: bool,
+}
+
+impl Default for KillSwitchTriggers {
+    fn default() -> Self {
+        Self {
+            on_stream_disconnect: true,
+            on_session_expiry: true,
+            on_in_play: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KillSwitchStatus {
+    pub locked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<KillSwitchReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engaged_at_ms: Option<u64>,
+    /// Number of instruction reports returned by the last `cancelOrders`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cancelled: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cancel_error: Option<UiErrorPayload>,
+    pub triggers: KillSwitchTriggers,
+}
+
+/// Gateway lock: once engaged, placement methods are refused until re-armed.
+pub struct KillSwitch {
+    status: RwLock<KillSwitchStatus>,
+}
+
+impl Default for KillSwitch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KillSwitch {
+    pub fn new() -> Self {
+        Self {
+            status: RwLock::new(KillSwitchStatus {
+                locked: false,
+                reason: None,
+                engaged_at_ms: None,
+                cancelled: None,
+                cancel_error: None,
+                triggers: KillSwitchTriggers::default(),
+            }),
+        }
+    }
+
+    pub async fn status(&self) -> KillSwitchStatus {
+        self.status.read().await.clone()
+    }
+
+    pub async fn is_locked(&self) -> bool {
+        self.status.read().await.locked
+    }
+
+    pub async fn triggers(&self) -> KillSwitchTriggers {
+        self.status.read().await.triggers
+    }
+
+    pub async fn set_triggers(&self, triggers: KillSwitchTriggers) {
+        self.status.write().await.triggers = triggers;
+    }
+
+    /// Locks the gateway. Returns `false` if it was already locked (the
+    /// original reason and timestamp are kept).
+    pub async fn engage(&self, reason: KillSwitchReason) -> bool {
+        let mut status = self.status.write().await;
+        if status.locked {
+            return false;
+        }
+        status.locked = true;
+        status.reason = Some(reason);
+        status.engaged_at_ms = Some(now_ms());
+        status.cancelled = None;
+        status.cancel_error = None;
+        true
+    }
+
+    pub async fn record_cancel(&self, outcome: Result<usize, UiErrorPayload>) {
+        let mut status = self.status.write().await;
+        match outcome {
+            Ok(n) => {
+                status.cancelled = Some(n);
+                status.cancel_error = None;
+            }
+            Err(e) => {
+                status.cancelled = None;
+                status.cancel_error = Some(e);
+            }
+        }
+    }
+
+    pub async fn rearm(&self) {
+        let mut status = self.status.write().await;
+        status.locked = false;
+        status.reason = None;
+        status.engaged_at_ms = None;
+        status.cancelled = None;
+        status.cancel_error = None;
+    }
+}
+
+/// Methods that can create new exposure and are refused while locked.
+pub fn is_placement_method(service: &str, method: &str) -> bool {
+    service == "betting" && matches!(method, "placeOrders" | "replaceOrders")
+}
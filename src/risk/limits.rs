@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::market::bsp::bsp_liability;
+use crate::stream::ocm::{Side, UnmatchedOrder};
+use crate::ticks::TickLadder;
+use crate::ui_error::UiErrorPayload;
+
+/// Liability caps checked before placement; `None` means no cap.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskLimits {
+    /// Per instruction.
+    pub max_order_liability: Option<f64>,
+    /// Open orders on a market plus the new ones.
+    pub max_market_liability: Option<f64>,
+}
+
+impl RiskLimits {
+    pub fn validate(&self) -> Result<(), UiErrorPayload> {
+        for limit in [self.max_order_liability, self.max_market_liability]
+            .into_iter()
+            .flatten()
+        {
+            if !(limit.is_finite() && limit > 0.0) {
+                return Err(UiErrorPayload::with_values(
+                    "errors:validation.invalidLiability",
+                    json!({ "liability": limit }),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn lay_liability(ladder: &TickLadder, price: f64, size: f64) -> f64 {
+    (ladder.odds(price) - 1.0).max(0.0) * size
+}
+
+/// Worst-case loss of a `placeOrders` instruction. BSP orders state their
+/// liability directly; limit lays lose `(odds - 1) * size`.
+pub fn instruction_liability(ins: &serde_json::Value, ladder: &TickLadder) -> Option<f64> {
+    let num = |p: &str| ins.pointer(p).and_then(|v| v.as_f64());
+    if let Some(liability) = num("/limitOnCloseOrder/liability") {
+        return Some(liability);
+    }
+    if let Some(liability) = num("/marketOnCloseOrder/liability") {
+        return Some(liability);
+    }
+    let (size, price) = (num("/limitOrder/size")?, num("/limitOrder/price")?);
+    match ins.get("side").and_then(|v| v.as_str()) {
+        Some("LAY") => Some(lay_liability(ladder, price, size)),
+        _ => Some(size),
+    }
+}
+
+/// Remaining worst-case loss of an open order.
+pub fn order_liability(order: &UnmatchedOrder, ladder: &TickLadder) -> f64 {
+    if let Some(liability) = bsp_liability(order) {
+        return liability;
+    }
+    match order.side {
+        Side::Back => order.sr,
+        Side::Lay => lay_liability(ladder, order.p, order.sr),
+    }
+}
+
+/// Rejects `placeOrders` that break a limit. `open_liability` is what the
+/// market's unmatched orders already risk.
+pub fn check_liability(
+    limits: &RiskLimits,
+    params: &serde_json::Value,
+    ladder: &TickLadder,
+    open_liability: f64,
+) -> Result<(), UiErrorPayload> {
+    let instructions = params
+        .get("instructions")
+        .and_then(|v| v.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let mut total = open_liability;
+    for ins in instructions {
+        let liability = instruction_liability(ins, ladder).unwrap_or(0.0);
+        if let Some(max) = limits.max_order_liability {
+            if liability > max {
+                return Err(UiErrorPayload::with_values(
+                    "errors:risk.orderLiabilityExceeded",
+                    json!({ "liability": liability, "max": max }),
+                ));
+            }
+        }
+        total += liability;
+    }
+    if let Some(max) = limits.max_market_liability {
+        if total > max {
+            return Err(UiErrorPayload::with_values(
+                "errors:risk.marketLiabilityExceeded",
+                json!({ "liability": total, "max": max }),
+            ));
+        }
+    }
+    Ok(())
+}
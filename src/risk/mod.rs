@@ -0,0 +1,3 @@
+pub mod kill_switch;
+pub mod limits;
+pub mod prices;
@@ -0,0 +1,34 @@
+use crate::ticks::TickLadder;
+use crate::ui_error::UiErrorPayload;
+
+/// Rejects `placeOrders` / `replaceOrders` whose limit prices are not on the
+/// market's tick ladder, before they reach Betfair.
+pub fn check_order_prices(
+    method: &str,
+    params: &serde_json::Value,
+    ladder_for: impl Fn(&str) -> TickLadder,
+) -> Result<(), UiErrorPayload> {
+    let Some(market_id) = params.get("marketId").and_then(|v| v.as_str()) else {
+        return Ok(());
+    };
+    let ladder = ladder_for(market_id);
+    let instructions = params
+        .get("instructions")
+        .and_then(|v| v.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for ins in instructions {
+        let prices = match method {
+            "placeOrders" => vec![
+                ins.pointer("/limitOrder/price"),
+                ins.pointer("/limitOnCloseOrder/price"),
+            ],
+            "replaceOrders" => vec![ins.get("newPrice")],
+            _ => Vec::new(),
+        };
+        for price in prices.into_iter().flatten().filter_map(|v| v.as_f64()) {
+            ladder.validate(price)?;
+        }
+    }
+    Ok(())
+}
@@ -1,9 +1,27 @@
 use reqwest::Client;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 
-use crate::stream::StreamConnection;
+use crate::audit::AuditLog;
+use crate::logging::Logging;
+use crate::market::cache::MarketCache;
+use crate::market::catalogue::CatalogueStore;
+use crate::market::lifecycle::LifecycleEvent;
+use crate::market::navigation::NavigationTree;
+use crate::market::replay::Replay;
+use crate::orders::cache::OrderCache;
+use crate::orders::stops::StopBook;
+use crate::redact;
+use crate::risk::kill_switch::KillSwitch;
+use crate::risk::limits::RiskLimits;
+use crate::strategy::runtime::StrategyRuntime;
+use crate::stream::history::StreamHistory;
+use crate::stream::subscriptions::SubscriptionManager;
+use crate::stream::{StreamPool, StreamRole};
+
+const LIFECYCLE_CAPACITY: usize = 256;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -13,19 +31,60 @@ pub struct AppState {
     pub allowlist_betting: Arc<HashSet<String>>,
     pub allowlist_account: Arc<HashSet<String>>,
     pub allowlist_heartbeat: Arc<HashSet<String>>,
-    pub stream: Arc<RwLock<Option<StreamConnection>>>,
+    pub streams: Arc<RwLock<StreamPool>>,
+    /// Roles with a connect in flight, so concurrent callers don't open a
+    /// second socket for one slot.
+    pub streams_connecting: Arc<std::sync::Mutex<BTreeSet<StreamRole>>>,
+    pub subscriptions: Arc<RwLock<SubscriptionManager>>,
+    pub stream_history: Arc<StreamHistory>,
+    /// Forward every inbound frame as `EVENT_STREAM_LINE` (debug only).
+    pub stream_raw_lines: Arc<AtomicBool>,
+    pub markets: Arc<MarketCache>,
+    /// Names, events and runners of markets, for local search.
+    pub catalogue: Arc<CatalogueStore>,
+    /// Event types → competitions → events → markets, fetched on expansion.
+    pub navigation: Arc<NavigationTree>,
+    /// In-process feed of market lifecycle events (kill switch, strategies).
+    pub lifecycle: broadcast::Sender<LifecycleEvent>,
+    /// Historical file playback, kept apart from live markets.
+    pub replay: Arc<Replay>,
+    pub orders: Arc<OrderCache>,
+    pub stops: Arc<StopBook>,
+    pub strategies: Arc<StrategyRuntime>,
+    pub kill_switch: Arc<KillSwitch>,
+    pub risk_limits: Arc<RwLock<RiskLimits>>,
+    pub audit: Arc<AuditLog>,
+    pub logging: Logging,
 }
 
 impl AppState {
-    pub fn new(http: Client) -> Self {
+    pub fn new(http: Client, logging: Logging) -> Self {
+        let app_key = resolve_app_key();
+        redact::register_secret(&app_key);
         Self {
-            app_key: Arc::new(resolve_app_key()),
+            app_key: Arc::new(app_key),
             session_token: Arc::new(RwLock::new(None)),
             http,
             allowlist_betting: Arc::new(build_allowlist_betting()),
             allowlist_account: Arc::new(build_allowlist_account()),
             allowlist_heartbeat: Arc::new(build_allowlist_heartbeat()),
-            stream: Arc::new(RwLock::new(None)),
+            streams: Arc::new(RwLock::new(StreamPool::new())),
+            streams_connecting: Arc::new(std::sync::Mutex::new(BTreeSet::new())),
+            subscriptions: Arc::new(RwLock::new(SubscriptionManager::new())),
+            stream_history: Arc::new(StreamHistory::new()),
+            stream_raw_lines: Arc::new(AtomicBool::new(false)),
+            markets: Arc::new(MarketCache::new()),
+            catalogue: Arc::new(CatalogueStore::new()),
+            navigation: Arc::new(NavigationTree::new()),
+            lifecycle: broadcast::channel(LIFECYCLE_CAPACITY).0,
+            replay: Arc::new(Replay::new()),
+            orders: Arc::new(OrderCache::new()),
+            stops: Arc::new(StopBook::new()),
+            strategies: Arc::new(StrategyRuntime::new()),
+            kill_switch: Arc::new(KillSwitch::new()),
+            risk_limits: Arc::new(RwLock::new(RiskLimits::default())),
+            audit: Arc::new(AuditLog::new()),
+            logging,
         }
     }
 }
@@ -0,0 +1,115 @@
+//! Strategies shipped with the app. Each is registered disabled; add your own
+//! to `BUILTINS` below. The backtester builds them from the same list.
+
+use serde_json::json;
+use std::collections::BTreeSet;
+
+use crate::market::cache::MarketState;
+use crate::market::lifecycle::{LifecycleEvent, LifecycleKind};
+use crate::orders::place::{OrderSide, Persistence};
+use crate::strategy::runtime::StrategyRuntime;
+use crate::strategy::{OrderIntent, Strategy, StrategyContext};
+use crate::ui_error::UiErrorPayload;
+
+pub struct Builtin {
+    pub id: &'static str,
+    pub strategy_ref: &'static str,
+    pub description: &'static str,
+    pub create: fn() -> Box<dyn Strategy>,
+}
+
+pub const BUILTINS: &[Builtin] = &[Builtin {
+    id: "favourite-back",
+    strategy_ref: "bsa-favback",
+    description: "Backs the pre-off favourite once per market at up to 3.0 for 2.00; cancels what is unmatched at the off.",
+    create: || Box::new(FavouriteBack::new(2.0, 3.0)),
+}];
+
+pub fn find(id: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.id == id)
+}
+
+pub fn register(runtime: &StrategyRuntime) -> Result<(), UiErrorPayload> {
+    for b in BUILTINS {
+        runtime.register(b.id, b.strategy_ref, b.description, (b.create)())?;
+    }
+    Ok(())
+}
+
+/// Reference strategy: one back bet on the favourite of every open, pre-off
+/// market, at the best available price if it is no longer than `max_price`.
+pub struct FavouriteBack {
+    stake: f64,
+    max_price: f64,
+    /// Markets already bet on.
+    placed: BTreeSet<String>,
+}
+
+impl FavouriteBack {
+    pub fn new(stake: f64, max_price: f64) -> Self {
+        Self {
+            stake,
+            max_price,
+            placed: BTreeSet::new(),
+        }
+    }
+}
+
+fn open_pre_off(market: &MarketState) -> bool {
+    let Some(def) = &market.market_definition else {
+        return false;
+    };
+    def.get("status").and_then(|v| v.as_str()) == Some("OPEN")
+        && def.get("inPlay").and_then(|v| v.as_bool()) != Some(true)
+}
+
+impl Strategy for FavouriteBack {
+    fn on_market(&mut self, ctx: &StrategyContext, market: &MarketState) -> Vec<OrderIntent> {
+        if self.placed.contains(&market.market_id) || ctx.position.is_some() {
+            return Vec::new();
+        }
+        if !open_pre_off(market) {
+            return Vec::new();
+        }
+        let favourite = market
+            .runners
+            .values()
+            .filter(|r| r.removal.is_none())
+            .filter_map(|r| Some((r, r.best_back()?)))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        let Some((runner, price)) = favourite else {
+            return Vec::new();
+        };
+        if price > self.max_price {
+            return Vec::new();
+        }
+        self.placed.insert(market.market_id.clone());
+        vec![OrderIntent::Place {
+            market_id: market.market_id.clone(),
+            selection_id: runner.selection_id,
+            handicap: runner.handicap,
+            side: OrderSide::Back,
+            price,
+            size: self.stake,
+            persistence: Persistence::Lapse,
+        }]
+    }
+
+    fn on_lifecycle(&mut self, _ctx: &StrategyContext, event: &LifecycleEvent) -> Vec<OrderIntent> {
+        if event.kind != LifecycleKind::InPlay || !self.placed.contains(&event.market_id) {
+            return Vec::new();
+        }
+        vec![OrderIntent::Cancel {
+            market_id: event.market_id.clone(),
+            bet_id: None,
+        }]
+    }
+
+    fn state(&self) -> serde_json::Value {
+        json!({
+            "stake": self.stake,
+            "maxPrice": self.max_price,
+            "placed": self.placed,
+        })
+    }
+}
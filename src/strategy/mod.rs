@@ -0,0 +1,105 @@
+//! Trading strategies that run inside the app. A strategy sees market
+//! snapshots, its own orders and lifecycle events, and answers with order
+//! intents. Intents go through the same risk checks and order commands as the
+//! UI, tagged with the strategy's `customerStrategyRef`.
+
+use serde::{Deserialize, Serialize};
+
+use crate::market::cache::MarketState;
+use crate::market::lifecycle::LifecycleEvent;
+use crate::orders::cache::RunnerOrdersSnapshot;
+use crate::orders::place::{OrderSide, Persistence};
+use crate::orders::position::MarketPosition;
+
+pub mod builtin;
+pub mod runtime;
+
+/// A strategy started, stopped or sent intents; payload `runtime::StrategyInfo`.
+pub const EVENT_STRATEGY: &str = "betfair_strategy";
+
+pub struct StrategyContext<'a> {
+    pub now_ms: u64,
+    pub strategy_ref: &'a str,
+    /// The strategy's own position on the market, from orders placed under
+    /// its `strategy_ref` only.
+    pub position: Option<&'a MarketPosition>,
+}
+
+/// What a strategy wants done. Cancels and replaces only touch the
+/// strategy's own bets.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum OrderIntent {
+    #[serde(rename_all = "camelCase")]
+    Place {
+        market_id: String,
+        selection_id: u64,
+        handicap: f64,
+        side: OrderSide,
+        price: f64,
+        size: f64,
+        persistence: Persistence,
+    },
+    /// One bet, or every unmatched bet of the strategy on the market.
+    #[serde(rename_all = "camelCase")]
+    Cancel {
+        market_id: String,
+        bet_id: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Replace {
+        market_id: String,
+        bet_id: String,
+        new_price: f64,
+    },
+}
+
+impl OrderIntent {
+    pub fn market_id(&self) -> &str {
+        match self {
+            Self::Place { market_id, .. }
+            | Self::Cancel { market_id, .. }
+            | Self::Replace { market_id, .. } => market_id,
+        }
+    }
+}
+
+/// Callbacks run on the market publisher with the runtime locked, so keep
+/// them quick and never block. A strategy that panics is stopped.
+pub trait Strategy: Send {
+    /// Markets to get events for; every market by default.
+    fn wants_market(&self, _market_id: &str) -> bool {
+        true
+    }
+
+    fn on_start(&mut self, _ctx: &StrategyContext) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// A market update was published.
+    fn on_market(&mut self, ctx: &StrategyContext, market: &MarketState) -> Vec<OrderIntent>;
+
+    /// The order stream changed the strategy's orders on a market.
+    fn on_orders(
+        &mut self,
+        _ctx: &StrategyContext,
+        _orders: &[RunnerOrdersSnapshot],
+    ) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    fn on_lifecycle(
+        &mut self,
+        _ctx: &StrategyContext,
+        _event: &LifecycleEvent,
+    ) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    fn on_stop(&mut self) {}
+
+    /// Shown by `strategies_inspect`.
+    fn state(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+}
@@ -0,0 +1,414 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::Mutex;
+use tracing::{error, info};
+
+use crate::clock::now_ms;
+use crate::orders::place::{validate_ref, MAX_STRATEGY_REF};
+use crate::strategy::{OrderIntent, Strategy, StrategyContext};
+use crate::ui_error::UiErrorPayload;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StopReason {
+    Manual,
+    Disabled,
+    KillSwitch,
+    Panicked,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrategyInfo {
+    pub id: String,
+    /// `customerStrategyRef` of every order the strategy sends.
+    pub strategy_ref: String,
+    pub description: String,
+    /// Disabled strategies can't be started.
+    pub enabled: bool,
+    pub running: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped_reason: Option<StopReason>,
+    pub intents_sent: u64,
+    pub intents_failed: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<UiErrorPayload>,
+}
+
+/// Intents one callback returned, to be sent in order.
+#[derive(Debug)]
+pub struct IntentBatch {
+    pub id: String,
+    pub strategy_ref: String,
+    pub intents: Vec<OrderIntent>,
+}
+
+struct Slot {
+    strategy: Box<dyn Strategy>,
+    info: StrategyInfo,
+}
+
+impl Slot {
+    /// Runs a callback; a panic stops the strategy instead of the app.
+    fn guarded(
+        &mut self,
+        f: impl FnOnce(&mut dyn Strategy) -> Vec<OrderIntent>,
+    ) -> Vec<OrderIntent> {
+        let strategy = &mut self.strategy;
+        match catch_unwind(AssertUnwindSafe(|| f(strategy.as_mut()))) {
+            Ok(intents) => intents,
+            Err(_) => {
+                error!(id = %self.info.id, "strategy panicked; stopped");
+                self.info.running = false;
+                self.info.stopped_reason = Some(StopReason::Panicked);
+                self.info.last_error = Some(UiErrorPayload::key("errors:strategies.panicked"));
+                Vec::new()
+            }
+        }
+    }
+
+    fn stop(&mut self, reason: StopReason) {
+        if !self.info.running {
+            return;
+        }
+        info!(id = %self.info.id, ?reason, "strategy stopped");
+        self.guarded(|s| {
+            s.on_stop();
+            Vec::new()
+        });
+        self.info.running = false;
+        self.info.stopped_reason.get_or_insert(reason);
+    }
+
+    fn batch(&self, intents: Vec<OrderIntent>) -> Option<IntentBatch> {
+        (!intents.is_empty()).then(|| IntentBatch {
+            id: self.info.id.clone(),
+            strategy_ref: self.info.strategy_ref.clone(),
+            intents,
+        })
+    }
+}
+
+/// Registered strategies and whether they run. Strategies are registered at
+/// startup (see `builtin`); the UI can only enable, start and stop them.
+pub struct StrategyRuntime {
+    slots: Mutex<BTreeMap<String, Slot>>,
+}
+
+impl Default for StrategyRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StrategyRuntime {
+    pub fn new() -> Self {
+        Self {
+            slots: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Slot>> {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds a strategy, disabled. Ids and refs must be unique so the order
+    /// stream can tell strategies apart.
+    pub fn register(
+        &self,
+        id: &str,
+        strategy_ref: &str,
+        description: &str,
+        strategy: Box<dyn Strategy>,
+    ) -> Result<(), UiErrorPayload> {
+        validate_ref(strategy_ref, MAX_STRATEGY_REF)?;
+        let mut slots = self.lock();
+        if slots
+            .values()
+            .any(|s| s.info.id == id || s.info.strategy_ref == strategy_ref)
+        {
+            return Err(UiErrorPayload::with_values(
+                "errors:strategies.duplicate",
+                serde_json::json!({ "id": id, "strategyRef": strategy_ref }),
+            ));
+        }
+        slots.insert(
+            id.to_string(),
+            Slot {
+                strategy,
+                info: StrategyInfo {
+                    id: id.to_string(),
+                    strategy_ref: strategy_ref.to_string(),
+                    description: description.to_string(),
+                    enabled: false,
+                    running: false,
+                    started_at_ms: None,
+                    stopped_reason: None,
+                    intents_sent: 0,
+                    intents_failed: 0,
+                    last_error: None,
+                },
+            },
+        );
+        Ok(())
+    }
+
+    pub fn list(&self) -> Vec<StrategyInfo> {
+        self.lock().values().map(|s| s.info.clone()).collect()
+    }
+
+    fn with_slot<T>(
+        &self,
+        id: &str,
+        f: impl FnOnce(&mut Slot) -> Result<T, UiErrorPayload>,
+    ) -> Result<T, UiErrorPayload> {
+        let mut slots = self.lock();
+        let Some(slot) = slots.get_mut(id) else {
+            return Err(UiErrorPayload::with_values(
+                "errors:strategies.notFound",
+                serde_json::json!({ "id": id }),
+            ));
+        };
+        f(slot)
+    }
+
+    /// Info plus the strategy's own view of its state.
+    pub fn inspect(&self, id: &str) -> Result<(StrategyInfo, serde_json::Value), UiErrorPayload> {
+        self.with_slot(id, |slot| {
+            let state = catch_unwind(AssertUnwindSafe(|| slot.strategy.state()))
+                .unwrap_or(serde_json::Value::Null);
+            Ok((slot.info.clone(), state))
+        })
+    }
+
+    /// Disabling a running strategy stops it.
+    pub fn set_enabled(&self, id: &str, enabled: bool) -> Result<StrategyInfo, UiErrorPayload> {
+        self.with_slot(id, |slot| {
+            if !enabled {
+                slot.stop(StopReason::Disabled);
+            }
+            slot.info.enabled = enabled;
+            Ok(slot.info.clone())
+        })
+    }
+
+    /// Starts an enabled strategy; starting a running one does nothing.
+    pub fn start(&self, id: &str) -> Result<(StrategyInfo, Option<IntentBatch>), UiErrorPayload> {
+        self.with_slot(id, |slot| {
+            if !slot.info.enabled {
+                return Err(UiErrorPayload::with_values(
+                    "errors:strategies.disabled",
+                    serde_json::json!({ "id": id }),
+                ));
+            }
+            if slot.info.running {
+                return Ok((slot.info.clone(), None));
+            }
+            info!(id = %id, "strategy started");
+            let now = now_ms();
+            slot.info.running = true;
+            slot.info.started_at_ms = Some(now);
+            slot.info.stopped_reason = None;
+            slot.info.last_error = None;
+            let strategy_ref = slot.info.strategy_ref.clone();
+            let intents = slot.guarded(|s| {
+                s.on_start(&StrategyContext {
+                    now_ms: now,
+                    strategy_ref: &strategy_ref,
+                    position: None,
+                })
+            });
+            Ok((slot.info.clone(), slot.batch(intents)))
+        })
+    }
+
+    pub fn stop(&self, id: &str, reason: StopReason) -> Result<StrategyInfo, UiErrorPayload> {
+        self.with_slot(id, |slot| {
+            slot.stop(reason);
+            Ok(slot.info.clone())
+        })
+    }
+
+    /// Stops every running strategy; returns the ones it stopped.
+    pub fn stop_all(&self, reason: StopReason) -> Vec<StrategyInfo> {
+        self.lock()
+            .values_mut()
+            .filter(|s| s.info.running)
+            .map(|s| {
+                s.stop(reason);
+                s.info.clone()
+            })
+            .collect()
+    }
+
+    pub fn is_running(&self, id: &str) -> bool {
+        self.lock().get(id).is_some_and(|s| s.info.running)
+    }
+
+    /// Runs `f` on every running strategy that wants `market_id`. Returns the
+    /// intents to send and the strategies a panic stopped.
+    pub fn dispatch(
+        &self,
+        market_id: &str,
+        mut f: impl FnMut(&mut dyn Strategy, &StrategyInfo) -> Vec<OrderIntent>,
+    ) -> (Vec<IntentBatch>, Vec<StrategyInfo>) {
+        let mut batches = Vec::new();
+        let mut stopped = Vec::new();
+        for slot in self.lock().values_mut() {
+            if !slot.info.running {
+                continue;
+            }
+            let wants = catch_unwind(AssertUnwindSafe(|| slot.strategy.wants_market(market_id)));
+            if !matches!(wants, Ok(true)) {
+                continue;
+            }
+            let info = slot.info.clone();
+            let intents = slot.guarded(|s| f(s, &info));
+            if !slot.info.running {
+                stopped.push(slot.info.clone());
+            }
+            batches.extend(slot.batch(intents));
+        }
+        (batches, stopped)
+    }
+
+    /// Counts a sent intent.
+    pub fn record(&self, id: &str, outcome: &Result<(), UiErrorPayload>) -> Option<StrategyInfo> {
+        let mut slots = self.lock();
+        let slot = slots.get_mut(id)?;
+        match outcome {
+            Ok(()) => slot.info.intents_sent += 1,
+            Err(e) => {
+                slot.info.intents_failed += 1;
+                slot.info.last_error = Some(e.clone());
+            }
+        }
+        Some(slot.info.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::cache::MarketState;
+
+    /// Cancels everything on start.
+    struct Probe;
+
+    impl Strategy for Probe {
+        fn on_start(&mut self, _ctx: &StrategyContext) -> Vec<OrderIntent> {
+            vec![OrderIntent::Cancel {
+                market_id: "1.1".into(),
+                bet_id: None,
+            }]
+        }
+
+        fn on_market(&mut self, _ctx: &StrategyContext, _market: &MarketState) -> Vec<OrderIntent> {
+            Vec::new()
+        }
+    }
+
+    fn runtime(ids: &[&str]) -> StrategyRuntime {
+        let rt = StrategyRuntime::new();
+        for id in ids {
+            rt.register(id, id, "", Box::new(Probe)).unwrap();
+            rt.set_enabled(id, true).unwrap();
+        }
+        rt
+    }
+
+    /// A callback that panics for strategy "bad" on market 1.2.
+    fn dispatch(rt: &StrategyRuntime, market_id: &str) -> (Vec<IntentBatch>, Vec<StrategyInfo>) {
+        rt.dispatch(market_id, |_s, info| {
+            if info.id == "bad" && market_id == "1.2" {
+                panic!("strategy bug");
+            }
+            vec![OrderIntent::Cancel {
+                market_id: market_id.into(),
+                bet_id: None,
+            }]
+        })
+    }
+
+    #[test]
+    fn register_rejects_duplicate_ids_and_refs() {
+        let rt = runtime(&["a"]);
+        let dup = |id: &str, strategy_ref: &str| {
+            rt.register(id, strategy_ref, "", Box::new(Probe))
+                .unwrap_err()
+                .key
+        };
+        assert_eq!(dup("a", "other"), "errors:strategies.duplicate");
+        assert_eq!(dup("other", "a"), "errors:strategies.duplicate");
+        assert_eq!(
+            dup("long", "a-much-too-long-ref"),
+            "errors:validation.refLength"
+        );
+        assert_eq!(rt.list().len(), 1);
+    }
+
+    #[test]
+    fn start_needs_the_strategy_enabled() {
+        let rt = runtime(&["a"]);
+        rt.set_enabled("a", false).unwrap();
+        assert_eq!(rt.start("a").unwrap_err().key, "errors:strategies.disabled");
+        assert!(!rt.is_running("a"));
+        assert_eq!(
+            rt.start("missing").unwrap_err().key,
+            "errors:strategies.notFound"
+        );
+
+        rt.set_enabled("a", true).unwrap();
+        let (info, batch) = rt.start("a").unwrap();
+        assert!(info.running);
+        assert_eq!(batch.unwrap().intents.len(), 1);
+        // Already running: no second on_start.
+        assert!(rt.start("a").unwrap().1.is_none());
+
+        let info = rt.set_enabled("a", false).unwrap();
+        assert!(!info.running);
+        assert_eq!(info.stopped_reason, Some(StopReason::Disabled));
+    }
+
+    #[test]
+    fn a_panic_stops_only_that_strategy() {
+        let rt = runtime(&["bad", "good"]);
+        rt.start("bad").unwrap();
+        rt.start("good").unwrap();
+
+        let (batches, stopped) = dispatch(&rt, "1.2");
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].id, "good");
+        assert_eq!(stopped.len(), 1);
+        assert_eq!(stopped[0].id, "bad");
+        assert_eq!(stopped[0].stopped_reason, Some(StopReason::Panicked));
+        assert_eq!(
+            stopped[0].last_error.as_ref().map(|e| e.key.as_str()),
+            Some("errors:strategies.panicked")
+        );
+        assert!(!rt.is_running("bad"));
+        assert!(rt.is_running("good"));
+
+        let (batches, stopped) = dispatch(&rt, "1.3");
+        assert_eq!(batches.len(), 1);
+        assert!(stopped.is_empty());
+    }
+
+    #[test]
+    fn stop_all_stops_only_running_strategies() {
+        let rt = runtime(&["a", "b", "c"]);
+        rt.start("a").unwrap();
+        rt.start("b").unwrap();
+        let stopped = rt.stop_all(StopReason::KillSwitch);
+        let ids: Vec<&str> = stopped.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+        assert!(stopped
+            .iter()
+            .all(|s| !s.running && s.stopped_reason == Some(StopReason::KillSwitch)));
+        assert!(rt.stop_all(StopReason::KillSwitch).is_empty());
+        assert!(rt.list().iter().all(|s| !s.running));
+    }
+}
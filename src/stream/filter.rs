@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::ui_error::UiErrorPayload;
+
+pub const LADDER_LEVELS_MIN: u32 = 1;
+pub const LADDER_LEVELS_MAX: u32 = 10;
+pub const DEFAULT_LADDER_LEVELS: u32 = 3;
+pub const HEARTBEAT_MS_MIN: u64 = 500;
+pub const HEARTBEAT_MS_MAX: u64 = 5000;
+pub const DEFAULT_HEARTBEAT_MS: u64 = 5000;
+pub const CONFLATE_MS_MAX: u64 = 120_000;
+
+/// `marketDataFilter.fields` flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DataField {
+    /// `bdatb`/`bdatl`, virtual best prices (depth from `ladderLevels`).
+    ExBestOffersDisp,
+    /// `batb`/`batl`, best prices without virtual bets.
+    ExBestOffers,
+    /// `atb`/`atl`, full available ladders.
+    ExAllOffers,
+    /// `trd`, full traded ladder.
+    ExTraded,
+    /// `tv`, market and runner traded volume.
+    ExTradedVol,
+    ExLtp,
+    ExMarketDef,
+    /// `spb`/`spl`, starting price ladders.
+    SpTraded,
+    /// `spn`/`spf`, projected starting prices.
+    SpProjected,
+}
+
+impl DataField {
+    /// Fields whose depth is controlled by `ladderLevels`.
+    pub fn is_depth_based(self) -> bool {
+        matches!(self, Self::ExBestOffersDisp | Self::ExBestOffers)
+    }
+}
+
+/// Named field sets; several can be combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FilterPreset {
+    /// Best offers, last traded price and market definition, 3 levels deep.
+    Ladder,
+    /// Full available and traded ladders.
+    FullDepth,
+    /// Starting price ladders and projections.
+    Sp,
+}
+
+impl FilterPreset {
+    pub fn fields(self) -> &'static [DataField] {
+        match self {
+            Self::Ladder => &[
+                DataField::ExBestOffers,
+                DataField::ExLtp,
+                DataField::ExMarketDef,
+            ],
+            Self::FullDepth => &[DataField::ExAllOffers, DataField::ExTraded],
+            Self::Sp => &[DataField::SpTraded, DataField::SpProjected],
+        }
+    }
+}
+
+/// `marketDataFilter` of a `marketSubscription`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketDataFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ladder_levels: Option<u32>,
+    fields: BTreeSet<DataField>,
+}
+
+impl Default for MarketDataFilter {
+    fn default() -> Self {
+        Self::preset(FilterPreset::Ladder)
+    }
+}
+
+impl MarketDataFilter {
+    /// No fields; Betfair then sends nothing but heartbeats.
+    pub fn empty() -> Self {
+        Self {
+            ladder_levels: None,
+            fields: BTreeSet::new(),
+        }
+    }
+
+    pub fn preset(preset: FilterPreset) -> Self {
+        Self::empty().with_preset(preset)
+    }
+
+    pub fn with_preset(mut self, preset: FilterPreset) -> Self {
+        self.fields.extend(preset.fields());
+        if preset == FilterPreset::Ladder && self.ladder_levels.is_none() {
+            self.ladder_levels = Some(DEFAULT_LADDER_LEVELS);
+        }
+        self
+    }
+
+    pub fn with_field(mut self, field: DataField) -> Self {
+        self.fields.insert(field);
+        self
+    }
+
+    pub fn with_ladder_levels(mut self, levels: u32) -> Result<Self, UiErrorPayload> {
+        if !(LADDER_LEVELS_MIN..=LADDER_LEVELS_MAX).contains(&levels) {
+            return Err(UiErrorPayload::with_values(
+                "errors:validation.ladderLevelsOutOfRange",
+                serde_json::json!({ "min": LADDER_LEVELS_MIN, "max": LADDER_LEVELS_MAX }),
+            ));
+        }
+        self.ladder_levels = Some(levels);
+        Ok(self)
+    }
+
+    pub fn fields(&self) -> &BTreeSet<DataField> {
+        &self.fields
+    }
+
+    pub fn has(&self, field: DataField) -> bool {
+        self.fields.contains(&field)
+    }
+
+    /// Levels actually requested; `None` when no depth-based field is set.
+    pub fn ladder_levels(&self) -> Option<u32> {
+        if self.fields.iter().any(|f| f.is_depth_based()) {
+            Some(self.ladder_levels.unwrap_or(DEFAULT_LADDER_LEVELS))
+        } else {
+            None
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut v = serde_json::json!({ "fields": self.fields });
+        if let Some(levels) = self.ladder_levels() {
+            v["ladderLevels"] = levels.into();
+        }
+        v
+    }
+}
+
+/// Everything in a `marketSubscription` except the market ids.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketStreamSettings {
+    pub data_filter: MarketDataFilter,
+    /// Forced conflation; `None` lets Betfair decide (normally none).
+    pub conflate_ms: Option<u64>,
+    pub heartbeat_ms: u64,
+}
+
+impl Default for MarketStreamSettings {
+    fn default() -> Self {
+        Self {
+            data_filter: MarketDataFilter::default(),
+            conflate_ms: None,
+            heartbeat_ms: DEFAULT_HEARTBEAT_MS,
+        }
+    }
+}
+
+impl MarketStreamSettings {
+    pub fn new(data_filter: MarketDataFilter) -> Self {
+        Self {
+            data_filter,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_heartbeat_ms(mut self, ms: u64) -> Result<Self, UiErrorPayload> {
+        if !(HEARTBEAT_MS_MIN..=HEARTBEAT_MS_MAX).contains(&ms) {
+            return Err(UiErrorPayload::with_values(
+                "errors:validation.heartbeatOutOfRange",
+                serde_json::json!({ "min": HEARTBEAT_MS_MIN, "max": HEARTBEAT_MS_MAX }),
+            ));
+        }
+        self.heartbeat_ms = ms;
+        Ok(self)
+    }
+
+    pub fn with_conflate_ms(mut self, ms: u64) -> Result<Self, UiErrorPayload> {
+        if ms > CONFLATE_MS_MAX {
+            return Err(UiErrorPayload::with_values(
+                "errors:validation.conflateOutOfRange",
+                serde_json::json!({ "min": 0, "max": CONFLATE_MS_MAX }),
+            ));
+        }
+        self.conflate_ms = Some(ms);
+        Ok(self)
+    }
+
+    /// `op=marketSubscription` for `market_ids`.
+    pub fn subscription_message(&self, id: u64, market_ids: &BTreeSet<String>) -> String {
+        let mut msg = serde_json::json!({
+            "op": "marketSubscription",
+            "id": id,
+            "segmentationEnabled": true,
+            "heartbeatMs": self.heartbeat_ms,
+            "marketFilter": { "marketIds": market_ids },
+            "marketDataFilter": self.data_filter.to_json(),
+        });
+        if let Some(ms) = self.conflate_ms {
+            msg["conflateMs"] = ms.into();
+        }
+        msg.to_string()
+    }
+}
@@ -0,0 +1,236 @@
+//! Betfair historical data (BASIC/ADVANCED/PRO): stream `mcm` lines, one
+//! file per market, bz2-compressed and shipped one by one or in tar bundles.
+//! The format is sniffed from the content, so plain recordings, `.bz2`
+//! files and tar bundles of either all read the same way.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::ops::ControlFlow;
+use std::path::Path;
+
+use crate::market::cache::MarketCache;
+use crate::stream::mcm::MarketChangeMessage;
+
+const BZ2_MAGIC: &[u8] = b"BZh";
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+/// bz2 inside tar inside bz2 is as deep as real bundles go.
+const MAX_DEPTH: u8 = 4;
+
+enum Format {
+    Bz2,
+    Tar,
+    Text,
+}
+
+fn sniff(head: &[u8]) -> Format {
+    if head.starts_with(BZ2_MAGIC) {
+        return Format::Bz2;
+    }
+    if head.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC) {
+        return Format::Tar;
+    }
+    Format::Text
+}
+
+type LineFn<'a> = dyn FnMut(&str, &str) -> ControlFlow<()> + 'a;
+
+fn read(
+    reader: &mut dyn Read,
+    name: &str,
+    f: &mut LineFn,
+    depth: u8,
+) -> io::Result<ControlFlow<()>> {
+    let mut head = Vec::with_capacity(512);
+    reader.take(512).read_to_end(&mut head)?;
+    let format = sniff(&head);
+    let mut reader = io::Cursor::new(head).chain(reader);
+    match format {
+        Format::Bz2 if depth < MAX_DEPTH => read(
+            &mut bzip2::read::MultiBzDecoder::new(reader),
+            name,
+            f,
+            depth + 1,
+        ),
+        Format::Tar if depth < MAX_DEPTH => {
+            let mut archive = tar::Archive::new(&mut reader);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let name = entry.path()?.to_string_lossy().into_owned();
+                if read(&mut entry, &name, f, depth + 1)?.is_break() {
+                    return Ok(ControlFlow::Break(()));
+                }
+            }
+            Ok(ControlFlow::Continue(()))
+        }
+        _ => {
+            let mut reader = BufReader::with_capacity(1 << 16, reader);
+            let mut buf = Vec::new();
+            loop {
+                buf.clear();
+                if reader.read_until(b'\n', &mut buf)? == 0 {
+                    return Ok(ControlFlow::Continue(()));
+                }
+                let line = String::from_utf8_lossy(&buf);
+                let line = line.trim_end_matches(['\r', '\n']);
+                if !line.is_empty() && f(line, name).is_break() {
+                    return Ok(ControlFlow::Break(()));
+                }
+            }
+        }
+    }
+}
+
+/// Calls `f` with every line of `path` and the file it came from: the tar
+/// entry name inside bundles, else `path`. `f` can stop the read early.
+pub fn for_each_line(
+    path: &Path,
+    mut f: impl FnMut(&str, &str) -> ControlFlow<()>,
+) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    read(&mut file, &path.to_string_lossy(), &mut f, 0).map(drop)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoricalMarket {
+    pub market_id: String,
+    /// Historical definitions carry names the live stream leaves out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub venue: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market_time: Option<String>,
+    /// Last status seen, CLOSED for complete files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    pub runners: usize,
+    pub winners: Vec<u64>,
+    /// Sum of the runners' traded volume at the end of the file.
+    pub traded_volume: f64,
+    pub frames: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_pt: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_pt: Option<u64>,
+    /// File or tar entry holding it.
+    pub source: String,
+}
+
+impl HistoricalMarket {
+    fn describe(&mut self, def: &serde_json::Value) {
+        let text = |key: &str| def.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let runners = def
+            .get("runners")
+            .and_then(|v| v.as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        self.event_name = text("eventName").or(self.event_name.take());
+        self.market_name = text("name").or(self.market_name.take());
+        self.market_type = text("marketType").or(self.market_type.take());
+        self.venue = text("venue").or(self.venue.take());
+        self.country_code = text("countryCode").or(self.country_code.take());
+        self.market_time = text("marketTime").or(self.market_time.take());
+        self.status = text("status").or(self.status.take());
+        self.runners = runners.len();
+        self.winners = runners
+            .iter()
+            .filter(|r| r.get("status").and_then(|v| v.as_str()) == Some("WINNER"))
+            .filter_map(|r| r.get("id")?.as_u64())
+            .collect();
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoricalSummary {
+    pub path: String,
+    /// Files read, counting each tar entry.
+    pub files: u64,
+    pub lines: u64,
+    pub frames: u64,
+    /// Lines that were not `mcm` frames.
+    pub skipped: u64,
+    pub markets: Vec<HistoricalMarket>,
+}
+
+/// Records traded volume of the markets a file held and drops them.
+fn file_done(
+    cache: &MarketCache,
+    open: &mut BTreeSet<String>,
+    markets: &mut BTreeMap<String, HistoricalMarket>,
+) {
+    for id in std::mem::take(open) {
+        if let (Some(state), Some(m)) = (cache.market(&id), markets.get_mut(&id)) {
+            let volume: f64 = state.runners.values().map(|r| r.trd.total()).sum();
+            m.traded_volume = (volume * 100.0).round() / 100.0;
+        }
+    }
+    cache.retain(&BTreeSet::new());
+}
+
+/// Reads `path` through a market cache and lists the markets in it.
+/// Markets are dropped from the cache as each file ends, so bundles of any
+/// size fit in memory.
+pub fn scan(path: &Path) -> io::Result<HistoricalSummary> {
+    let cache = MarketCache::new();
+    let mut summary = HistoricalSummary {
+        path: path.to_string_lossy().into_owned(),
+        ..HistoricalSummary::default()
+    };
+    let mut markets: BTreeMap<String, HistoricalMarket> = BTreeMap::new();
+    let mut current = String::new();
+    let mut open: BTreeSet<String> = BTreeSet::new();
+
+    for_each_line(path, |line, source| {
+        if source != current {
+            file_done(&cache, &mut open, &mut markets);
+            current = source.to_string();
+            summary.files += 1;
+        }
+        summary.lines += 1;
+        let Some(msg) = MarketChangeMessage::parse(line) else {
+            summary.skipped += 1;
+            return ControlFlow::Continue(());
+        };
+        summary.frames += 1;
+        cache.apply(0, &msg);
+        // Only the summary is wanted; keep the alert queues empty.
+        cache.take_removals();
+        cache.take_lifecycle();
+        for mc in &msg.mc {
+            let m = markets
+                .entry(mc.id.clone())
+                .or_insert_with(|| HistoricalMarket {
+                    market_id: mc.id.clone(),
+                    source: source.to_string(),
+                    ..HistoricalMarket::default()
+                });
+            m.frames += 1;
+            if let Some(pt) = msg.pt {
+                m.first_pt.get_or_insert(pt);
+                m.last_pt = Some(pt);
+            }
+            if let Some(def) = &mc.market_definition {
+                m.describe(def);
+            }
+            open.insert(mc.id.clone());
+        }
+        ControlFlow::Continue(())
+    })?;
+    file_done(&cache, &mut open, &mut markets);
+    summary.markets = markets.into_values().collect();
+    Ok(summary)
+}
@@ -0,0 +1,268 @@
+use serde::Serialize;
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Mutex;
+
+use crate::clock::now_ms;
+use crate::redact;
+use crate::stream::mcm::ChangeType;
+use crate::stream::{StatusMessage, StreamRole};
+
+/// Connections kept for diagnostics (oldest dropped first).
+const MAX_CONNECTIONS: usize = 50;
+const MAX_STATUS_ERRORS: usize = 20;
+pub const MAX_FRAMES: usize = 500;
+/// Image frames can be megabytes; keep the head only.
+const MAX_FRAME_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FrameDirection {
+    In,
+    Out,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawFrame {
+    pub conn: u64,
+    pub ts_ms: u64,
+    pub direction: FrameDirection,
+    pub len: usize,
+    pub truncated: bool,
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusError {
+    pub ts_ms: u64,
+    pub id: Option<u64>,
+    pub error_code: Option<String>,
+    pub error_message: Option<String>,
+    pub connection_closed: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionRecord {
+    pub conn: u64,
+    pub role: StreamRole,
+    /// Betfair's id from the `op=connection` frame.
+    pub connection_id: Option<String>,
+    pub opened_at_ms: u64,
+    pub closed_at_ms: Option<u64>,
+    /// True when the socket dropped without `stream_disconnect`.
+    pub unexpected_close: bool,
+    pub frames_in: u64,
+    pub frames_out: u64,
+    pub heartbeats: u64,
+    pub last_frame_at_ms: Option<u64>,
+    /// Receive time minus Betfair's publish time (`pt`) on the last mcm/ocm.
+    pub last_lag_ms: Option<i64>,
+    pub max_lag_ms: Option<i64>,
+    /// Longest silence between two inbound frames.
+    pub max_gap_ms: u64,
+    pub status_errors: Vec<StatusError>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamHistorySnapshot {
+    pub reconnects: u64,
+    pub connections: Vec<ConnectionRecord>,
+}
+
+/// The fields of an mcm/ocm frame needed for lag accounting, taken from
+/// the reader's own parse.
+#[derive(Debug, Clone, Copy)]
+pub struct ChangeMeta {
+    pub pt: Option<u64>,
+    pub heartbeat: bool,
+}
+
+impl ChangeMeta {
+    pub fn new(pt: Option<u64>, ct: Option<ChangeType>) -> Self {
+        Self {
+            pt,
+            heartbeat: ct == Some(ChangeType::Heartbeat),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    reconnects: u64,
+    /// Roles opened so far; opening one again is a reconnect.
+    roles: BTreeSet<StreamRole>,
+    connections: VecDeque<ConnectionRecord>,
+    frames: VecDeque<RawFrame>,
+}
+
+impl Inner {
+    fn connection(&mut self, conn: u64) -> Option<&mut ConnectionRecord> {
+        self.connections.iter_mut().rev().find(|c| c.conn == conn)
+    }
+
+    fn push_frame(&mut self, conn: u64, direction: FrameDirection, line: &str) {
+        let mut cut = line.len().min(MAX_FRAME_BYTES);
+        while !line.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        if self.frames.len() == MAX_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(RawFrame {
+            conn,
+            ts_ms: now_ms(),
+            direction,
+            len: line.len(),
+            truncated: cut < line.len(),
+            line: line[..cut].to_string(),
+        });
+    }
+}
+
+/// Per-session record of stream connections and recent raw frames, for the
+/// diagnostics bundle.
+pub struct StreamHistory {
+    inner: Mutex<Inner>,
+}
+
+impl Default for StreamHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamHistory {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn opened(&self, conn: u64, role: StreamRole) {
+        let mut inner = self.lock();
+        if !inner.roles.insert(role) {
+            inner.reconnects += 1;
+        }
+        if inner.connections.len() == MAX_CONNECTIONS {
+            inner.connections.pop_front();
+        }
+        inner.connections.push_back(ConnectionRecord {
+            conn,
+            role,
+            connection_id: None,
+            opened_at_ms: now_ms(),
+            closed_at_ms: None,
+            unexpected_close: false,
+            frames_in: 0,
+            frames_out: 0,
+            heartbeats: 0,
+            last_frame_at_ms: None,
+            last_lag_ms: None,
+            max_lag_ms: None,
+            max_gap_ms: 0,
+            status_errors: Vec::new(),
+        });
+    }
+
+    pub fn closed(&self, conn: u64, unexpected: bool) {
+        if let Some(c) = self.lock().connection(conn) {
+            c.closed_at_ms = Some(now_ms());
+            c.unexpected_close = unexpected;
+        }
+    }
+
+    pub fn connection_id(&self, conn: u64, connection_id: &str) {
+        if let Some(c) = self.lock().connection(conn) {
+            c.connection_id = Some(connection_id.to_string());
+        }
+    }
+
+    pub fn status(&self, conn: u64, status: &StatusMessage) {
+        if status.error_code.is_none() {
+            return;
+        }
+        if let Some(c) = self.lock().connection(conn) {
+            if c.status_errors.len() == MAX_STATUS_ERRORS {
+                c.status_errors.remove(0);
+            }
+            c.status_errors.push(StatusError {
+                ts_ms: now_ms(),
+                id: status.id,
+                error_code: status.error_code.clone(),
+                error_message: status.error_message.clone(),
+                connection_closed: status.connection_closed,
+            });
+        }
+    }
+
+    pub fn frame_in(&self, conn: u64, line: &str, change: Option<ChangeMeta>) {
+        let now = now_ms();
+
+        let mut inner = self.lock();
+        if let Some(c) = inner.connection(conn) {
+            c.frames_in += 1;
+            if let Some(last) = c.last_frame_at_ms {
+                c.max_gap_ms = c.max_gap_ms.max(now.saturating_sub(last));
+            }
+            c.last_frame_at_ms = Some(now);
+
+            if let Some(change) = change {
+                if change.heartbeat {
+                    c.heartbeats += 1;
+                }
+                if let Some(pt) = change.pt {
+                    let lag = now as i64 - pt as i64;
+                    c.last_lag_ms = Some(lag);
+                    c.max_lag_ms = Some(c.max_lag_ms.map_or(lag, |m| m.max(lag)));
+                }
+            }
+        }
+        inner.push_frame(conn, FrameDirection::In, line);
+    }
+
+    pub fn frame_out(&self, conn: u64, line: &str) {
+        let mut inner = self.lock();
+        if let Some(c) = inner.connection(conn) {
+            c.frames_out += 1;
+        }
+        inner.push_frame(conn, FrameDirection::Out, line);
+    }
+
+    pub fn snapshot(&self) -> StreamHistorySnapshot {
+        let inner = self.lock();
+        StreamHistorySnapshot {
+            reconnects: inner.reconnects,
+            connections: inner.connections.iter().cloned().collect(),
+        }
+    }
+
+    /// Last `limit` raw frames, oldest first, with secrets masked.
+    pub fn frames(&self, limit: usize) -> Vec<RawFrame> {
+        let inner = self.lock();
+        let skip = inner.frames.len().saturating_sub(limit);
+        inner
+            .frames
+            .iter()
+            .skip(skip)
+            .map(|f| RawFrame {
+                line: mask_frame(&f.line),
+                ..f.clone()
+            })
+            .collect()
+    }
+}
+
+fn mask_frame(line: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(line) {
+        Ok(v) => redact::mask_json(v).to_string(),
+        // Truncated or non-JSON frames: scrub known secret values only.
+        Err(_) => redact::mask_text(line),
+    }
+}
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Deserializer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ChangeType {
+    SubImage,
+    ResubDelta,
+    Heartbeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SegmentType {
+    SegStart,
+    Seg,
+    SegEnd,
+}
+
+/// `op=mcm` frame. Field names follow the Stream API spec.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketChangeMessage {
+    /// Id of the subscription this change belongs to.
+    pub id: Option<u64>,
+    pub ct: Option<ChangeType>,
+    pub segment_type: Option<SegmentType>,
+    pub pt: Option<u64>,
+    pub clk: Option<String>,
+    pub initial_clk: Option<String>,
+    #[serde(default)]
+    pub mc: Vec<MarketChange>,
+}
+
+impl MarketChangeMessage {
+    /// Cheap pre-check so only mcm frames are fully parsed.
+    pub fn parse(line: &str) -> Option<Self> {
+        if !line.contains("\"op\":\"mcm\"") {
+            return None;
+        }
+        serde_json::from_str(line).ok()
+    }
+
+    /// First (or only) frame of a subscription image: the cache starts over.
+    pub fn starts_image(&self) -> bool {
+        self.ct == Some(ChangeType::SubImage)
+            && matches!(self.segment_type, None | Some(SegmentType::SegStart))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketChange {
+    pub id: String,
+    /// Replace the cached market rather than merge.
+    #[serde(default)]
+    pub img: bool,
+    pub tv: Option<f64>,
+    /// Passed through to the UI as-is.
+    pub market_definition: Option<serde_json::Value>,
+    #[serde(default)]
+    pub rc: Vec<RunnerChange>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunnerChange {
+    pub id: u64,
+    /// Handicap; with `id` it identifies the runner on Asian handicap markets.
+    pub hc: Option<f64>,
+    pub ltp: Option<f64>,
+    pub tv: Option<f64>,
+    /// Best available to back, `[level, price, size]`; size 0 removes the level.
+    pub batb: Option<Vec<[f64; 3]>>,
+    pub batl: Option<Vec<[f64; 3]>>,
+    /// Virtual (cross-matched) best prices from `EX_BEST_OFFERS_DISP`.
+    pub bdatb: Option<Vec<[f64; 3]>>,
+    pub bdatl: Option<Vec<[f64; 3]>>,
+    /// Full available to back/lay, `[price, size]`; size 0 removes the price.
+    pub atb: Option<Vec<[f64; 2]>>,
+    pub atl: Option<Vec<[f64; 2]>>,
+    /// Traded volume by price.
+    pub trd: Option<Vec<[f64; 2]>>,
+    /// Projected BSP: near (includes the SP ladders) and far (SP bets only).
+    /// `Some(None)` when sent without a price, which clears the projection.
+    #[serde(default, deserialize_with = "sp_price")]
+    pub spn: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "sp_price")]
+    pub spf: Option<Option<f64>>,
+    /// SP back/lay liability waiting for reconciliation, `[price, size]`.
+    pub spb: Option<Vec<[f64; 2]>>,
+    pub spl: Option<Vec<[f64; 2]>>,
+}
+
+/// Projected prices arrive as `"NaN"` / `"Infinity"` strings when there is
+/// nothing to project; those read as no price.
+fn sp_price<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Option<f64>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Num(f64),
+        Text(String),
+    }
+    Ok(Some(match Option::<Raw>::deserialize(d)? {
+        Some(Raw::Num(n)) if n.is_finite() => Some(n),
+        Some(Raw::Text(t)) => t.parse::<f64>().ok().filter(|n| n.is_finite()),
+        _ => None,
+    }))
+}
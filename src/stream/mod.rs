@@ -1,5 +1,163 @@
+use futures_util::{Sink, SinkExt};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::debug;
+
+pub mod filter;
+pub mod historical;
+pub mod history;
+pub mod mcm;
+pub mod ocm;
+pub mod subscriptions;
+
+/// Raw inbound frames; debug channel, off unless enabled with `stream_set_raw_lines`.
 pub const EVENT_STREAM_LINE: &str = "betfair_stream_line";
+pub const EVENT_STREAM_STATUS: &str = "betfair_stream_status";
+pub const EVENT_STREAM_CONNECTION: &str = "betfair_stream_connection";
+/// `op=ocm` frames from the order connection, forwarded as parsed JSON.
+pub const EVENT_ORDER_CHANGE: &str = "betfair_order_change";
+/// Markets left out of the subscription (limit rotation or CLOSED).
+pub const EVENT_MARKETS_DROPPED: &str = "betfair_markets_dropped";
+
+pub const STREAM_HOST: &str = "stream-api.betfair.com";
+pub const STREAM_PORT: u16 = 443;
+
+/// What a connection is for. Market connections form a small pool (slot
+/// index); orders always get their own connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind", content = "slot")]
+pub enum StreamRole {
+    Market(usize),
+    Orders,
+}
+
+impl std::fmt::Display for StreamRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamRole::Market(slot) => write!(f, "market-{slot}"),
+            StreamRole::Orders => f.write_str("orders"),
+        }
+    }
+}
 
 pub struct StreamConnection {
+    /// Distinguishes connections so a stale reader can't clear a newer one.
+    pub id: u64,
+    pub role: StreamRole,
     pub tx: tokio::sync::mpsc::Sender<String>,
+    /// Set once Betfair accepts the authentication frame; subscriptions are
+    /// only sent after that.
+    pub authenticated: bool,
+}
+
+/// Open connections by role.
+pub type StreamPool = std::collections::BTreeMap<StreamRole, StreamConnection>;
+
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+pub fn next_connection_id() -> u64 {
+    NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Request id used for the authentication frame (the UI matches its status on it).
+pub const AUTH_REQUEST_ID: u64 = 1;
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(AUTH_REQUEST_ID + 1);
+
+/// Ids for subscription frames, unique across connections so status replies
+/// in logs can be matched to the request.
+pub fn next_request_id() -> u64 {
+    NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub fn authentication_message(app_key: &str, session: &str) -> String {
+    serde_json::json!({
+        "op": "authentication",
+        "id": AUTH_REQUEST_ID,
+        "appKey": app_key,
+        "session": session
+    })
+    .to_string()
+}
+
+#[derive(Default, Deserialize)]
+struct OutboundHead {
+    #[serde(default)]
+    op: String,
+    id: Option<u64>,
+}
+
+/// Forwards queued frames to the socket until every sender is dropped.
+pub async fn run_writer<S>(mut sink: S, mut rx: tokio::sync::mpsc::Receiver<String>)
+where
+    S: Sink<String> + Unpin,
+{
+    while let Some(line) = rx.recv().await {
+        // Never the body: the authentication frame carries the app key and
+        // session token.
+        let head: OutboundHead = serde_json::from_str(&line).unwrap_or_default();
+        debug!(n = line.len(), op = %head.op, id = ?head.id, "stream_out");
+        let _ = sink.send(line).await;
+    }
+    let _ = sink.close().await;
+}
+
+/// `op=connection` frame the Stream API sends right after the socket opens.
+/// Betfair support asks for the `connectionId` when investigating stream issues.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionMessage {
+    pub connection_id: String,
+}
+
+impl ConnectionMessage {
+    pub fn parse(line: &str) -> Option<Self> {
+        if !line.contains("\"op\":\"connection\"") {
+            return None;
+        }
+        serde_json::from_str(line).ok()
+    }
+}
+
+/// Payload of `EVENT_STREAM_STATUS`: the status frame plus which connection sent it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamStatusEvent<'a> {
+    pub conn: u64,
+    pub role: StreamRole,
+    #[serde(flatten)]
+    pub status: &'a StatusMessage,
+}
+
+/// `op=status` response from the Stream API.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusMessage {
+    pub id: Option<u64>,
+    pub status_code: Option<String>,
+    pub error_code: Option<String>,
+    pub error_message: Option<String>,
+    pub connection_closed: Option<bool>,
+}
+
+impl StatusMessage {
+    /// Cheap pre-check so only status frames are fully parsed.
+    pub fn parse(line: &str) -> Option<Self> {
+        if !line.contains("\"op\":\"status\"") {
+            return None;
+        }
+        serde_json::from_str(line).ok()
+    }
+
+    pub fn is_session_error(&self) -> bool {
+        matches!(
+            self.error_code.as_deref(),
+            Some("NO_SESSION" | "INVALID_SESSION_INFORMATION")
+        )
+    }
+
+    /// The only error that leaves the connection open.
+    pub fn is_subscription_limit(&self) -> bool {
+        self.error_code.as_deref() == Some("SUBSCRIPTION_LIMIT_EXCEEDED")
+    }
 }
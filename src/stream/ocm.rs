@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::stream::mcm::{ChangeType, SegmentType};
+
+/// `op=ocm` frame. Field names follow the Stream API spec.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderChangeMessage {
+    pub id: Option<u64>,
+    pub ct: Option<ChangeType>,
+    pub segment_type: Option<SegmentType>,
+    pub pt: Option<u64>,
+    pub clk: Option<String>,
+    pub initial_clk: Option<String>,
+    #[serde(default)]
+    pub oc: Vec<OrderMarketChange>,
+}
+
+impl OrderChangeMessage {
+    pub fn parse(line: &str) -> Option<Self> {
+        if !line.contains("\"op\":\"ocm\"") {
+            return None;
+        }
+        serde_json::from_str(line).ok()
+    }
+
+    pub fn starts_image(&self) -> bool {
+        self.ct == Some(ChangeType::SubImage)
+            && matches!(self.segment_type, None | Some(SegmentType::SegStart))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderMarketChange {
+    pub id: String,
+    #[serde(default)]
+    pub closed: bool,
+    #[serde(default)]
+    pub full_image: bool,
+    #[serde(default)]
+    pub orc: Vec<OrderRunnerChange>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderRunnerChange {
+    pub id: u64,
+    pub hc: Option<f64>,
+    #[serde(default)]
+    pub full_image: bool,
+    /// Orders are sent in full on every change.
+    #[serde(default)]
+    pub uo: Vec<UnmatchedOrder>,
+    /// Matched backs / lays by price, `[price, size]` deltas.
+    pub mb: Option<Vec<[f64; 2]>>,
+    pub ml: Option<Vec<[f64; 2]>>,
+    /// Matched deltas per `customerStrategyRef` (with
+    /// `partitionMatchedByStrategyRef`).
+    pub smc: Option<HashMap<String, StrategyMatchChange>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StrategyMatchChange {
+    pub mb: Option<Vec<[f64; 2]>>,
+    pub ml: Option<Vec<[f64; 2]>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    #[serde(rename = "B")]
+    Back,
+    #[serde(rename = "L")]
+    Lay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderStatus {
+    #[serde(rename = "E")]
+    Executable,
+    #[serde(rename = "EC")]
+    ExecutionComplete,
+}
+
+/// `uo` entry; serialized to the UI with the same short names.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnmatchedOrder {
+    /// Bet id.
+    pub id: String,
+    pub p: f64,
+    pub s: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bsp: Option<f64>,
+    pub side: Side,
+    pub status: OrderStatus,
+    /// Persistence type: L, P or MOC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pt: Option<String>,
+    /// Order type: L, MOC or LOC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ot: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pd: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub md: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avp: Option<f64>,
+    #[serde(default)]
+    pub sm: f64,
+    #[serde(default)]
+    pub sr: f64,
+    #[serde(default)]
+    pub sl: f64,
+    #[serde(default)]
+    pub sc: f64,
+    #[serde(default)]
+    pub sv: f64,
+    /// customerOrderRef.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rfo: Option<String>,
+    /// customerStrategyRef.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rfs: Option<String>,
+}
@@ -0,0 +1,425 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::market::cache::LadderSource;
+use crate::stream::filter::{DataField, MarketStreamSettings};
+
+/// Betfair's default per-account market subscription limit.
+pub const MARKET_SUBSCRIPTION_LIMIT: usize = 200;
+pub const MAX_POOL_SIZE: usize = 4;
+
+/// Which markets win when views want more than the limit allows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PriorityOrder {
+    /// Earliest `marketTime` first; markets with no known start go last.
+    #[default]
+    NearestStart,
+    FirstRequested,
+    /// Newest request first, so opening a view rotates the oldest markets out.
+    LastRequested,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionRules {
+    /// Always subscribed (while some view wants them), ahead of `order`.
+    #[serde(default)]
+    pub pinned: Vec<String>,
+    #[serde(default)]
+    pub order: PriorityOrder,
+    /// Account limit if Betfair granted more or less than the default 200.
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DropReason {
+    /// Outranked by other markets under the subscription limit.
+    Limit,
+    /// `marketDefinition.status == CLOSED`.
+    Closed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DroppedMarket {
+    pub market_id: String,
+    pub reason: DropReason,
+    /// Views that asked for it.
+    pub subscribers: Vec<String>,
+}
+
+/// Payload of `EVENT_MARKETS_DROPPED`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketsDropped {
+    pub limit: usize,
+    pub subscribed: usize,
+    pub dropped: Vec<DroppedMarket>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionStatus {
+    pub limit: usize,
+    pub pool_size: usize,
+    /// Markets actually subscribed across all slots.
+    pub market_count: usize,
+    pub rules: SubscriptionRules,
+    pub settings: MarketStreamSettings,
+    /// Views showing a non-default ladder.
+    pub ladders: BTreeMap<String, LadderSource>,
+    /// Subscriber id -> requested market ids.
+    pub subscribers: BTreeMap<String, Vec<String>>,
+    /// Market ids per pool slot.
+    pub slots: Vec<Vec<String>>,
+    /// Requested but not subscribed, with the reason.
+    pub dropped: BTreeMap<String, DropReason>,
+}
+
+/// Outcome of recomputing the subscription.
+#[derive(Debug, Default)]
+pub struct Rebalance {
+    /// Slots whose `marketSubscription` must be re-sent (or closed).
+    pub changed: Vec<usize>,
+    /// Markets newly left out since the previous rebalance.
+    pub dropped: Vec<DroppedMarket>,
+}
+
+/// Merges the market ids requested by independent views into one
+/// `marketSubscription` per pooled connection.
+///
+/// Betfair subscriptions replace rather than add, so every change recomputes
+/// the full set for each slot. Existing assignments are kept stable so adding
+/// a view doesn't resubscribe (and re-image) unrelated connections. When views
+/// want more than the limit, markets are ranked by `SubscriptionRules` and the
+/// rest are reported as dropped; closed markets are dropped for good.
+pub struct SubscriptionManager {
+    views: BTreeMap<String, BTreeSet<String>>,
+    slots: Vec<BTreeSet<String>>,
+    rules: SubscriptionRules,
+    settings: MarketStreamSettings,
+    ladders: BTreeMap<String, LadderSource>,
+    limit: usize,
+    /// Order in which markets were first requested.
+    requested_seq: HashMap<String, u64>,
+    next_seq: u64,
+    start_ms: HashMap<String, u64>,
+    closed: BTreeSet<String>,
+    dropped: BTreeMap<String, DropReason>,
+}
+
+impl Default for SubscriptionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SubscriptionManager {
+    pub fn new() -> Self {
+        Self {
+            views: BTreeMap::new(),
+            slots: vec![BTreeSet::new()],
+            rules: SubscriptionRules::default(),
+            settings: MarketStreamSettings::default(),
+            ladders: BTreeMap::new(),
+            limit: MARKET_SUBSCRIPTION_LIMIT,
+            requested_seq: HashMap::new(),
+            next_seq: 0,
+            start_ms: HashMap::new(),
+            closed: BTreeSet::new(),
+            dropped: BTreeMap::new(),
+        }
+    }
+
+    pub fn pool_size(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Settings as sent: the configured filter plus `EX_BEST_OFFERS_DISP`
+    /// while any view shows the virtual ladder.
+    pub fn effective_settings(&self) -> MarketStreamSettings {
+        let mut settings = self.settings.clone();
+        if self.ladders.values().any(|l| *l == LadderSource::Virtual) {
+            settings.data_filter = settings.data_filter.with_field(DataField::ExBestOffersDisp);
+        }
+        settings
+    }
+
+    /// Replaces the data filter and options used by every market slot;
+    /// returns the slots to resubscribe.
+    pub fn set_settings(&mut self, settings: MarketStreamSettings) -> Vec<usize> {
+        let before = self.effective_settings();
+        self.settings = settings;
+        self.resend_if_changed(&before)
+    }
+
+    pub fn ladder_source(&self, subscriber: &str) -> LadderSource {
+        self.ladders.get(subscriber).copied().unwrap_or_default()
+    }
+
+    /// Switches a view between the direct and virtual ladder; returns the
+    /// slots to resubscribe if the data filter changed.
+    pub fn set_ladder_source(&mut self, subscriber: &str, source: LadderSource) -> Vec<usize> {
+        let before = self.effective_settings();
+        if source == LadderSource::default() {
+            self.ladders.remove(subscriber);
+        } else {
+            self.ladders.insert(subscriber.to_string(), source);
+        }
+        self.resend_if_changed(&before)
+    }
+
+    fn resend_if_changed(&self, before: &MarketStreamSettings) -> Vec<usize> {
+        if *before == self.effective_settings() {
+            return Vec::new();
+        }
+        (0..self.slots.len())
+            .filter(|i| !self.slots[*i].is_empty())
+            .collect()
+    }
+
+    pub fn slot(&self, slot: usize) -> Option<&BTreeSet<String>> {
+        self.slots.get(slot)
+    }
+
+    /// Markets currently subscribed on some slot.
+    pub fn active(&self) -> BTreeSet<String> {
+        self.slots.iter().flatten().cloned().collect()
+    }
+
+    /// Every market some view wants, subscribed or not.
+    pub fn wanted(&self) -> BTreeSet<String> {
+        self.views.values().flatten().cloned().collect()
+    }
+
+    pub fn subscribers_for(&self, market_id: &str) -> Vec<String> {
+        self.views
+            .iter()
+            .filter(|(_, markets)| markets.contains(market_id))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Replaces a view's markets.
+    pub fn set_view(&mut self, subscriber: &str, market_ids: &[String]) -> Rebalance {
+        let markets: BTreeSet<String> = market_ids
+            .iter()
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .collect();
+        for m in &markets {
+            if !self.requested_seq.contains_key(m) {
+                self.requested_seq.insert(m.clone(), self.next_seq);
+                self.next_seq += 1;
+            }
+        }
+
+        if markets.is_empty() {
+            self.views.remove(subscriber);
+        } else {
+            self.views.insert(subscriber.to_string(), markets);
+        }
+        self.rebalance()
+    }
+
+    pub fn remove_view(&mut self, subscriber: &str) -> Rebalance {
+        let resend = self.set_ladder_source(subscriber, LadderSource::default());
+        self.views.remove(subscriber);
+        let mut r = self.rebalance();
+        r.changed.extend(resend);
+        r.changed.sort_unstable();
+        r.changed.dedup();
+        r
+    }
+
+    fn configured_limit(&self) -> usize {
+        self.rules.limit.unwrap_or(MARKET_SUBSCRIPTION_LIMIT).max(1)
+    }
+
+    pub fn set_rules(&mut self, rules: SubscriptionRules) -> Rebalance {
+        self.rules = rules;
+        self.limit = self.configured_limit();
+        self.rebalance()
+    }
+
+    /// Betfair rejected the subscription as over the account limit: step
+    /// down 10% (at least one) from what is subscribed now. The error text
+    /// doesn't reliably say what the limit is.
+    pub fn limit_exceeded(&mut self) -> Rebalance {
+        let active = self.slots.iter().map(|s| s.len()).sum::<usize>();
+        self.limit = active.saturating_sub((active / 10).max(1)).max(1);
+        self.rebalance()
+    }
+
+    /// Back to the configured limit, undoing `limit_exceeded` for a new
+    /// connection.
+    pub fn reset_limit(&mut self) -> Rebalance {
+        self.limit = self.configured_limit();
+        self.rebalance()
+    }
+
+    /// Records what a market definition says about a market; true when the
+    /// ranking may have changed and the caller should `rebalance`.
+    pub fn note_definition(
+        &mut self,
+        market_id: &str,
+        start_ms: Option<u64>,
+        closed: bool,
+    ) -> bool {
+        if !self.views.values().any(|m| m.contains(market_id)) {
+            return false;
+        }
+        let moved = match start_ms {
+            Some(ms) => self.start_ms.insert(market_id.to_string(), ms) != Some(ms),
+            None => false,
+        };
+        let newly_closed = closed && self.closed.insert(market_id.to_string());
+        let contested =
+            self.rules.order == PriorityOrder::NearestStart && self.wanted().len() > self.limit;
+        newly_closed || (moved && contested)
+    }
+
+    /// Resizes the pool; returns the slots (old or new) that changed.
+    pub fn set_pool_size(&mut self, size: usize) -> Rebalance {
+        let size = size.clamp(1, MAX_POOL_SIZE);
+        let old = self.slots.len();
+        if size < old {
+            // Markets on removed slots are reassigned by `rebalance`.
+            self.slots.truncate(size);
+        } else {
+            self.slots.resize(size, BTreeSet::new());
+        }
+        let mut r = self.rebalance();
+        r.changed.extend(size..old);
+        r
+    }
+
+    pub fn rebalance(&mut self) -> Rebalance {
+        let wanted = self.wanted();
+        // Forget markets no view wants any more.
+        self.requested_seq.retain(|m, _| wanted.contains(m));
+        self.start_ms.retain(|m, _| wanted.contains(m));
+        self.closed.retain(|m| wanted.contains(m));
+        let pinned: BTreeSet<&str> = self.rules.pinned.iter().map(|s| s.as_str()).collect();
+
+        let mut ranked: Vec<&String> = wanted
+            .iter()
+            .filter(|m| !self.closed.contains(*m))
+            .collect();
+        ranked.sort_by_key(|m| {
+            let seq = self.requested_seq.get(*m).copied().unwrap_or(u64::MAX);
+            let order = match self.rules.order {
+                PriorityOrder::NearestStart => {
+                    (self.start_ms.get(*m).copied().unwrap_or(u64::MAX), seq)
+                }
+                PriorityOrder::FirstRequested => (seq, 0),
+                PriorityOrder::LastRequested => (u64::MAX - seq, 0),
+            };
+            (!pinned.contains(m.as_str()), order)
+        });
+        let active: BTreeSet<String> = ranked
+            .iter()
+            .take(self.limit)
+            .map(|m| (*m).clone())
+            .collect();
+
+        let mut dropped = BTreeMap::new();
+        for m in &wanted {
+            if self.closed.contains(m) {
+                dropped.insert(m.clone(), DropReason::Closed);
+            } else if !active.contains(m) {
+                dropped.insert(m.clone(), DropReason::Limit);
+            }
+        }
+        let newly_dropped = dropped
+            .iter()
+            .filter(|(m, r)| self.dropped.get(*m) != Some(*r))
+            .map(|(m, r)| DroppedMarket {
+                market_id: m.clone(),
+                reason: *r,
+                subscribers: self.subscribers_for(m),
+            })
+            .collect();
+        self.dropped = dropped;
+
+        let before = self.slots.clone();
+        for slot in &mut self.slots {
+            slot.retain(|m| active.contains(m));
+        }
+        let assigned = self.active();
+        for market in active.difference(&assigned) {
+            let Some(slot) = self.slots.iter_mut().min_by_key(|s| s.len()) else {
+                break;
+            };
+            slot.insert(market.clone());
+        }
+
+        Rebalance {
+            changed: (0..self.slots.len())
+                .filter(|i| before.get(*i) != Some(&self.slots[*i]))
+                .collect(),
+            dropped: newly_dropped,
+        }
+    }
+
+    pub fn dropped_event(&self, dropped: Vec<DroppedMarket>) -> MarketsDropped {
+        MarketsDropped {
+            limit: self.limit,
+            subscribed: self.slots.iter().map(|s| s.len()).sum(),
+            dropped,
+        }
+    }
+
+    pub fn status(&self) -> SubscriptionStatus {
+        SubscriptionStatus {
+            limit: self.limit,
+            pool_size: self.slots.len(),
+            market_count: self.slots.iter().map(|s| s.len()).sum(),
+            rules: self.rules.clone(),
+            settings: self.effective_settings(),
+            ladders: self.ladders.clone(),
+            subscribers: self
+                .views
+                .iter()
+                .map(|(id, m)| (id.clone(), m.iter().cloned().collect()))
+                .collect(),
+            slots: self
+                .slots
+                .iter()
+                .map(|s| s.iter().cloned().collect())
+                .collect(),
+            dropped: self.dropped.clone(),
+        }
+    }
+}
+
+/// `op=orderSubscription` for the dedicated order connection.
+pub fn order_subscription_message(id: u64) -> String {
+    serde_json::json!({
+        "op": "orderSubscription",
+        "id": id,
+        "segmentationEnabled": true,
+        "heartbeatMs": 5000,
+        "orderFilter": {
+            "includeOverallPosition": true,
+            "partitionMatchedByStrategyRef": true
+        }
+    })
+    .to_string()
+}
+
+/// Subscriber ids become part of an event name, so keep them to safe characters.
+pub fn is_valid_subscriber_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
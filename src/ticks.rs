@@ -0,0 +1,358 @@
+//! Betfair price ladders: which prices are valid and how far apart they are.
+//!
+//! Prices are handled internally in hundredths (`price_key`) so rounding
+//! noise in `f64` never produces an off-ladder price.
+
+use serde::Serialize;
+
+use crate::ui_error::UiErrorPayload;
+
+pub const MIN_ODDS: f64 = 1.01;
+pub const MAX_ODDS: f64 = 1000.0;
+
+/// CLASSIC ladder as (from, to, increment) in hundredths.
+const CLASSIC_BANDS: [(i64, i64, i64); 10] = [
+    (101, 200, 1),
+    (200, 300, 2),
+    (300, 400, 5),
+    (400, 600, 10),
+    (600, 1000, 20),
+    (1000, 2000, 50),
+    (2000, 3000, 100),
+    (3000, 5000, 200),
+    (5000, 10000, 500),
+    (10000, 100000, 1000),
+];
+
+const FINEST_BANDS: [(i64, i64, i64); 1] = [(101, 100000, 1)];
+
+/// `marketDefinition.priceLadderDefinition.type`, plus the line range for
+/// LINE_RANGE markets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum TickLadder {
+    /// 0.01 up to 2, 0.02 up to 3, ... 10 from 100 to 1000.
+    #[default]
+    Classic,
+    /// 0.01 steps from 1.01 to 1000.
+    Finest,
+    /// Line markets: `min..=max` in steps of `interval` (market units).
+    LineRange { min: f64, max: f64, interval: f64 },
+}
+
+/// Direction for `TickLadder::round`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Nearest,
+    Down,
+    Up,
+}
+
+pub fn price_key(price: f64) -> i64 {
+    (price * 100.0).round() as i64
+}
+
+pub fn key_price(key: i64) -> f64 {
+    key as f64 / 100.0
+}
+
+impl TickLadder {
+    /// Ladder for a market from its stream `marketDefinition`; CLASSIC when
+    /// the definition doesn't say.
+    pub fn from_market_definition(def: &serde_json::Value) -> Self {
+        let kind = def
+            .pointer("/priceLadderDefinition/type")
+            .and_then(|v| v.as_str());
+        let line = || {
+            let num = |k: &str| def.get(k).and_then(|v| v.as_f64());
+            let (min, max, interval) = (
+                num("lineMinUnit")?,
+                num("lineMaxUnit")?,
+                num("lineInterval")?,
+            );
+            (interval > 0.0 && max > min).then_some(Self::LineRange { min, max, interval })
+        };
+        match kind {
+            Some("FINEST") => Self::Finest,
+            Some("LINE_RANGE") => line().unwrap_or_default(),
+            // Older definitions only carry `bettingType`.
+            None if def.get("bettingType").and_then(|v| v.as_str()) == Some("LINE") => {
+                line().unwrap_or_default()
+            }
+            _ => Self::Classic,
+        }
+    }
+
+    pub fn is_line(&self) -> bool {
+        matches!(self, Self::LineRange { .. })
+    }
+
+    /// Odds a matched bet at `price` pays at: line markets trade at even
+    /// money whatever the line value.
+    pub fn odds(&self, price: f64) -> f64 {
+        if self.is_line() {
+            2.0
+        } else {
+            price
+        }
+    }
+
+    fn bands(&self) -> Vec<(i64, i64, i64)> {
+        match self {
+            Self::Classic => CLASSIC_BANDS.to_vec(),
+            Self::Finest => FINEST_BANDS.to_vec(),
+            Self::LineRange { min, max, interval } => {
+                let (from, inc) = (price_key(*min), price_key(*interval).max(1));
+                // The last step may not land exactly on `max`.
+                let to = from + (price_key(*max) - from) / inc * inc;
+                vec![(from, to, inc)]
+            }
+        }
+    }
+
+    pub fn min_price(&self) -> f64 {
+        key_price(self.bands()[0].0)
+    }
+
+    pub fn max_price(&self) -> f64 {
+        key_price(self.bands().last().map_or(0, |b| b.1))
+    }
+
+    /// Position of `key` on the ladder if it is exactly on a tick.
+    fn index_of(&self, key: i64) -> Option<i64> {
+        let mut base = 0;
+        for (from, to, inc) in self.bands() {
+            if key >= from && key <= to {
+                return ((key - from) % inc == 0).then_some(base + (key - from) / inc);
+            }
+            base += (to - from) / inc;
+        }
+        None
+    }
+
+    fn key_at(&self, index: i64) -> Option<i64> {
+        if index < 0 {
+            return None;
+        }
+        let mut base = 0;
+        for (from, to, inc) in self.bands() {
+            let count = (to - from) / inc;
+            if index <= base + count {
+                return Some(from + (index - base) * inc);
+            }
+            base += count;
+        }
+        None
+    }
+
+    fn tick_count(&self) -> i64 {
+        self.bands()
+            .iter()
+            .map(|(from, to, inc)| (to - from) / inc)
+            .sum()
+    }
+
+    /// Ticks at or either side of `key`, clamped to the ladder's range.
+    fn bracket(&self, key: i64) -> (i64, i64) {
+        let bands = self.bands();
+        let (lo, hi) = (bands[0].0, bands.last().map_or(0, |b| b.1));
+        let key = key.clamp(lo, hi);
+        let mut base = 0;
+        for (from, to, inc) in bands {
+            if key >= from && key <= to {
+                let below = base + (key - from) / inc;
+                let above = if (key - from) % inc == 0 {
+                    below
+                } else {
+                    below + 1
+                };
+                return (below, above);
+            }
+            base += (to - from) / inc;
+        }
+        (0, 0)
+    }
+
+    pub fn is_valid(&self, price: f64) -> bool {
+        let key = price_key(price);
+        // Reject prices that only land on a tick after rounding, e.g. 2.005.
+        price.is_finite()
+            && (price * 100.0 - key as f64).abs() < 1e-6
+            && self.index_of(key).is_some()
+    }
+
+    /// Snaps `price` onto the ladder. Prices outside the range clamp to the
+    /// nearest end; `None` only for non-finite input.
+    pub fn round(&self, price: f64, rounding: Rounding) -> Option<f64> {
+        if !price.is_finite() {
+            return None;
+        }
+        let key = price_key(price);
+        let (below, above) = self.bracket(key);
+        let index = match rounding {
+            Rounding::Down => below,
+            Rounding::Up => above,
+            Rounding::Nearest => {
+                let (lo, hi) = (self.key_at(below)?, self.key_at(above)?);
+                if key - lo <= hi - key {
+                    below
+                } else {
+                    above
+                }
+            }
+        };
+        self.key_at(index).map(key_price)
+    }
+
+    /// The price `n` ticks above (negative: below) a valid `price`.
+    pub fn offset(&self, price: f64, n: i64) -> Option<f64> {
+        let index = self.index_of(price_key(price))? + n;
+        (index <= self.tick_count())
+            .then(|| self.key_at(index).map(key_price))
+            .flatten()
+    }
+
+    pub fn next(&self, price: f64) -> Option<f64> {
+        self.offset(price, 1)
+    }
+
+    pub fn prev(&self, price: f64) -> Option<f64> {
+        self.offset(price, -1)
+    }
+
+    /// Signed number of ticks from `from` to `to`; both must be valid.
+    pub fn distance(&self, from: f64, to: f64) -> Option<i64> {
+        Some(self.index_of(price_key(to))? - self.index_of(price_key(from))?)
+    }
+
+    /// `Ok(price)` if it is on the ladder, otherwise an error naming the
+    /// nearest valid price.
+    pub fn validate(&self, price: f64) -> Result<f64, UiErrorPayload> {
+        if self.is_valid(price) {
+            return Ok(key_price(price_key(price)));
+        }
+        Err(UiErrorPayload::with_values(
+            "errors:validation.invalidPrice",
+            serde_json::json!({
+                "price": price,
+                "nearest": self.round(price, Rounding::Nearest),
+            }),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: TickLadder = TickLadder::LineRange {
+        min: 0.5,
+        max: 10.0,
+        interval: 1.0,
+    };
+    const NEGATIVE_LINE: TickLadder = TickLadder::LineRange {
+        min: -10.5,
+        max: 10.5,
+        interval: 1.0,
+    };
+
+    #[test]
+    fn classic_steps_across_band_boundaries() {
+        let l = TickLadder::Classic;
+        assert_eq!(l.next(1.99), Some(2.0));
+        assert_eq!(l.next(2.0), Some(2.02));
+        assert_eq!(l.prev(2.02), Some(2.0));
+        assert_eq!(l.prev(2.0), Some(1.99));
+        assert_eq!(l.distance(1.99, 2.02), Some(2));
+
+        assert_eq!(l.next(95.0), Some(100.0));
+        assert_eq!(l.next(99.0), None);
+        assert_eq!(l.next(100.0), Some(110.0));
+        assert_eq!(l.prev(110.0), Some(100.0));
+        assert_eq!(l.distance(95.0, 110.0), Some(2));
+    }
+
+    #[test]
+    fn classic_rejects_and_rounds_off_ladder_prices() {
+        let l = TickLadder::Classic;
+        assert!(!l.is_valid(2.01));
+        assert!(!l.is_valid(99.0));
+        assert!(!l.is_valid(105.0));
+        assert!(!l.is_valid(2.005));
+        assert!(!l.is_valid(f64::NAN));
+        assert_eq!(l.round(2.01, Rounding::Down), Some(2.0));
+        assert_eq!(l.round(2.01, Rounding::Up), Some(2.02));
+        // Halfway goes down.
+        assert_eq!(l.round(2.01, Rounding::Nearest), Some(2.0));
+        assert_eq!(l.round(99.0, Rounding::Nearest), Some(100.0));
+        assert_eq!(l.round(1.0, Rounding::Down), Some(MIN_ODDS));
+        assert_eq!(l.round(2000.0, Rounding::Up), Some(MAX_ODDS));
+        assert_eq!(l.round(f64::INFINITY, Rounding::Up), None);
+        assert_eq!(l.next(MAX_ODDS), None);
+        assert_eq!(l.prev(MIN_ODDS), None);
+
+        let err = l.validate(2.01).unwrap_err();
+        assert_eq!(err.key, "errors:validation.invalidPrice");
+        assert_eq!(err.values.unwrap()["nearest"], 2.0);
+        assert_eq!(l.validate(2.02).unwrap(), 2.02);
+    }
+
+    #[test]
+    fn finest_steps_by_a_hundredth_throughout() {
+        let l = TickLadder::Finest;
+        assert_eq!(l.next(1.99), Some(2.0));
+        assert_eq!(l.next(2.0), Some(2.01));
+        assert_eq!(l.next(99.0), Some(99.01));
+        assert!(l.is_valid(105.37));
+        assert_eq!(l.distance(MIN_ODDS, MAX_ODDS), Some(99_899));
+        assert_eq!(l.next(MAX_ODDS), None);
+    }
+
+    #[test]
+    fn line_range_stops_at_the_last_whole_step() {
+        // 0.5 + 9 steps of 1 is 9.5; 10 isn't on the ladder.
+        assert_eq!(LINE.min_price(), 0.5);
+        assert_eq!(LINE.max_price(), 9.5);
+        assert!(!LINE.is_valid(10.0));
+        assert!(!LINE.is_valid(1.0));
+        assert_eq!(LINE.round(10.0, Rounding::Up), Some(9.5));
+        assert_eq!(LINE.round(1.2, Rounding::Nearest), Some(1.5));
+        assert_eq!(LINE.next(9.5), None);
+        assert_eq!(LINE.odds(7.5), 2.0);
+    }
+
+    #[test]
+    fn line_range_handles_negative_minimums() {
+        let l = NEGATIVE_LINE;
+        assert_eq!(l.min_price(), -10.5);
+        assert_eq!(l.max_price(), 10.5);
+        assert_eq!(l.next(-10.5), Some(-9.5));
+        assert_eq!(l.prev(-10.5), None);
+        assert!(l.is_valid(-0.5));
+        assert!(!l.is_valid(0.0));
+        assert_eq!(l.round(0.0, Rounding::Down), Some(-0.5));
+        assert_eq!(l.round(0.0, Rounding::Up), Some(0.5));
+        assert_eq!(l.round(-20.0, Rounding::Down), Some(-10.5));
+        assert_eq!(l.distance(-10.5, 10.5), Some(21));
+    }
+
+    #[test]
+    fn ladder_type_comes_from_the_market_definition() {
+        let def = serde_json::json!({
+            "priceLadderDefinition": { "type": "LINE_RANGE" },
+            "lineMinUnit": -10.5,
+            "lineMaxUnit": 10.5,
+            "lineInterval": 1.0,
+        });
+        assert_eq!(TickLadder::from_market_definition(&def), NEGATIVE_LINE);
+        let finest = serde_json::json!({ "priceLadderDefinition": { "type": "FINEST" } });
+        assert_eq!(
+            TickLadder::from_market_definition(&finest),
+            TickLadder::Finest
+        );
+        assert_eq!(
+            TickLadder::from_market_definition(&serde_json::json!({})),
+            TickLadder::Classic
+        );
+    }
+}
@@ -1,10 +1,12 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize)]
+use crate::redact;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UiErrorPayload {
     pub key: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub values: Option<serde_json::Value>,
 }
 
@@ -19,7 +21,7 @@ impl UiErrorPayload {
     pub fn with_values(key: impl Into<String>, values: serde_json::Value) -> Self {
         Self {
             key: key.into(),
-            values: Some(values),
+            values: Some(redact::redact_json(values)),
         }
     }
 
@@ -0,0 +1,277 @@
+use betfair_stream_app::backtest::{Backtest, BacktestConfig};
+use betfair_stream_app::market::cache::MarketState;
+use betfair_stream_app::orders::place::{OrderSide, Persistence};
+use betfair_stream_app::strategy::builtin;
+use betfair_stream_app::strategy::{OrderIntent, Strategy, StrategyContext};
+use betfair_stream_app::stream::historical;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::ops::ControlFlow;
+
+const MARKET: &str = "1.100";
+
+fn frame(pt: u64, mc: Value) -> String {
+    json!({ "op": "mcm", "pt": pt, "mc": [mc] }).to_string()
+}
+
+fn definition(status: &str, in_play: bool, results: [&str; 2]) -> Value {
+    json!({
+        "status": status,
+        "inPlay": in_play,
+        "runners": [
+            { "id": 1, "status": results[0] },
+            { "id": 2, "status": results[1] },
+        ],
+    })
+}
+
+fn image(pt: u64) -> String {
+    frame(
+        pt,
+        json!({
+            "id": MARKET,
+            "img": true,
+            "marketDefinition": definition("OPEN", false, ["ACTIVE", "ACTIVE"]),
+            "rc": [
+                { "id": 1, "atb": [[2.0, 10.0]], "atl": [[2.02, 5.0]] },
+                { "id": 2, "atb": [[3.0, 10.0]], "atl": [[3.1, 10.0]] },
+            ],
+        }),
+    )
+}
+
+fn runner(pt: u64, rc: Value) -> String {
+    frame(pt, json!({ "id": MARKET, "rc": [rc] }))
+}
+
+fn settle(pt: u64) -> String {
+    frame(
+        pt,
+        json!({
+            "id": MARKET,
+            "marketDefinition": definition("CLOSED", true, ["WINNER", "LOSER"]),
+        }),
+    )
+}
+
+/// Sends one order on the first update it sees.
+struct OneOrder {
+    side: OrderSide,
+    price: f64,
+    size: f64,
+    sent: bool,
+}
+
+impl Strategy for OneOrder {
+    fn on_market(&mut self, _ctx: &StrategyContext, market: &MarketState) -> Vec<OrderIntent> {
+        if std::mem::replace(&mut self.sent, true) {
+            return Vec::new();
+        }
+        vec![OrderIntent::Place {
+            market_id: market.market_id.clone(),
+            selection_id: 1,
+            handicap: 0.0,
+            side: self.side,
+            price: self.price,
+            size: self.size,
+            persistence: Persistence::Lapse,
+        }]
+    }
+}
+
+fn one_order(side: OrderSide, price: f64, size: f64) -> Box<dyn Strategy> {
+    Box::new(OneOrder {
+        side,
+        price,
+        size,
+        sent: false,
+    })
+}
+
+#[test]
+fn resting_order_fills_after_the_queue_ahead_trades() {
+    let config = BacktestConfig {
+        latency_ms: 100,
+        commission: 0.05,
+        ..BacktestConfig::default()
+    };
+    // Back 4.00 at 2.02 joins 5.00 already waiting there.
+    let mut bt = Backtest::new(config, "test", one_order(OrderSide::Back, 2.02, 4.0));
+    bt.feed(&image(1_000));
+    bt.feed(&runner(1_050, json!({ "id": 2, "ltp": 3.0 })));
+    // Arrives before this frame; 3.00 trades, 2.00 of the queue is left.
+    bt.feed(&runner(
+        1_200,
+        json!({ "id": 1, "trd": [[2.02, 3.0]], "atl": [[2.02, 2.0]] }),
+    ));
+    // 4.00 more trades: 2.00 clears the queue, 2.00 is ours.
+    bt.feed(&runner(
+        1_300,
+        json!({ "id": 1, "trd": [[2.02, 7.0]], "atl": [[2.02, 0.0]] }),
+    ));
+    // Someone lays at our price: the rest fills.
+    bt.feed(&runner(1_400, json!({ "id": 1, "atb": [[2.02, 10.0]] })));
+    bt.feed(&settle(2_000));
+
+    let report = bt.finish();
+    let fills: Vec<(u64, f64, f64)> = report
+        .fills
+        .iter()
+        .map(|f| (f.at_ms, f.price, f.size))
+        .collect();
+    assert_eq!(fills, vec![(1_300, 2.02, 2.0), (1_400, 2.02, 2.0)]);
+    assert!(report.rejected.is_empty());
+
+    let market = &report.markets[0];
+    assert!(market.settled);
+    assert_eq!(market.matched_back, 4.0);
+    assert_eq!(market.pnl, 4.08);
+    assert_eq!(market.commission, 0.2);
+    assert_eq!(market.net, 3.88);
+    assert_eq!(report.curve.last().map(|p| p.pnl), Some(4.08));
+}
+
+#[test]
+fn latency_decides_what_the_order_sees() {
+    let config = BacktestConfig {
+        latency_ms: 100,
+        ..BacktestConfig::default()
+    };
+    // A lay at 2.04 takes the 2.02 on offer when it gets there.
+    let mut bt = Backtest::new(config, "test", one_order(OrderSide::Lay, 2.04, 3.0));
+    bt.feed(&image(1_000));
+    bt.feed(&runner(1_100, json!({ "id": 2, "ltp": 3.0 })));
+    let report = bt.finish();
+    assert_eq!(report.fills.len(), 1);
+    assert_eq!((report.fills[0].price, report.fills[0].size), (2.02, 3.0));
+
+    // The market suspends before the order arrives.
+    let mut bt = Backtest::new(config, "test", one_order(OrderSide::Lay, 2.04, 3.0));
+    bt.feed(&image(1_000));
+    bt.feed(&frame(
+        1_050,
+        json!({
+            "id": MARKET,
+            "marketDefinition": definition("SUSPENDED", false, ["ACTIVE", "ACTIVE"]),
+        }),
+    ));
+    bt.feed(&runner(1_100, json!({ "id": 2, "ltp": 3.0 })));
+    let report = bt.finish();
+    assert!(report.fills.is_empty());
+    assert_eq!(report.rejected.len(), 1);
+    assert_eq!(report.rejected[0].error.key, "errors:orders.rejected");
+}
+
+#[test]
+fn offers_taken_on_arrival_are_not_taken_again() {
+    let config = BacktestConfig {
+        latency_ms: 100,
+        ..BacktestConfig::default()
+    };
+    // A lay of 8.00 at 2.04 takes the 5.00 at 2.02 when it gets there.
+    let mut bt = Backtest::new(config, "test", one_order(OrderSide::Lay, 2.04, 8.0));
+    bt.feed(&image(1_000));
+    bt.feed(&runner(1_100, json!({ "id": 2, "ltp": 3.0 })));
+    // The recorded book still shows the 5.00 it took.
+    bt.feed(&runner(1_200, json!({ "id": 2, "ltp": 3.05 })));
+    bt.feed(&runner(1_300, json!({ "id": 1, "ltp": 2.02 })));
+    let report = bt.finish();
+    let fills: Vec<(u64, f64, f64)> = report
+        .fills
+        .iter()
+        .map(|f| (f.at_ms, f.price, f.size))
+        .collect();
+    assert_eq!(fills, vec![(1_100, 2.02, 5.0)]);
+    assert_eq!(report.markets[0].matched_lay, 5.0);
+}
+
+#[test]
+fn builtin_strategies_run_unchanged() {
+    let favourite = builtin::find("favourite-back").unwrap();
+    let mut bt = Backtest::new(
+        BacktestConfig::default(),
+        favourite.strategy_ref,
+        (favourite.create)(),
+    );
+    bt.feed(&image(1_000));
+    // The back at 2.00 matches straight away at the 2.00 on offer.
+    bt.feed(&runner(1_200, json!({ "id": 2, "ltp": 3.0 })));
+    bt.feed(&frame(
+        1_300,
+        json!({
+            "id": MARKET,
+            "marketDefinition": definition("OPEN", true, ["ACTIVE", "ACTIVE"]),
+        }),
+    ));
+    bt.feed(&settle(2_000));
+    let report = bt.finish();
+    assert_eq!(report.fills.len(), 1);
+    assert_eq!(report.markets[0].pnl, 2.0);
+    assert_eq!(report.strategy_state["placed"], json!([MARKET]));
+}
+
+fn bz2(text: &str) -> Vec<u8> {
+    let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    encoder.write_all(text.as_bytes()).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn historical_bundles_scan_and_backtest() {
+    // A tar of bz2 market files, as Betfair historical data ships them.
+    let market = [
+        image(1_000),
+        runner(1_200, json!({ "id": 2, "ltp": 3.0 })),
+        settle(2_000),
+    ];
+    let other = frame(
+        500,
+        json!({
+            "id": "1.200",
+            "img": true,
+            "marketDefinition": { "status": "OPEN", "eventName": "Ascot", "runners": [] },
+        }),
+    );
+    let mut bundle = tar::Builder::new(Vec::new());
+    for (name, text) in [("1.100.bz2", market.join("\n")), ("1.200.bz2", other)] {
+        let data = bz2(&text);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        bundle
+            .append_data(&mut header, name, data.as_slice())
+            .unwrap();
+    }
+    let path = std::env::temp_dir().join(format!("historical-{}.tar", std::process::id()));
+    std::fs::write(&path, bundle.into_inner().unwrap()).unwrap();
+
+    let summary = historical::scan(&path).unwrap();
+    assert_eq!((summary.files, summary.frames, summary.skipped), (2, 4, 0));
+    let ids: Vec<&str> = summary
+        .markets
+        .iter()
+        .map(|m| m.market_id.as_str())
+        .collect();
+    assert_eq!(ids, [MARKET, "1.200"]);
+    assert_eq!(summary.markets[0].status.as_deref(), Some("CLOSED"));
+    assert_eq!(summary.markets[0].winners, [1]);
+    assert_eq!(summary.markets[1].event_name.as_deref(), Some("Ascot"));
+
+    // The second file starts earlier; the clock follows it back.
+    let favourite = builtin::find("favourite-back").unwrap();
+    let mut bt = Backtest::new(
+        BacktestConfig::default(),
+        favourite.strategy_ref,
+        (favourite.create)(),
+    );
+    historical::for_each_line(&path, |line, _| {
+        bt.feed(line);
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let report = bt.finish();
+    assert_eq!(report.frames, 4);
+    assert_eq!(report.markets[0].pnl, 2.0);
+}
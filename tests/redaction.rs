@@ -0,0 +1,150 @@
+use betfair_stream_app::betfair::rpc::call_json_rpc;
+use betfair_stream_app::redact;
+use betfair_stream_app::stream::{authentication_message, run_writer};
+use betfair_stream_app::ui_error::UiErrorPayload;
+use std::io;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::fmt::MakeWriter;
+
+const APP_KEY: &str = "TestAppKey0123456789";
+const SESSION: &str = "TestSessionToken+abcdefghijklmnop=";
+const USERNAME: &str = "trader.jane@example.com";
+
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+    }
+}
+
+impl io::Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Capture {
+    type Writer = Capture;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Same formatter/writer stack as `logging.rs`, writing into a buffer.
+fn capture_logs() -> (Capture, tracing::subscriber::DefaultGuard) {
+    redact::register_secret(APP_KEY);
+    redact::register_secret(SESSION);
+    redact::register_secret(USERNAME);
+
+    let capture = Capture::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_ansi(false)
+        .fmt_fields(redact::field_formatter())
+        .with_writer(redact::RedactingMakeWriter::new(capture.clone()))
+        .finish();
+    let guard = tracing::subscriber::set_default(subscriber);
+    (capture, guard)
+}
+
+fn assert_no_secrets(logs: &str) {
+    for secret in [APP_KEY, SESSION, USERNAME] {
+        assert!(!logs.contains(secret), "secret leaked into logs:\n{logs}");
+    }
+}
+
+#[tokio::test]
+async fn gateway_request_logs_never_contain_credentials() {
+    let (capture, _guard) = capture_logs();
+
+    // Point api.betfair.com at a local socket that drops the connection, so the
+    // real request/log path runs without network access.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((sock, _)) = listener.accept().await {
+            drop(sock);
+        }
+    });
+    let http = reqwest::Client::builder()
+        .resolve("api.betfair.com", addr)
+        .build()
+        .unwrap();
+
+    let result = call_json_rpc(
+        &http,
+        APP_KEY,
+        SESSION,
+        "betting",
+        "listMarketCatalogue",
+        serde_json::json!({ "filter": {}, "maxResults": 1 }),
+    )
+    .await;
+    assert!(result.is_err());
+
+    let logs = capture.text();
+    assert!(logs.contains("betfair json-rpc request"), "{logs}");
+    assert!(logs.contains(redact::REDACTED), "{logs}");
+    assert_no_secrets(&logs);
+}
+
+#[tokio::test]
+async fn stream_writer_logs_never_contain_credentials() {
+    let (capture, _guard) = capture_logs();
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<String>(4);
+    tx.send(authentication_message(APP_KEY, SESSION))
+        .await
+        .unwrap();
+    tx.send(r#"{"op":"heartbeat","id":2}"#.to_string())
+        .await
+        .unwrap();
+    drop(tx);
+    run_writer(futures_util::sink::drain(), rx).await;
+
+    let logs = capture.text();
+    assert_eq!(logs.matches("stream_out").count(), 2, "{logs}");
+    assert_no_secrets(&logs);
+}
+
+#[test]
+fn secret_named_fields_are_masked_even_when_unregistered() {
+    let (capture, _guard) = capture_logs();
+
+    tracing::info!(
+        password = "hunter2-unregistered",
+        account_id = 987654321u64,
+        username = %redact::secret("someone-else"),
+        "login attempt"
+    );
+
+    let logs = capture.text();
+    assert!(logs.contains("login attempt"), "{logs}");
+    assert!(!logs.contains("hunter2-unregistered"), "{logs}");
+    assert!(!logs.contains("987654321"), "{logs}");
+    assert!(!logs.contains("someone-else"), "{logs}");
+}
+
+#[test]
+fn error_payloads_mask_secret_keys_and_values() {
+    redact::register_secret(SESSION);
+
+    let payload = UiErrorPayload::with_values(
+        "errors:unexpected.withDetails",
+        serde_json::json!({
+            "details": format!("upstream echoed {SESSION}"),
+            "nested": { "appKey": "anything", "sessionToken": "anything" }
+        }),
+    );
+    let text = serde_json::to_string(&payload).unwrap();
+    assert!(!text.contains(SESSION), "{text}");
+    assert!(!text.contains("anything"), "{text}");
+}
@@ -59,7 +59,9 @@ function App() {
                   selectedMarket={model.selectedMarket}
                   onRefreshMarkets={model.refreshMarkets}
                   onSelectMarket={model.setSelectedMarketId}
-                  bestBackLayBySelectionId={model.bestBackLayBySelectionId}
+                  bestBackLayByRunner={model.bestBackLayByRunner}
+                  pnlByRunner={model.pnlByRunner}
+                  lineMarket={model.lineMarket}
                   snapshotConnected={model.snapshotConnected}
                 />
               }
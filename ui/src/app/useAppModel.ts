@@ -1,9 +1,12 @@
-import { useMemo } from 'react'
+import { useEffect, useMemo } from 'react'
 import type { MarketCatalogue } from '../types/betfair'
+import type { BestBackLay } from '../lib/streamState'
 import { useAppSnackbar } from '../hooks/useAppSnackbar'
 import { useMarkets } from '../hooks/useMarkets'
 import { useMarketStream } from '../hooks/useMarketStream'
 import { useSession } from '../hooks/useSession'
+import type { UiMessage } from '../lib/streamClient'
+import { tauriInvoke } from '../lib/tauri'
 
 export type AppModel = {
   // Session
@@ -20,14 +23,9 @@ export type AppModel = {
   selectedMarket: MarketCatalogue | null
 
   // Stream-derived view model
-  bestBackLayBySelectionId: Map<
-    number,
-    {
-      back: Array<{ price: number; size: number }>
-      lay: Array<{ price: number; size: number }>
-      ltp?: number
-    }
-  >
+  bestBackLayByRunner: Map<string, BestBackLay>
+  pnlByRunner: Map<string, number>
+  lineMarket: boolean
   snapshotConnected: boolean
 
   // Notifications
@@ -51,6 +49,18 @@ export function useAppModel(): AppModel {
     onError: (m) => snackbar.showFromUiMessage('error', m),
   })
 
+  const { showFromUiMessage } = snackbar
+  useEffect(() => {
+    // Saved stops that failed to load are otherwise only in the log.
+    void tauriInvoke<{ loadError?: UiMessage }>('stops_status')
+      .then((status) => {
+        if (status?.loadError) showFromUiMessage('error', status.loadError)
+      })
+      .catch(() => {
+        // ignore
+      })
+  }, [showFromUiMessage])
+
   const selectedMarket = useMemo(() => {
     return markets.markets.find((m) => m.marketId === stream.selectedMarketId) ?? null
   }, [markets.markets, stream.selectedMarketId])
@@ -79,7 +89,9 @@ export function useAppModel(): AppModel {
     setSelectedMarketId: stream.setSelectedMarketId,
     selectedMarket,
 
-    bestBackLayBySelectionId: stream.bestBackLayBySelectionId,
+    bestBackLayByRunner: stream.bestBackLayByRunner,
+    pnlByRunner: stream.pnlByRunner,
+    lineMarket: stream.lineMarket,
     snapshotConnected: stream.snapshotConnected,
 
     snackbar: snackbar.snackbar,
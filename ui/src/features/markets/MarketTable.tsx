@@ -13,20 +13,16 @@ import {
 } from '@mui/material'
 import { useTranslation } from 'react-i18next'
 import type { MarketCatalogue } from '../../types/betfair'
+import { runnerKey, type BestBackLay } from '../../lib/streamState'
 import { formatMoney } from '../../lib/format'
 import { LtpCell } from './cells/LtpCell'
 import { PriceAmountCell } from './cells/PriceAmountCell'
 
 export function MarketTable(props: {
   selectedMarket: MarketCatalogue | null
-  bestBackLayBySelectionId: Map<
-    number,
-    {
-      back: Array<{ price: number; size: number }>
-      lay: Array<{ price: number; size: number }>
-      ltp?: number
-    }
-  >
+  bestBackLayByRunner: Map<string, BestBackLay>
+  pnlByRunner: Map<string, number>
+  lineMarket: boolean
   snapshotConnected: boolean
 }): React.ReactNode {
   const theme = useTheme()
@@ -85,12 +81,15 @@ export function MarketTable(props: {
           <TableHead>
             <TableRow>
               <TableCell sx={{ fontWeight: 800, width: { xs: 160, sm: 220 } }}>{t('markets:table.selection')}</TableCell>
+              <TableCell align="right" sx={{ fontWeight: 800, width: 80 }}>
+                {t('markets:table.pnl')}
+              </TableCell>
               <TableCell
                 align="center"
                 colSpan={3}
                 sx={{ fontWeight: 800, bgcolor: alpha(theme.palette.info.main, 0.18) }}
               >
-                {t('markets:table.back')}
+                {t(props.lineMarket ? 'markets:table.under' : 'markets:table.back')}
               </TableCell>
               <TableCell align="center" sx={{ fontWeight: 800 }}>
                 {t('markets:table.ltp')}
@@ -100,7 +99,7 @@ export function MarketTable(props: {
                 colSpan={3}
                 sx={{ fontWeight: 800, bgcolor: alpha(theme.palette.error.main, 0.18) }}
               >
-                {t('markets:table.lay')}
+                {t(props.lineMarket ? 'markets:table.over' : 'markets:table.lay')}
               </TableCell>
             </TableRow>
           </TableHead>
@@ -109,7 +108,7 @@ export function MarketTable(props: {
               .slice()
               .sort((a, b) => (a.sortPriority ?? 0) - (b.sortPriority ?? 0))
               .map((r) => {
-                const best = props.bestBackLayBySelectionId.get(r.selectionId)
+                const best = props.bestBackLayByRunner.get(runnerKey(r.selectionId, r.handicap))
                 const b1 = best?.back[0]
                 const b2 = best?.back[1]
                 const b3 = best?.back[2]
@@ -117,9 +116,11 @@ export function MarketTable(props: {
                 const l2 = best?.lay[1]
                 const l3 = best?.lay[2]
                 const ltp = best?.ltp
+                const removed = best?.removedFactor !== undefined
+                const pnl = props.pnlByRunner.get(runnerKey(r.selectionId, r.handicap))
 
                 return (
-                  <TableRow key={r.selectionId} hover>
+                  <TableRow key={runnerKey(r.selectionId, r.handicap)} hover sx={{ opacity: removed ? 0.5 : 1 }}>
                     <TableCell
                       sx={{
                         fontWeight: 700,
@@ -130,6 +131,17 @@ export function MarketTable(props: {
                       }}
                     >
                       {r.runnerName}
+                      {removed && ` (${t('markets:table.removed', { factor: best?.removedFactor })})`}
+                    </TableCell>
+                    <TableCell
+                      align="right"
+                      sx={{
+                        fontWeight: 700,
+                        width: 80,
+                        color: pnl === undefined ? 'text.secondary' : pnl < 0 ? 'error.main' : 'success.main',
+                      }}
+                    >
+                      {formatMoney(pnl, dash)}
                     </TableCell>
                     <TableCell align="center" sx={{ bgcolor: alpha(theme.palette.info.main, 0.1), width: 92 }}>
                       <PriceAmountCell price={b3?.price} amount={b3?.size} formatAmount={formatAmount} />
@@ -1,5 +1,6 @@
 import { Paper, Stack } from '@mui/material'
 import type { MarketCatalogue } from '../../types/betfair'
+import type { BestBackLay } from '../../lib/streamState'
 import { MarketList } from './MarketList'
 import { MarketTable } from './MarketTable'
 
@@ -10,14 +11,9 @@ export function MarketsView(props: {
   selectedMarket: MarketCatalogue | null
   onRefreshMarkets: () => void
   onSelectMarket: (marketId: string) => void
-  bestBackLayBySelectionId: Map<
-    number,
-    {
-      back: Array<{ price: number; size: number }>
-      lay: Array<{ price: number; size: number }>
-      ltp?: number
-    }
-  >
+  bestBackLayByRunner: Map<string, BestBackLay>
+  pnlByRunner: Map<string, number>
+  lineMarket: boolean
   snapshotConnected: boolean
 }): React.ReactNode {
   return (
@@ -34,7 +30,9 @@ export function MarketsView(props: {
         <Paper variant="outlined" sx={{ p: 1.5, flex: 1, minWidth: 0, minHeight: 0, display: 'flex', flexDirection: 'column' }}>
           <MarketTable
             selectedMarket={props.selectedMarket}
-            bestBackLayBySelectionId={props.bestBackLayBySelectionId}
+            bestBackLayByRunner={props.bestBackLayByRunner}
+            pnlByRunner={props.pnlByRunner}
+            lineMarket={props.lineMarket}
             snapshotConnected={props.snapshotConnected}
           />
         </Paper>
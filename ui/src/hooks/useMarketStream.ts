@@ -1,5 +1,11 @@
 import { useCallback, useEffect, useMemo, useRef, useState } from 'react'
-import type { MarketSnapshot } from '../lib/streamState'
+import {
+  runnerKey,
+  type BestBackLay,
+  type LadderSource,
+  type MarketPosition,
+  type MarketSnapshot,
+} from '../lib/streamState'
 import type { UiMessage } from '../lib/streamClient'
 import { TauriStreamClient } from '../lib/streamClientTauri'
 
@@ -8,14 +14,16 @@ export type MarketStreamState = {
   setSelectedMarketId: (marketId: string) => void
   snapshot: MarketSnapshot | null
   snapshotConnected: boolean
-  bestBackLayBySelectionId: Map<
-    number,
-    {
-      back: Array<{ price: number; size: number }>
-      lay: Array<{ price: number; size: number }>
-      ltp?: number
-    }
-  >
+  ladderSource: LadderSource
+  setLadderSource: (source: LadderSource) => void
+  /** The shown prices include virtual bets and lag the book slightly. */
+  pricesVirtual: boolean
+  /** LINE market: prices are line values, back = under (sell), lay = over (buy). */
+  lineMarket: boolean
+  /** Keyed by `runnerKey(selectionId, handicap)`. */
+  bestBackLayByRunner: Map<string, BestBackLay>
+  /** "If wins" P&L keyed by `runnerKey`; empty without matched bets. */
+  pnlByRunner: Map<string, number>
   disconnect: () => void
 }
 
@@ -24,15 +32,18 @@ export function useMarketStream(args: {
   onInfo?: (m: UiMessage) => void
   onError?: (m: UiMessage) => void
 }): MarketStreamState {
-  const streamRef = useRef<{ disconnect: () => void; subscribeToMarket: (marketId: string) => void } | null>(null)
+  const streamRef = useRef<TauriStreamClient | null>(null)
   const [selectedMarketId, setSelectedMarketIdState] = useState<string>('')
+  const [ladderSource, setLadderSourceState] = useState<LadderSource>('direct')
   const [snapshot, setSnapshot] = useState<MarketSnapshot | null>(null)
+  const [position, setPosition] = useState<MarketPosition | null>(null)
 
   const ensureClient = useCallback(() => {
     if (streamRef.current) return streamRef.current
 
     const client = new TauriStreamClient({
       onSnapshot: (s) => setSnapshot(s),
+      onPosition: (p) => setPosition(p),
       onInfo: args.onInfo,
       onError: args.onError,
     })
@@ -45,6 +56,7 @@ export function useMarketStream(args: {
     streamRef.current?.disconnect()
     streamRef.current = null
     setSnapshot(null)
+    setPosition(null)
     setSelectedMarketIdState('')
   }, [])
 
@@ -52,6 +64,7 @@ export function useMarketStream(args: {
     (marketId: string) => {
       setSelectedMarketIdState(marketId)
       setSnapshot(null)
+      setPosition(null)
 
       if (!args.isAuthed) return
 
@@ -61,6 +74,15 @@ export function useMarketStream(args: {
     [args.isAuthed, ensureClient],
   )
 
+  const setLadderSource = useCallback(
+    (source: LadderSource) => {
+      setLadderSourceState(source)
+      if (!args.isAuthed) return
+      ensureClient().setLadderSource(source)
+    },
+    [args.isAuthed, ensureClient],
+  )
+
   useEffect(() => {
     return () => {
       streamRef.current?.disconnect()
@@ -68,32 +90,46 @@ export function useMarketStream(args: {
     }
   }, [])
 
-  const bestBackLayBySelectionId = useMemo(() => {
-    const map = new Map<
-      number,
-      {
-        back: Array<{ price: number; size: number }>
-        lay: Array<{ price: number; size: number }>
-        ltp?: number
-      }
-    >()
+  const bestBackLayByRunner = useMemo(() => {
+    const map = new Map<string, BestBackLay>()
 
     if (!snapshot) return map
+    const virtual = snapshot.ladder === 'virtual'
     for (const r of snapshot.runners) {
-      const back = (r.batb ?? []).slice(0, 3).map((x) => ({ price: x.price, size: x.size }))
-      const lay = (r.batl ?? []).slice(0, 3).map((x) => ({ price: x.price, size: x.size }))
-      map.set(r.selectionId, { back, lay, ltp: r.ltp })
+      const backLadder = virtual ? r.bdatb : r.batb
+      const layLadder = virtual ? r.bdatl : r.batl
+      const back = (backLadder ?? []).slice(0, 3).map((x) => ({ price: x.price, size: x.size }))
+      const lay = (layLadder ?? []).slice(0, 3).map((x) => ({ price: x.price, size: x.size }))
+      map.set(runnerKey(r.selectionId, r.handicap), {
+        back,
+        lay,
+        ltp: r.ltp,
+        removedFactor: r.removal ? (r.removal.adjustmentFactor ?? 0) : undefined,
+      })
     }
 
     return map
   }, [snapshot])
 
+  const pnlByRunner = useMemo(() => {
+    const map = new Map<string, number>()
+    const hasBets = position?.runners.some((r) => r.ifWins !== 0)
+    if (!position || !hasBets) return map
+    for (const r of position.runners) map.set(runnerKey(r.selectionId, r.handicap), r.ifWins)
+    return map
+  }, [position])
+
   return {
     selectedMarketId,
     setSelectedMarketId,
     snapshot,
     snapshotConnected: Boolean(snapshot),
-    bestBackLayBySelectionId,
+    ladderSource,
+    setLadderSource,
+    pricesVirtual: snapshot?.ladder === 'virtual',
+    lineMarket: snapshot?.tickLadder?.type === 'lineRange',
+    bestBackLayByRunner,
+    pnlByRunner,
     disconnect,
   }
 }
@@ -1,4 +1,4 @@
-import type { ListMarketCatalogueResponse } from '../types/betfair'
+import type { ListMarketCatalogueResponse, MarketCatalogue } from '../types/betfair'
 import { UiError } from '../errors/UiError'
 import { tauriInvoke } from './tauri'
 
@@ -75,27 +75,98 @@ export async function betfairLogin(args: LoginArgs): Promise<void> {
   }
 }
 
-export async function listNextHorseWinMarkets(): Promise<ListMarketCatalogueResponse> {
-  const nowIso = new Date().toISOString()
-
-  const requestBody = {
-    filter: {
-      eventTypeIds: ['7'],
-      marketTypeCodes: ['WIN'],
-      marketStartTime: { from: nowIso },
-    },
-    maxResults: '100',
-    marketProjection: ['RUNNER_DESCRIPTION', 'EVENT', 'MARKET_START_TIME'],
-    sort: 'FIRST_TO_START',
-  }
+export type CatalogueQuery = {
+  text?: string
+  venue?: string
+  eventName?: string
+  runnerName?: string
+  eventTypeIds?: string[]
+  marketTypes?: string[]
+  countryCodes?: string[]
+  startFromMs?: number
+  startToMs?: number
+  includeClosed?: boolean
+  limit?: number
+}
 
+export type CatalogueRefresh = {
+  listed: number
+  fetched: number
+  pruned: number
+  stored: number
+}
+
+async function invokeUi<T>(cmd: string, args: Record<string, unknown>): Promise<T> {
   try {
-    return await tauriInvoke<ListMarketCatalogueResponse>('betfair_rpc', {
-      args: { service: 'betting', method: 'listMarketCatalogue', params: requestBody },
-    })
+    return await tauriInvoke<T>(cmd, args)
   } catch (e) {
     const extracted = extractInvokeUiError(e)
     if (extracted) throw new UiError(extracted)
     throw e
   }
 }
+
+// Lists markets on Betfair and fetches catalogue entries the backend lacks.
+export async function refreshCatalogue(filter: Record<string, unknown>, maxResults = 100): Promise<CatalogueRefresh> {
+  return invokeUi<CatalogueRefresh>('catalogue_refresh', { args: { filter, maxResults } })
+}
+
+// Searches the backend catalogue without calling Betfair.
+export async function searchCatalogue(query: CatalogueQuery): Promise<ListMarketCatalogueResponse> {
+  return invokeUi<ListMarketCatalogueResponse>('catalogue_search', { query })
+}
+
+export async function listNextHorseWinMarkets(): Promise<ListMarketCatalogueResponse> {
+  const now = Date.now()
+  await refreshCatalogue({
+    eventTypeIds: ['7'],
+    marketTypeCodes: ['WIN'],
+    marketStartTime: { from: new Date(now).toISOString() },
+  })
+  return searchCatalogue({ eventTypeIds: ['7'], marketTypes: ['WIN'], startFromMs: now, limit: 100 })
+}
+
+export type NavNodeKind = 'eventType' | 'competition' | 'event' | 'market'
+
+export type NavNodeRef = { kind: NavNodeKind; id: string }
+
+export type NavNode = NavNodeRef & {
+  name: string
+  marketCount?: number
+  countryCode?: string
+  venue?: string
+  startTime?: string
+  marketType?: string
+  status?: string
+  inPlay?: boolean
+}
+
+export type NavLevel = {
+  parent?: NavNodeRef
+  marketTypes: string[]
+  children: NavNode[]
+  fetchedMs: number
+  expiresMs: number
+}
+
+// Children of a navigation tree node (event types when `parent` is unset),
+// cached by the backend until the level's TTL runs out.
+export async function navigationChildren(
+  parent?: NavNodeRef,
+  opts: { marketTypes?: string[]; refresh?: boolean } = {},
+): Promise<NavLevel> {
+  return invokeUi<NavLevel>('navigation_children', {
+    args: { parent, marketTypes: opts.marketTypes ?? [], refresh: opts.refresh ?? false },
+  })
+}
+
+export type MarketTypeCount = { marketType: string; marketCount: number }
+
+export async function navigationMarketTypes(eventTypeId?: string): Promise<MarketTypeCount[]> {
+  return invokeUi<MarketTypeCount[]>('navigation_market_types', { eventTypeId })
+}
+
+// Catalogue entry of a market picked in the tree, to select and subscribe it.
+export async function catalogueMarket(marketId: string): Promise<MarketCatalogue | null> {
+  return invokeUi<MarketCatalogue | null>('catalogue_market', { marketId })
+}
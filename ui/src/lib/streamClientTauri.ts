@@ -1,16 +1,17 @@
 import {
-  applyMcm,
-  emptyMarketState,
-  toSnapshot,
+  applyMarketUpdate,
+  type LadderSource,
   type MarketSnapshot,
-  type MarketState,
-  type StreamMcmMessage,
+  type MarketPosition,
+  type MarketUpdate,
+  type RunnerRemoval,
 } from './streamState'
 import type { UiMessage } from './streamClient'
 import { tauriInvoke, tauriListen } from './tauri'
 
 type StatusMessage = {
-  op?: 'status'
+  conn?: number
+  role?: { kind: 'market' | 'orders'; slot?: number }
   id?: number
   statusCode?: 'SUCCESS' | 'FAILURE'
   errorCode?: string
@@ -18,30 +19,56 @@ type StatusMessage = {
   connectionClosed?: boolean
 }
 
+type MarketsDropped = {
+  limit: number
+  subscribed: number
+  dropped: Array<{ marketId: string; reason: 'limit' | 'closed'; subscribers: string[] }>
+}
+
+type RunnerRemoved = RunnerRemoval & {
+  marketId: string
+  totalReductionFactor: number
+  /** Set on events from a historical replay. */
+  replay?: boolean
+}
+
+type MarketLifecycle = { marketId: string; atMs: number; replay?: boolean } & (
+  | { type: 'inPlay' | 'suspended' | 'reopened' | 'closed' }
+  | { type: 'betDelayChanged'; from: number; to: number }
+  | { type: 'runnerSettled'; selectionId: number; handicap: number; status: string }
+)
+
 type ConnectionMessage = {
   op?: 'connection'
   connectionId?: string
 }
 
-const EVENT_STREAM_LINE = 'betfair_stream_line'
+const EVENT_STREAM_STATUS = 'betfair_stream_status'
+const EVENT_STREAM_CONNECTION = 'betfair_stream_connection'
+const EVENT_MARKET_UPDATE = 'betfair_market_update'
+const EVENT_MARKETS_DROPPED = 'betfair_markets_dropped'
+const EVENT_RUNNER_REMOVED = 'betfair_runner_removed'
+const EVENT_MARKET_LIFECYCLE = 'betfair_market_lifecycle'
+const EVENT_POSITION = 'betfair_position'
 const AUTH_REQUEST_ID = 1
+// Updates for this client's markets are routed to `betfair_market_update/main`.
+const SUBSCRIBER_ID = 'main'
 
 export type TauriStreamClientOptions = {
   onSnapshot: (snapshot: MarketSnapshot) => void
+  onPosition?: (position: MarketPosition) => void
   onInfo?: (message: UiMessage) => void
   onError?: (error: UiMessage) => void
 }
 
 export class TauriStreamClient {
   private readonly opts: TauriStreamClientOptions
-  private nextId = 1000
-  private authenticated = false
   private currentMarketId: string | null = null
-  private currentSubscriptionId: number | null = null
-  private marketState: MarketState | undefined
+  private snapshot: MarketSnapshot | undefined
   private pendingAuth = true
-  private unlisten: (() => void) | null = null
+  private unlisten: Array<() => void> = []
   private connected = false
+  private ladderSource: LadderSource = 'direct'
 
   constructor(opts: TauriStreamClientOptions) {
     this.opts = opts
@@ -51,25 +78,21 @@ export class TauriStreamClient {
     if (this.connected) return
 
     this.connected = true
-    this.authenticated = false
-    this.currentSubscriptionId = null
     this.pendingAuth = true
 
     void this.start()
   }
 
   disconnect(): void {
-    this.unlisten?.()
-    this.unlisten = null
+    for (const unlisten of this.unlisten) unlisten()
+    this.unlisten = []
 
     this.connected = false
-    this.authenticated = false
     this.currentMarketId = null
-    this.currentSubscriptionId = null
-    this.marketState = undefined
+    this.snapshot = undefined
     this.pendingAuth = false
 
-    void tauriInvoke<void>('stream_disconnect').catch(() => {
+    void tauriInvoke<void>('stream_unsubscribe_markets', { subscriberId: SUBSCRIBER_ID }).catch(() => {
       // ignore
     })
   }
@@ -82,20 +105,29 @@ export class TauriStreamClient {
       return
     }
 
-    if (!this.authenticated) {
-      return
-    }
-
     this.sendMarketSubscription(marketId)
   }
 
   private async start(): Promise<void> {
     try {
-      this.unlisten = await tauriListen<string>(EVENT_STREAM_LINE, (line) => {
-        this.handleLine(line)
-      })
-
-      await tauriInvoke<void>('stream_connect')
+      this.unlisten = await Promise.all([
+        tauriListen<ConnectionMessage>(EVENT_STREAM_CONNECTION, (c) => {
+          if (c.connectionId) this.opts.onInfo?.({ key: 'errors:stream.connectionId', values: { id: c.connectionId } })
+        }),
+        tauriListen<StatusMessage>(EVENT_STREAM_STATUS, (msg) => this.handleStatus(msg)),
+        tauriListen<MarketUpdate>(`${EVENT_MARKET_UPDATE}/${SUBSCRIBER_ID}`, (update) =>
+          this.handleMarketUpdate(update),
+        ),
+        tauriListen<MarketsDropped>(EVENT_MARKETS_DROPPED, (e) => this.handleDropped(e)),
+        tauriListen<RunnerRemoved>(EVENT_RUNNER_REMOVED, (e) => this.handleRunnerRemoved(e)),
+        tauriListen<MarketLifecycle>(EVENT_MARKET_LIFECYCLE, (e) => this.handleLifecycle(e)),
+        tauriListen<MarketPosition>(EVENT_POSITION, (p) => {
+          if (p.marketId === this.currentMarketId) this.opts.onPosition?.(p)
+        }),
+      ])
+
+      // Rust opens and authenticates connections as subscriptions need them.
+      if (this.currentMarketId) this.sendMarketSubscription(this.currentMarketId)
       this.opts.onInfo?.({ key: 'errors:stream.websocketConnected' })
     } catch (e) {
       this.opts.onError?.({ key: 'errors:stream.websocketError' })
@@ -103,37 +135,14 @@ export class TauriStreamClient {
     }
   }
 
-  private handleLine(line: string): void {
-    const parsed = safeJsonParse(line)
-    if (!parsed) return
-
-    if (parsed.op === 'connection') {
-      const c = parsed as ConnectionMessage
-      if (c.connectionId) this.opts.onInfo?.({ key: 'errors:stream.connectionId', values: { id: c.connectionId } })
-      return
-    }
-
-    if (parsed.op === 'status') {
-      this.handleStatus(parsed as StatusMessage)
-      return
-    }
-
-    if (parsed.op === 'mcm') {
-      this.handleMcm(parsed as StreamMcmMessage)
-    }
-  }
-
   private handleStatus(msg: StatusMessage): void {
-    // Auth status: the Rust side sends auth with id=1.
+    if (msg.role && msg.role.kind !== 'market') return
+
+    // Auth status: the Rust side sends auth with id=1 and subscribes itself on success.
     if (this.pendingAuth && (msg.id === AUTH_REQUEST_ID || typeof msg.id !== 'number')) {
       if (msg.statusCode === 'SUCCESS') {
-        this.authenticated = true
         this.pendingAuth = false
         this.opts.onInfo?.({ key: 'errors:stream.authenticated' })
-
-        if (this.currentMarketId) {
-          this.sendMarketSubscription(this.currentMarketId)
-        }
       } else if (msg.statusCode === 'FAILURE') {
         this.pendingAuth = false
         const details = msg.errorMessage ?? msg.errorCode
@@ -158,51 +167,66 @@ export class TauriStreamClient {
     }
   }
 
-  private handleMcm(msg: StreamMcmMessage): void {
-    if (!this.currentMarketId || !this.currentSubscriptionId) return
+  private handleMarketUpdate(update: MarketUpdate): void {
+    if (!this.currentMarketId || update.marketId !== this.currentMarketId) return
 
-    const next = applyMcm(this.marketState, msg, {
-      currentSubscriptionId: this.currentSubscriptionId,
-      selectedMarketId: this.currentMarketId,
-    })
+    this.snapshot = applyMarketUpdate(this.snapshot, update)
+    this.opts.onSnapshot(this.snapshot)
+  }
 
-    if (!next) return
-    this.marketState = next
-    this.opts.onSnapshot(toSnapshot(next))
+  private handleDropped(event: MarketsDropped): void {
+    const mine = event.dropped.find((d) => d.marketId === this.currentMarketId)
+    if (mine) this.opts.onInfo?.({ key: 'errors:stream.marketDropped', values: { marketId: mine.marketId, reason: mine.reason } })
   }
 
-  private sendMarketSubscription(marketId: string): void {
-    const id = this.nextId++
-    this.currentSubscriptionId = id
-    this.marketState = emptyMarketState(marketId)
-    this.opts.onSnapshot(toSnapshot(this.marketState))
-
-    const line = JSON.stringify({
-      op: 'marketSubscription',
-      id,
-      segmentationEnabled: true,
-      conflateMs: 0,
-      heartbeatMs: 5000,
-      marketFilter: {
-        marketIds: [marketId],
-      },
-      marketDataFilter: {
-        ladderLevels: 3,
-        fields: ['EX_MARKET_DEF', 'EX_LTP', 'EX_BEST_OFFERS'],
-      },
+  private handleRunnerRemoved(event: RunnerRemoved): void {
+    if (event.replay || event.marketId !== this.currentMarketId) return
+    this.opts.onError?.({
+      key: 'errors:stream.runnerRemoved',
+      values: { selectionId: event.selectionId, factor: event.adjustmentFactor ?? 0 },
     })
+  }
 
-    void tauriInvoke<void>('stream_send', { line }).catch(() => {
+  private handleLifecycle(event: MarketLifecycle): void {
+    if (event.replay || event.marketId !== this.currentMarketId) return
+    switch (event.type) {
+      case 'betDelayChanged':
+        this.opts.onInfo?.({ key: 'errors:stream.lifecycle.betDelayChanged', values: { from: event.from, to: event.to } })
+        break
+      case 'runnerSettled':
+        this.opts.onInfo?.({
+          key: 'errors:stream.lifecycle.runnerSettled',
+          values: { selectionId: event.selectionId, status: event.status },
+        })
+        break
+      default:
+        this.opts.onInfo?.({ key: `errors:stream.lifecycle.${event.type}` })
+    }
+  }
+
+  setLadderSource(source: LadderSource): void {
+    this.ladderSource = source
+    if (!this.connected) return
+    void tauriInvoke<void>('stream_set_ladder_source', {
+      args: { subscriberId: SUBSCRIBER_ID, source },
+    }).catch(() => {
       this.opts.onError?.({ key: 'errors:stream.websocketError' })
     })
   }
-}
 
-function safeJsonParse(data: unknown): any | null {
-  try {
-    if (typeof data !== 'string') return null
-    return JSON.parse(data)
-  } catch {
-    return null
+  private sendMarketSubscription(marketId: string): void {
+    this.snapshot = { marketId, runners: [] }
+    this.opts.onSnapshot(this.snapshot)
+
+    void tauriInvoke<void>('stream_subscribe_markets', {
+      args: { subscriberId: SUBSCRIBER_ID, marketIds: [marketId] },
+    })
+      .then(() => {
+        // Unsubscribing forgets the view's ladder choice, so restate it.
+        if (this.ladderSource !== 'direct') this.setLadderSource(this.ladderSource)
+      })
+      .catch(() => {
+        this.opts.onError?.({ key: 'errors:stream.websocketError' })
+      })
   }
 }
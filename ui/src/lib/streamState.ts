@@ -45,17 +45,72 @@ export type MarketState = {
   runners: Map<number, RunnerState>
 }
 
+/** Which best-offers ladder a view shows: `batb`/`batl` or virtual `bdatb`/`bdatl`. */
+export type LadderSource = 'direct' | 'virtual'
+
+/** Price ladder from the market definition; `lineRange` marks a LINE market. */
+export type TickLadder =
+  | { type: 'classic' }
+  | { type: 'finest' }
+  | { type: 'lineRange'; min: number; max: number; interval: number }
+
+/** Runners are unique by selection id and handicap (Asian handicap markets repeat selections). */
+export function runnerKey(selectionId: number, handicap?: number): string {
+  return `${selectionId}:${handicap ?? 0}`
+}
+
+export type RunnerRemoval = {
+  selectionId: number
+  handicap: number
+  removedAtMs: number
+  adjustmentFactor?: number
+}
+
+export type BestBackLay = {
+  back: Array<{ price: number; size: number }>
+  lay: Array<{ price: number; size: number }>
+  ltp?: number
+  /** Withdrawn runner: reduction factor in percent, 0 if Betfair gave none. */
+  removedFactor?: number
+}
+
+/** `betfair_position`: P&L per selection from our matched bets, before commission. */
+export type MarketPosition = {
+  marketId: string
+  runners: Array<{
+    selectionId: number
+    handicap: number
+    ifWins: number
+    hedge?: { side: 'BACK' | 'LAY'; price: number; stake: number; profit: number }
+  }>
+  liability: number
+  unmatchedLiability: number
+  greenProfit?: number
+  realised?: number
+}
+
 export type MarketSnapshot = {
   marketId: string
   publishTime?: number
   clk?: string
   marketDefinition?: unknown
+  tickLadder?: TickLadder
+  ladder?: LadderSource
+  /** Set when the ladder is virtual; those prices trail the direct ones by about this much. */
+  virtualLagMs?: number
   runners: Array<{
     selectionId: number
+    handicap?: number
     ltp?: number
     tv?: number
     batb: LadderLevel[]
     batl: LadderLevel[]
+    bdatb?: LadderLevel[]
+    bdatl?: LadderLevel[]
+    /** Projected BSP, near and far (`sp` preset). */
+    spn?: number
+    spf?: number
+    removal?: RunnerRemoval
   }>
 }
 
@@ -167,3 +222,41 @@ export function toSnapshot(state: MarketState): MarketSnapshot {
       })),
   }
 }
+
+/** Payload of the backend `betfair_market_update` event (conflated by Rust). */
+export type MarketUpdate = MarketSnapshot & {
+  kind: 'snapshot' | 'diff'
+  tv?: number
+}
+
+export function applyMarketUpdate(prev: MarketSnapshot | undefined, update: MarketUpdate): MarketSnapshot {
+  if (update.kind === 'snapshot' || !prev || prev.marketId !== update.marketId) {
+    return {
+      marketId: update.marketId,
+      publishTime: update.publishTime,
+      clk: update.clk,
+      marketDefinition: update.marketDefinition,
+      tickLadder: update.tickLadder,
+      ladder: update.ladder,
+      virtualLagMs: update.virtualLagMs,
+      runners: update.runners,
+    }
+  }
+
+  // Diffs carry only changed runners, each one complete.
+  const runners = new Map(prev.runners.map((r) => [runnerKey(r.selectionId, r.handicap), r]))
+  for (const r of update.runners) runners.set(runnerKey(r.selectionId, r.handicap), r)
+
+  return {
+    marketId: prev.marketId,
+    publishTime: update.publishTime ?? prev.publishTime,
+    clk: update.clk ?? prev.clk,
+    marketDefinition: update.marketDefinition ?? prev.marketDefinition,
+    tickLadder: update.tickLadder ?? prev.tickLadder,
+    ladder: update.ladder ?? prev.ladder,
+    virtualLagMs: update.virtualLagMs,
+    runners: Array.from(runners.values()).sort(
+      (a, b) => a.selectionId - b.selectionId || (a.handicap ?? 0) - (b.handicap ?? 0),
+    ),
+  }
+}
@@ -11,6 +11,8 @@ export type MarketCatalogueRunner = {
   runnerName: string
   handicap?: number
   sortPriority?: number
+  metadata?: Record<string, string | null>
+  status?: string
 }
 
 export type MarketCatalogue = {
@@ -27,6 +29,11 @@ export type MarketCatalogue = {
     venue?: string
     openDate?: string
   }
+  eventType?: { id?: string; name?: string }
+  competition?: { id?: string; name?: string }
+  description?: { marketType?: string; bettingType?: string; raceType?: string }
+  status?: string
+  inPlay?: boolean
 }
 
 export type ListMarketCatalogueResponse = MarketCatalogue[]